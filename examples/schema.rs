@@ -3,7 +3,9 @@ use std::fs::create_dir_all;
 
 use cosmwasm_schema::{export_schema, remove_schemas, schema_for};
 use prism_forge::msg::{
-    ConfigResponse, DepositResponse, ExecuteMsg, InstantiateMsg, LaunchConfig, QueryMsg,
+    ClaimTargetsResponse, ConfigResponse, Cw20HookMsg, DelegatesResponse, DepositResponse,
+    DutchAuctionResponse, ExecuteMsg, InstantiateMsg, LaunchConfig, LocksResponse,
+    PositionResponse, QueryMsg, ReferralResponse, TokenAccountingResponse,
 };
 
 fn main() {
@@ -15,7 +17,15 @@ fn main() {
     export_schema(&schema_for!(InstantiateMsg), &out_dir);
     export_schema(&schema_for!(ExecuteMsg), &out_dir);
     export_schema(&schema_for!(QueryMsg), &out_dir);
+    export_schema(&schema_for!(Cw20HookMsg), &out_dir);
     export_schema(&schema_for!(LaunchConfig), &out_dir);
     export_schema(&schema_for!(DepositResponse), &out_dir);
     export_schema(&schema_for!(ConfigResponse), &out_dir);
+    export_schema(&schema_for!(TokenAccountingResponse), &out_dir);
+    export_schema(&schema_for!(DutchAuctionResponse), &out_dir);
+    export_schema(&schema_for!(ReferralResponse), &out_dir);
+    export_schema(&schema_for!(LocksResponse), &out_dir);
+    export_schema(&schema_for!(PositionResponse), &out_dir);
+    export_schema(&schema_for!(DelegatesResponse), &out_dir);
+    export_schema(&schema_for!(ClaimTargetsResponse), &out_dir);
 }
@@ -1,25 +1,61 @@
+use crate::curve::MAX_EXPONENT;
 use crate::error::ContractError;
-use crate::state::{Config, DepositInfo, CONFIG, DEPOSITS, TOTAL_DEPOSIT};
+use crate::state::{
+    BatchAuction, Config, Delegation, DepositInfo, LockPosition, Position, ReferralInfo,
+    WeightedDeposit, ADMIN_WITHDRAWN, BATCH_AUCTION, BIDS, BID_LEVELS, BONDING_CURVE_SELLS,
+    BONDING_CURVE_SUPPLY, CLAIM_TARGETS, CONFIG, DELEGATES, DEPOSITS, DISTRIBUTION_CURSOR,
+    DISTRIBUTION_SKIPPED, DUTCH_AUCTION, LOCKS, NON_LOYAL_DEPOSIT, POSITIONS, POSITION_COUNT,
+    REFERRALS, TOTAL_CLAIMED_BONUS, TOTAL_CLAIMED_DEPOSIT, TOTAL_CLAIMED_LOYAL_DEPOSIT,
+    TOTAL_CLAIMED_REFERRAL_REWARDS, TOTAL_CLAIMED_REFERRED_DEPOSIT, TOTAL_CLAIMED_WEIGHT,
+    TOTAL_DEPOSIT, TOTAL_ESCROW, TOTAL_REFERRED_DEPOSIT, TOTAL_REFUNDED, TOTAL_SWEPT, TOTAL_TOKEN,
+    TOTAL_WEIGHT, WEIGHTED_DEPOSITS,
+};
 
 use crate::msg::{
-    ConfigResponse, DepositResponse, ExecuteMsg, InstantiateMsg, LaunchConfig, QueryMsg,
+    ClaimTargetsResponse, ConfigResponse, Curve, Cw20HookMsg, DelegateResponse, DelegatesResponse,
+    DepositResponse, DutchAuctionResponse, ExecuteMsg, InstantiateMsg, LaunchConfig,
+    LockPositionResponse, LocksResponse, Permission, PositionResponse, QueryMsg, ReferralResponse,
+    SaleMode, TokenAccountingResponse,
+};
+use crate::position_nft::{
+    instantiate_position_nft, mint_position_nft, query_position_owner, MintMsg,
+    POSITION_NFT_REPLY_ID,
 };
 use crate::querier::query_balance;
+use crate::receipt::{
+    instantiate_receipt_token, parse_instantiated_address, receipt_token_msg,
+    RECEIPT_TOKEN_REPLY_ID,
+};
+use crate::sale::{
+    bonus_multiplier, dutch_auction_price, funded_amount, is_weighted_pro_rata, mul_ceil,
+    price_ceil, tokens_at_price, Allocation, SaleResult,
+};
+use crate::staking::bond_hook;
 use cosmwasm_std::{
-    attr, entry_point, to_binary, Binary, CosmosMsg, Decimal, Deps, DepsMut, Env, MessageInfo,
-    Response, StdResult, Uint128, WasmMsg,
+    attr, entry_point, from_binary, to_binary, Addr, Api, Binary, CosmosMsg, Decimal, Deps,
+    DepsMut, Empty, Env, Fraction, MessageInfo, Order, Reply, Response, StdError, StdResult,
+    Storage, Uint128, WasmMsg,
 };
 use cw2::set_contract_version;
-use cw20::Cw20ExecuteMsg;
-use cw_asset::{Asset, AssetInfo};
+use cw20::{Cw20ExecuteMsg, Cw20ReceiveMsg, Expiration};
+use cw_asset::{Asset, AssetInfo, AssetInfoUnchecked};
+use cw_storage_plus::{Bound, PrimaryKey, U128Key};
+use sha2::{Digest, Sha256};
+use std::convert::TryInto;
 
 const CONTRACT_NAME: &str = "prism-forge";
 const CONTRACT_VERSION: &str = env!("CARGO_PKG_VERSION");
 
+// settings for pagination
+const DEFAULT_LIMIT: u32 = 10;
+const MAX_LIMIT: u32 = 30;
+// max share of the claimed tokens a depositor can tip to the relayer of its claim
+const MAX_CLAIM_TIP_PERCENT: u64 = 5;
+
 #[cfg_attr(not(feature = "library"), entry_point)]
 pub fn instantiate(
     deps: DepsMut,
-    _env: Env,
+    env: Env,
     _info: MessageInfo,
     msg: InstantiateMsg,
 ) -> Result<Response, ContractError> {
@@ -29,6 +65,12 @@ pub fn instantiate(
         return Err(ContractError::InvalidHostPortion {});
     }
 
+    if msg.receipt_token.is_some() && msg.position_nft.is_some() {
+        return Err(
+            StdError::generic_err("receipt tokens and position nfts can not be combined").into(),
+        );
+    }
+
     let cfg = Config {
         operator: deps.api.addr_validate(&msg.operator)?,
         receiver: deps.api.addr_validate(&msg.receiver)?,
@@ -38,10 +80,72 @@ pub fn instantiate(
         tokens_released: false,
         host_portion: msg.host_portion,
         host_portion_receiver: deps.api.addr_validate(&msg.host_portion_receiver)?,
+        unclaimed_tokens_receiver: deps.api.addr_validate(&msg.unclaimed_tokens_receiver)?,
+        tokens_swept: false,
+        receipt_token: None,
+        position_nft: None,
+        staking_contract: msg
+            .staking_contract
+            .map(|addr| deps.api.addr_validate(&addr))
+            .transpose()?,
     };
     TOTAL_DEPOSIT.save(deps.storage, &Uint128::zero())?;
+    TOTAL_TOKEN.save(deps.storage, &Uint128::zero())?;
+    TOTAL_CLAIMED_DEPOSIT.save(deps.storage, &Uint128::zero())?;
+    TOTAL_WEIGHT.save(deps.storage, &Uint128::zero())?;
+    TOTAL_CLAIMED_WEIGHT.save(deps.storage, &Uint128::zero())?;
+    NON_LOYAL_DEPOSIT.save(deps.storage, &Uint128::zero())?;
+    TOTAL_CLAIMED_LOYAL_DEPOSIT.save(deps.storage, &Uint128::zero())?;
+    TOTAL_CLAIMED_BONUS.save(deps.storage, &Uint128::zero())?;
+    TOTAL_REFERRED_DEPOSIT.save(deps.storage, &Uint128::zero())?;
+    TOTAL_CLAIMED_REFERRED_DEPOSIT.save(deps.storage, &Uint128::zero())?;
+    TOTAL_CLAIMED_REFERRAL_REWARDS.save(deps.storage, &Uint128::zero())?;
+    TOTAL_SWEPT.save(deps.storage, &Uint128::zero())?;
+    TOTAL_REFUNDED.save(deps.storage, &Uint128::zero())?;
+    ADMIN_WITHDRAWN.save(deps.storage, &Uint128::zero())?;
+    TOTAL_ESCROW.save(deps.storage, &Uint128::zero())?;
+    CONFIG.save(deps.storage, &cfg)?;
+
+    // the receipt token and position nft addresses are saved by the reply
+    let mut res = Response::new();
+    if let Some(receipt_token) = msg.receipt_token {
+        res = res.add_submessage(instantiate_receipt_token(
+            &receipt_token,
+            &env.contract.address,
+        )?);
+    }
+    if let Some(position_nft) = msg.position_nft {
+        res = res.add_submessage(instantiate_position_nft(
+            &position_nft,
+            &env.contract.address,
+        )?);
+    }
+
+    Ok(res)
+}
+
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn reply(deps: DepsMut, _env: Env, msg: Reply) -> Result<Response, ContractError> {
+    let res = msg.result.into_result().map_err(StdError::generic_err)?;
+    let contract_addr = parse_instantiated_address(&res.events)
+        .ok_or_else(|| StdError::generic_err("instantiated contract address not found"))?;
+    let contract_addr = deps.api.addr_validate(&contract_addr)?;
+
+    let mut cfg = CONFIG.load(deps.storage)?;
+    let key = match msg.id {
+        RECEIPT_TOKEN_REPLY_ID => {
+            cfg.receipt_token = Some(contract_addr.clone());
+            "receipt_token"
+        }
+        POSITION_NFT_REPLY_ID => {
+            cfg.position_nft = Some(contract_addr.clone());
+            "position_nft"
+        }
+        id => return Err(StdError::generic_err(format!("unknown reply id {}", id)).into()),
+    };
     CONFIG.save(deps.storage, &cfg)?;
-    Ok(Response::new())
+
+    Ok(Response::new().add_attribute(key, contract_addr))
 }
 
 #[cfg_attr(not(feature = "library"), entry_point)]
@@ -52,14 +156,110 @@ pub fn execute(
     msg: ExecuteMsg,
 ) -> Result<Response, ContractError> {
     match msg {
-        ExecuteMsg::Deposit {} => deposit(deps, env, info),
-        ExecuteMsg::Withdraw { amount } => withdraw(deps, env, info, amount),
-        ExecuteMsg::WithdrawTokens {} => withdraw_tokens(deps, env, info),
+        ExecuteMsg::Receive(msg) => receive_cw20(deps, env, info, msg),
+        ExecuteMsg::Deposit {
+            max_price,
+            referrer,
+            lock_duration,
+            recipient,
+        } => deposit(
+            deps,
+            env,
+            info,
+            max_price,
+            referrer,
+            lock_duration,
+            recipient,
+        ),
+        ExecuteMsg::Withdraw {
+            amount,
+            recipient,
+            depositor,
+        } => withdraw(deps, env, info, amount, recipient, depositor),
+        ExecuteMsg::WithdrawTokens {
+            recipient,
+            depositor,
+            contract,
+            msg,
+            stake,
+        } => {
+            let destination = match (contract, msg, stake) {
+                (None, None, false) => ClaimDestination::Recipient,
+                (None, None, true) => ClaimDestination::Stake,
+                (Some(contract), Some(msg), false) => ClaimDestination::Hook {
+                    contract: deps.api.addr_validate(&contract)?,
+                    msg,
+                },
+                (_, _, true) => {
+                    return Err(ContractError::InvalidWithdrawTokens {
+                        reason: "can not stake and send the tokens to a contract at once"
+                            .to_string(),
+                    })
+                }
+                _ => {
+                    return Err(ContractError::InvalidWithdrawTokens {
+                        reason: "contract and msg must be set together".to_string(),
+                    })
+                }
+            };
+            withdraw_tokens(deps, env, info, recipient, depositor, destination)
+        }
+        ExecuteMsg::TransferPosition {
+            recipient,
+            amount,
+            depositor,
+        } => transfer_position(deps, env, info, recipient, amount, depositor),
+        ExecuteMsg::Authorize {
+            delegate,
+            permissions,
+            expires,
+            transfer_recipients,
+        } => authorize(
+            deps,
+            env,
+            info,
+            delegate,
+            permissions,
+            expires,
+            transfer_recipients,
+        ),
+        ExecuteMsg::Revoke { delegate } => revoke(deps, info, delegate),
+        ExecuteMsg::SetClaimTip { tip } => set_claim_tip(deps, info, tip),
+        ExecuteMsg::ClaimFor { address } => claim_for(deps, env, info, address),
+        ExecuteMsg::WithdrawPosition {
+            token_id,
+            amount,
+            recipient,
+        } => withdraw_position(deps, env, info, token_id, amount, recipient),
+        ExecuteMsg::ClaimPosition {
+            token_id,
+            recipient,
+        } => claim_position(deps, env, info, token_id, recipient),
+        ExecuteMsg::ClaimReferralRewards {} => claim_referral_rewards(deps, env, info),
+        ExecuteMsg::WithdrawAndClaim {} => withdraw_and_claim(deps, env, info),
+        ExecuteMsg::ClaimRefund {} => claim_refund(deps, info),
+        ExecuteMsg::CommitBid { commitment } => commit_bid(deps, env, info, commitment),
+        ExecuteMsg::RevealBid { amount, salt } => reveal_bid(deps, env, info, amount, salt),
+        ExecuteMsg::ReclaimEscrow {} => reclaim_escrow(deps, env, info),
         ExecuteMsg::PostInitialize { launch_config } => {
             post_initialize(deps, env, info, launch_config)
         }
         ExecuteMsg::AdminWithdraw {} => admin_withdraw(deps, env, info),
         ExecuteMsg::ReleaseTokens {} => release_tokens(deps, env, info),
+        ExecuteMsg::DistributeTokens { limit } => distribute_tokens(deps, env, limit),
+        ExecuteMsg::Settle { limit } => settle(deps, env, limit),
+        ExecuteMsg::SweepUnclaimedTokens {} => sweep_unclaimed_tokens(deps, env, info),
+        ExecuteMsg::SweepDust {} => sweep_dust(deps, env, info),
+        ExecuteMsg::RecoverStrayFunds { recipient } => {
+            recover_stray_funds(deps, env, info, recipient)
+        }
+        ExecuteMsg::RecoverAsset {
+            asset_info,
+            recipient,
+        } => recover_asset(deps, env, info, asset_info, recipient),
+        ExecuteMsg::UpdateClaimTargets { add, remove } => {
+            update_claim_targets(deps, info, add, remove)
+        }
     }
 }
 
@@ -95,6 +295,120 @@ pub fn post_initialize(
         return Err(ContractError::InvalidLaunchConfig {});
     }
 
+    // claim deadline must leave time to claim after phase 2
+    if let Some(claim_deadline) = launch_config.claim_deadline {
+        if claim_deadline <= launch_config.phase2_end {
+            return Err(ContractError::InvalidLaunchConfig {});
+        }
+    }
+
+    // bonus tiers are sorted and only give a bonus on pro-rata sales
+    if !launch_config.bonus_tiers.is_empty() {
+        if launch_config.sale_mode != (SaleMode::ProRata {}) {
+            return Err(ContractError::InvalidLaunchConfig {});
+        }
+        let mut last_until = launch_config.phase1_start;
+        for tier in launch_config.bonus_tiers.iter() {
+            if tier.until_timestamp <= last_until || tier.multiplier < Decimal::one() {
+                return Err(ContractError::InvalidLaunchConfig {});
+            }
+            last_until = tier.until_timestamp;
+        }
+    }
+
+    // locks boost the allocation on pro-rata sales, each duration has a single multiplier and
+    // must end before the tokens held by the lock can be swept
+    if !launch_config.lock_options.is_empty() {
+        if launch_config.sale_mode != (SaleMode::ProRata {}) {
+            return Err(ContractError::InvalidLaunchConfig {});
+        }
+        for (i, option) in launch_config.lock_options.iter().enumerate() {
+            let unlock_time = launch_config.phase2_end + option.duration;
+            if option.duration == 0
+                || option.multiplier < Decimal::one()
+                || launch_config.claim_deadline.unwrap_or(u64::MAX) < unlock_time
+                || launch_config.lock_options[..i]
+                    .iter()
+                    .any(|other| other.duration == option.duration)
+            {
+                return Err(ContractError::InvalidLaunchConfig {});
+            }
+        }
+    }
+
+    // the bonus is shared pro-rata to the deposits, so every deposit must buy tokens at the same
+    // rate and keep its amount, which bids, refunds and curve sell backs do not
+    if launch_config.loyalty_bonus.is_some()
+        && !matches!(
+            launch_config.sale_mode,
+            SaleMode::ProRata {} | SaleMode::TimeWeighted {}
+        )
+    {
+        return Err(ContractError::InvalidLaunchConfig {});
+    }
+
+    // referral rewards are set aside from the tokens split between the depositors
+    if let Some(referral_portion) = launch_config.referral_portion {
+        if !matches!(
+            launch_config.sale_mode,
+            SaleMode::ProRata {} | SaleMode::TimeWeighted {}
+        ) || referral_portion.is_zero()
+            || referral_portion >= Decimal::one()
+        {
+            return Err(ContractError::InvalidLaunchConfig {});
+        }
+    }
+
+    // receipts and position nfts are claimed outside of the deposits of each address, so every
+    // unit of deposit must get the same allocation
+    if (cfg.receipt_token.is_some() || cfg.position_nft.is_some())
+        && (launch_config.sale_mode != (SaleMode::ProRata {})
+            || is_weighted_pro_rata(&launch_config)
+            || launch_config.loyalty_bonus.is_some()
+            || launch_config.referral_portion.is_some())
+    {
+        return Err(ContractError::InvalidLaunchConfig {});
+    }
+
+    match launch_config.sale_mode {
+        SaleMode::ProRata {}
+        | SaleMode::BatchAuction {}
+        | SaleMode::SealedBid {}
+        | SaleMode::TimeWeighted {} => {}
+        SaleMode::BondingCurve { ref curve, spread } => {
+            let (base_price, exponent) = match *curve {
+                Curve::Linear { base_price, .. } => (base_price, 1),
+                Curve::Polynomial {
+                    base_price,
+                    exponent,
+                    ..
+                } => (base_price, exponent),
+            };
+            // the first tokens can not be free and the spread can not take all the proceeds
+            if base_price.is_zero()
+                || exponent == 0
+                || exponent > MAX_EXPONENT
+                || spread >= Decimal::one()
+            {
+                return Err(ContractError::InvalidLaunchConfig {});
+            }
+        }
+        SaleMode::FixedPrice { price } => {
+            if price.is_zero() {
+                return Err(ContractError::InvalidLaunchConfig {});
+            }
+        }
+        SaleMode::DutchAuction {
+            start_price,
+            floor_price,
+        } => {
+            // the price must fall during phase 1 and never reach 0
+            if floor_price.is_zero() || start_price <= floor_price {
+                return Err(ContractError::InvalidLaunchConfig {});
+            }
+        }
+    }
+
     cfg.launch_config = Some(launch_config.clone());
 
     CONFIG.save(deps.storage, &cfg)?;
@@ -105,14 +419,22 @@ pub fn post_initialize(
             msg: to_binary(&Cw20ExecuteMsg::TransferFrom {
                 owner: info.sender.to_string(),
                 recipient: env.contract.address.to_string(),
-                amount: launch_config.amount,
+                amount: funded_amount(&launch_config),
             })?,
             funds: vec![],
         })),
     )
 }
 
-pub fn deposit(deps: DepsMut, env: Env, info: MessageInfo) -> Result<Response, ContractError> {
+pub fn deposit(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    max_price: Option<Decimal>,
+    referrer: Option<String>,
+    lock_duration: Option<u64>,
+    recipient: Option<String>,
+) -> Result<Response, ContractError> {
     let cfg = CONFIG.load(deps.storage)?;
     let launch_cfg = cfg.launch_config.unwrap();
 
@@ -140,300 +462,3148 @@ pub fn deposit(deps: DepsMut, env: Env, info: MessageInfo) -> Result<Response, C
         });
     }
 
-    DEPOSITS.update(
-        deps.storage,
-        &info.sender,
-        |curr| -> StdResult<DepositInfo> {
-            let mut deposit = curr.unwrap_or_default();
-            deposit.amount += coin.amount;
-
-            Ok(deposit)
-        },
-    )?;
-    TOTAL_DEPOSIT.update(deps.storage, |curr| -> StdResult<Uint128> {
-        Ok(curr + coin.amount)
-    })?;
-
-    Ok(Response::new().add_attribute("action", "deposit"))
-}
-
-pub fn withdraw(
-    deps: DepsMut,
-    env: Env,
-    info: MessageInfo,
-    amount: Option<Uint128>,
-) -> Result<Response, ContractError> {
-    let cfg = CONFIG.load(deps.storage)?;
-    let launch_config = cfg.launch_config.unwrap();
-    let current_time = env.block.time.seconds();
-
-    if current_time >= launch_config.phase2_end {
-        return Err(ContractError::InvalidWithdraw {
-            reason: "withdraw period is over".to_string(),
+    if launch_cfg.sale_mode == (SaleMode::SealedBid {}) {
+        return Err(ContractError::InvalidDeposit {
+            reason: "sealed bid launches only take committed bids".to_string(),
         });
     }
-    let mut deposit_info = DEPOSITS
-        .load(deps.storage, &info.sender)
-        .unwrap_or_default();
 
-    if deposit_info.amount == Uint128::zero() {
-        return Err(ContractError::InvalidWithdraw {
-            reason: "no funds available to withdraw".to_string(),
+    let is_batch_auction = launch_cfg.sale_mode == SaleMode::BatchAuction {};
+    if is_batch_auction && max_price.is_none() {
+        return Err(ContractError::InvalidDeposit {
+            reason: "batch auction bids require a max price".to_string(),
+        });
+    }
+    if !is_batch_auction && max_price.is_some() {
+        return Err(ContractError::InvalidDeposit {
+            reason: "max price is only allowed on batch auctions".to_string(),
         });
     }
 
-    let withdrawable_amount = if current_time > launch_config.phase2_start {
-        // check if user already withdrew on phase 2
-        if deposit_info.withdrew_phase2 {
-            return Err(ContractError::InvalidWithdraw {
-                reason: "a withdraw was already executed on phase 2".to_string(),
+    // the position is credited to the recipient, the sender only pays for it
+    let depositor = match recipient {
+        Some(recipient) => deps.api.addr_validate(&recipient)?,
+        None => info.sender.clone(),
+    };
+
+    // the referrer is recorded on the first deposit and can not change afterwards
+    let referrer = referrer
+        .map(|referrer| deps.api.addr_validate(&referrer))
+        .transpose()?;
+    if referrer.is_some() {
+        if launch_cfg.referral_portion.is_none() {
+            return Err(ContractError::InvalidDeposit {
+                reason: "launch has no referral rewards".to_string(),
             });
         }
+        if referrer.as_ref() == Some(&depositor) {
+            return Err(ContractError::InvalidDeposit {
+                reason: "can not refer yourself".to_string(),
+            });
+        }
+        if let Some(deposit_info) = DEPOSITS.may_load(deps.storage, &depositor)? {
+            if deposit_info.referrer != referrer {
+                return Err(ContractError::InvalidDeposit {
+                    reason: "referrer can not be changed after the first deposit".to_string(),
+                });
+            }
+        }
+    }
 
-        let current_slot =
-            (launch_config.phase2_end - current_time) / launch_config.phase2_slot_period;
-        let total_slots = (launch_config.phase2_end - launch_config.phase2_start)
-            / launch_config.phase2_slot_period;
-        let withdrawable_portion =
-            Decimal::from_ratio(current_slot + 1u64, total_slots).min(Decimal::one());
+    // a deposit for another address can only add to what the recipient chose itself, it can not
+    // lock the recipient's position, set its referrer or open a bid at its own price
+    if depositor != info.sender {
+        let recipient_info = DEPOSITS
+            .may_load(deps.storage, &depositor)?
+            .unwrap_or_default();
+        if lock_duration.is_some() {
+            return Err(ContractError::InvalidDeposit {
+                reason: "can not lock a deposit for another address".to_string(),
+            });
+        }
+        if referrer.is_some() && !DEPOSITS.has(deps.storage, &depositor) {
+            return Err(ContractError::InvalidDeposit {
+                reason: "can not set the referrer of another address".to_string(),
+            });
+        }
+        if max_price.is_some() && recipient_info.amount.is_zero() {
+            return Err(ContractError::InvalidDeposit {
+                reason: "can not open a bid for another address".to_string(),
+            });
+        }
+    }
 
-        // on phase 2 can only withraw one time, so flag the position
-        deposit_info.withdrew_phase2 = true;
+    let lock_option = lock_duration
+        .map(|duration| {
+            launch_cfg
+                .lock_options
+                .iter()
+                .find(|option| option.duration == duration)
+                .ok_or_else(|| ContractError::InvalidDeposit {
+                    reason: "lock duration is not available".to_string(),
+                })
+        })
+        .transpose()?;
+
+    let mut deposit_amount = coin.amount;
+    let mut tokens_bought = Uint128::zero();
+    let mut weight = Uint128::zero();
+    let mut msgs: Vec<CosmosMsg> = vec![];
+    match launch_cfg.sale_mode {
+        SaleMode::DutchAuction {
+            start_price,
+            floor_price,
+        } => {
+            let mut auction = DUTCH_AUCTION.may_load(deps.storage)?.unwrap_or_default();
+            let remaining_supply = launch_cfg.amount - auction.tokens_sold;
+            if remaining_supply.is_zero() {
+                return Err(ContractError::InvalidDeposit {
+                    reason: "tokens are sold out".to_string(),
+                });
+            }
 
-        deposit_info.amount * withdrawable_portion
-    } else {
-        deposit_info.amount
-    };
+            let price = dutch_auction_price(
+                &launch_cfg,
+                start_price,
+                floor_price,
+                env.block.time.seconds(),
+            );
+            tokens_bought = tokens_at_price(coin.amount, price);
+            if tokens_bought > remaining_supply {
+                // the deposit selling out the supply only pays for the remaining tokens
+                tokens_bought = remaining_supply;
+                deposit_amount = mul_ceil(remaining_supply, price);
+
+                let excess_asset = Asset {
+                    info: AssetInfo::Native(cfg.base_denom.clone()),
+                    amount: coin.amount - deposit_amount,
+                };
+                msgs.push(excess_asset.transfer_msg(info.sender.clone())?);
+            }
+            if tokens_bought.is_zero() {
+                return Err(ContractError::InvalidDeposit {
+                    reason: "deposit is too small to buy tokens at the current price".to_string(),
+                });
+            }
 
-    let withdraw_amount = match amount {
-        None => withdrawable_amount,
-        Some(requested_amount) => {
-            if requested_amount > withdrawable_amount {
-                return Err(ContractError::InvalidWithdraw {
-                    reason: format!(
-                        "can not withdraw more than current withdrawable amount ({})",
-                        withdrawable_amount
-                    ),
+            // prices only fall, so the last deposit sets the clearing price
+            auction.tokens_sold += tokens_bought;
+            auction.clearing_price = price;
+            DUTCH_AUCTION.save(deps.storage, &auction)?;
+        }
+        SaleMode::BondingCurve { ref curve, .. } => {
+            let supply = BONDING_CURVE_SUPPLY
+                .may_load(deps.storage)?
+                .unwrap_or_default();
+            if supply == launch_cfg.amount {
+                return Err(ContractError::InvalidDeposit {
+                    reason: "tokens are sold out".to_string(),
                 });
             }
-            if requested_amount == Uint128::zero() {
-                return Err(ContractError::InvalidWithdraw {
-                    reason: "withdraw amount must be bigger than 0".to_string(),
+
+            let (tokens, cost) = curve.tokens_for_funds(launch_cfg.amount, supply, coin.amount);
+            if tokens.is_zero() {
+                return Err(ContractError::InvalidDeposit {
+                    reason: "deposit is too small to buy tokens at the current price".to_string(),
                 });
             }
 
-            requested_amount
+            // only the area under the curve is paid, the rest goes back
+            tokens_bought = tokens;
+            deposit_amount = cost;
+            if cost < coin.amount {
+                let excess_asset = Asset {
+                    info: AssetInfo::Native(cfg.base_denom.clone()),
+                    amount: coin.amount - cost,
+                };
+                msgs.push(excess_asset.transfer_msg(info.sender.clone())?);
+            }
+
+            BONDING_CURVE_SUPPLY.save(deps.storage, &(supply + tokens))?;
         }
-    };
+        SaleMode::TimeWeighted {} => {
+            // earlier deposits weigh more, a deposit right before phase 2 barely counts
+            let time_remaining = launch_cfg.phase2_start - env.block.time.seconds();
+            weight = coin.amount * Uint128::from(time_remaining);
+
+            let mut weighted_deposits = WEIGHTED_DEPOSITS
+                .may_load(deps.storage, &depositor)?
+                .unwrap_or_default();
+            match weighted_deposits.last_mut() {
+                Some(last) if last.time_remaining == time_remaining => last.amount += coin.amount,
+                _ => weighted_deposits.push(WeightedDeposit {
+                    amount: coin.amount,
+                    time_remaining,
+                }),
+            }
+            WEIGHTED_DEPOSITS.save(deps.storage, &depositor, &weighted_deposits)?;
+        }
+        SaleMode::ProRata {} if is_weighted_pro_rata(&launch_cfg) => {
+            weight = coin.amount * bonus_multiplier(&launch_cfg, env.block.time.seconds());
+
+            // every lock is a separate position, its amount can no longer be withdrawn on phase 2
+            if let Some(lock_option) = lock_option {
+                weight = weight * lock_option.multiplier;
+
+                let mut locks = LOCKS
+                    .may_load(deps.storage, &depositor)?
+                    .unwrap_or_default();
+                locks.push(LockPosition {
+                    amount: coin.amount,
+                    multiplier: lock_option.multiplier,
+                    weight,
+                    unlock_time: launch_cfg.phase2_end + lock_option.duration,
+                });
+                LOCKS.save(deps.storage, &depositor, &locks)?;
+            }
+        }
+        SaleMode::BatchAuction {} => {
+            let max_price = max_price.unwrap();
+            if max_price.is_zero() {
+                return Err(ContractError::InvalidDeposit {
+                    reason: "max price must be bigger than 0".to_string(),
+                });
+            }
 
-    // update user deposit amount
-    deposit_info.amount -= withdraw_amount;
+            let deposit_info = DEPOSITS
+                .may_load(deps.storage, &depositor)?
+                .unwrap_or_default();
+            if !deposit_info.amount.is_zero() && deposit_info.max_price != Some(max_price) {
+                return Err(ContractError::InvalidDeposit {
+                    reason: "max price of an open bid can not be changed".to_string(),
+                });
+            }
 
-    DEPOSITS.save(deps.storage, &info.sender, &deposit_info)?;
+            update_bid(
+                deps.storage,
+                &depositor,
+                max_price,
+                deposit_info.amount,
+                deposit_info.amount + coin.amount,
+            )?;
+        }
+        _ => {}
+    }
 
+    // every deposit opens a new position, held by the owner of its nft
+    if let Some(position_nft) = &cfg.position_nft {
+        let token_id = POSITION_COUNT.may_load(deps.storage)?.unwrap_or_default() + 1;
+        POSITION_COUNT.save(deps.storage, &token_id)?;
+
+        let token_id = token_id.to_string();
+        let position = Position {
+            amount: deposit_amount,
+            deposit_time: env.block.time.seconds(),
+            withdrew_phase2: false,
+            tokens_claimed: false,
+        };
+        POSITIONS.save(deps.storage, &token_id, &position)?;
+        TOTAL_DEPOSIT.update(deps.storage, |curr| -> StdResult<Uint128> {
+            Ok(curr + deposit_amount)
+        })?;
+
+        msgs.push(mint_position_nft(
+            position_nft,
+            MintMsg {
+                token_id: token_id.clone(),
+                owner: depositor.to_string(),
+                name: format!("Position #{}", token_id),
+                description: Some(format!(
+                    "{}{} deposited at {}",
+                    position.amount, cfg.base_denom, position.deposit_time
+                )),
+                image: None,
+            },
+        )?);
+
+        return Ok(Response::new().add_messages(msgs).add_attributes(vec![
+            attr("action", "deposit"),
+            attr("depositor", depositor),
+            attr("sender", info.sender),
+            attr("token_id", token_id),
+        ]));
+    }
+
+    // the receipts are the position, so the deposit is only accounted in their balances
+    if let Some(receipt_token) = &cfg.receipt_token {
+        TOTAL_DEPOSIT.update(deps.storage, |curr| -> StdResult<Uint128> {
+            Ok(curr + deposit_amount)
+        })?;
+        msgs.push(receipt_token_msg(
+            receipt_token,
+            Cw20ExecuteMsg::Mint {
+                recipient: depositor.to_string(),
+                amount: deposit_amount,
+            },
+        )?);
+
+        return Ok(Response::new().add_messages(msgs).add_attributes(vec![
+            attr("action", "deposit"),
+            attr("depositor", depositor),
+            attr("sender", info.sender),
+        ]));
+    }
+
+    let deposit_info =
+        DEPOSITS.update(deps.storage, &depositor, |curr| -> StdResult<DepositInfo> {
+            let mut deposit = curr.unwrap_or_default();
+            deposit.amount += deposit_amount;
+            deposit.tokens_bought += tokens_bought;
+            deposit.weight += weight;
+            if max_price.is_some() {
+                deposit.max_price = max_price;
+            }
+            if referrer.is_some() {
+                deposit.referrer = referrer;
+            }
+            if lock_option.is_some() {
+                deposit.locked_amount += deposit_amount;
+                deposit.locked_weight += weight;
+            }
+
+            Ok(deposit)
+        })?;
+    if let Some(referrer) = deposit_info.referrer {
+        update_referral(
+            deps.storage,
+            &referrer,
+            deposit_info.amount - deposit_amount,
+            deposit_info.amount,
+        )?;
+    }
     TOTAL_DEPOSIT.update(deps.storage, |curr| -> StdResult<Uint128> {
-        Ok(curr - withdraw_amount)
+        Ok(curr + deposit_amount)
     })?;
+    if !weight.is_zero() {
+        TOTAL_WEIGHT.update(deps.storage, |curr| -> StdResult<Uint128> {
+            Ok(curr + weight)
+        })?;
+    }
 
-    let withdraw_asset = Asset {
-        info: AssetInfo::Native(cfg.base_denom),
-        amount: withdraw_amount,
-    };
-    let msg = withdraw_asset.transfer_msg(info.sender)?;
-
-    Ok(Response::new().add_message(msg).add_attributes(vec![
-        attr("action", "withdraw"),
-        attr("withdraw_amount", withdraw_asset.amount.to_string()),
+    Ok(Response::new().add_messages(msgs).add_attributes(vec![
+        attr("action", "deposit"),
+        attr("depositor", depositor),
+        attr("sender", info.sender),
     ]))
 }
 
-pub fn withdraw_tokens(
+pub fn commit_bid(
     deps: DepsMut,
     env: Env,
     info: MessageInfo,
+    commitment: Binary,
 ) -> Result<Response, ContractError> {
     let cfg = CONFIG.load(deps.storage)?;
     let launch_cfg = cfg.launch_config.unwrap();
 
-    if env.block.time.seconds() < launch_cfg.phase2_end || !cfg.tokens_released {
-        return Err(ContractError::InvalidWithdrawTokens {
-            reason: "cannot withdraw tokens yet".to_string(),
+    if launch_cfg.sale_mode != (SaleMode::SealedBid {}) {
+        return Err(ContractError::InvalidCommitBid {
+            reason: "launch does not take sealed bids".to_string(),
         });
     }
 
-    let mut deposit_info = DEPOSITS.load(deps.storage, &info.sender).map_err(|_| {
-        ContractError::InvalidWithdrawTokens {
-            reason: "deposit information not found".to_string(),
-        }
-    })?;
-    if deposit_info.tokens_claimed {
-        return Err(ContractError::InvalidWithdrawTokens {
-            reason: "tokens were already claimed".to_string(),
+    if env.block.time.seconds() < launch_cfg.phase1_start {
+        return Err(ContractError::InvalidCommitBid {
+            reason: "commit period did not start yet".to_string(),
         });
     }
 
-    let deposit_total = TOTAL_DEPOSIT.load(deps.storage)?;
-    let amount = launch_cfg
-        .amount
-        .multiply_ratio(deposit_info.amount, deposit_total);
-    if amount == Uint128::zero() {
-        return Err(ContractError::InvalidWithdrawTokens {
-            reason: "no tokens available for withdraw".to_string(),
+    if env.block.time.seconds() >= launch_cfg.phase2_start {
+        return Err(ContractError::InvalidCommitBid {
+            reason: "commit period is over".to_string(),
         });
     }
 
-    // update claimed flag, we don't delete storage to keep the record
-    deposit_info.tokens_claimed = true;
-
-    DEPOSITS.save(deps.storage, &info.sender, &deposit_info)?;
-    let to_send = Asset {
-        info: AssetInfo::Cw20(cfg.token),
-        amount,
-    };
-    Ok(Response::new()
-        .add_message(to_send.transfer_msg(&info.sender)?)
-        .add_attributes(vec![
-            attr("action", "withdraw_tokens"),
-            attr("withdraw_amount", amount.to_string()),
-        ]))
-}
-
-pub fn release_tokens(
-    deps: DepsMut,
-    env: Env,
-    info: MessageInfo,
-) -> Result<Response, ContractError> {
-    let mut cfg = CONFIG.load(deps.storage)?;
-    let launch_cfg = cfg.launch_config.clone().unwrap();
-
-    if info.sender != cfg.operator {
-        return Err(ContractError::Unauthorized {});
+    if info.funds.len() != 1 {
+        return Err(ContractError::InvalidCommitBid {
+            reason: "requires 1 coin escrowed".to_string(),
+        });
+    }
+    let coin = &info.funds[0];
+    if coin.denom != cfg.base_denom || coin.amount == Uint128::zero() {
+        return Err(ContractError::InvalidCommitBid {
+            reason: format!("requires {} and positive amount", cfg.base_denom),
+        });
     }
 
-    if env.block.time.seconds() < launch_cfg.phase2_end {
-        return Err(ContractError::InvalidReleaseTokens {
-            reason: "cannot release tokens yet".to_string(),
+    if commitment.len() != 32 {
+        return Err(ContractError::InvalidCommitBid {
+            reason: "commitment must be a sha256 hash".to_string(),
         });
     }
 
-    if cfg.tokens_released {
-        return Err(ContractError::InvalidReleaseTokens {
-            reason: "tokens are already released".to_string(),
+    let mut deposit_info = DEPOSITS
+        .may_load(deps.storage, &info.sender)?
+        .unwrap_or_default();
+    if deposit_info.commitment.is_some() {
+        return Err(ContractError::InvalidCommitBid {
+            reason: "a bid was already committed".to_string(),
         });
     }
 
-    cfg.tokens_released = true;
+    deposit_info.commitment = Some(commitment);
+    deposit_info.escrow = coin.amount;
+    DEPOSITS.save(deps.storage, &info.sender, &deposit_info)?;
 
-    CONFIG.save(deps.storage, &cfg)?;
+    TOTAL_ESCROW.update(deps.storage, |curr| -> StdResult<Uint128> {
+        Ok(curr + coin.amount)
+    })?;
 
-    Ok(Response::new().add_attribute("action", "release_tokens"))
+    Ok(Response::new().add_attribute("action", "commit_bid"))
 }
 
-pub fn admin_withdraw(
+pub fn reveal_bid(
     deps: DepsMut,
     env: Env,
     info: MessageInfo,
+    amount: Uint128,
+    salt: String,
 ) -> Result<Response, ContractError> {
     let cfg = CONFIG.load(deps.storage)?;
     let launch_cfg = cfg.launch_config.unwrap();
 
-    if info.sender != cfg.operator {
-        return Err(ContractError::Unauthorized {});
+    if env.block.time.seconds() < launch_cfg.phase2_start {
+        return Err(ContractError::InvalidRevealBid {
+            reason: "reveal period did not start yet".to_string(),
+        });
     }
 
-    if env.block.time.seconds() < launch_cfg.phase2_end {
-        return Err(ContractError::InvalidAdminWithdraw {
-            reason: "cannot withdraw funds yet".to_string(),
+    if env.block.time.seconds() >= launch_cfg.phase2_end {
+        return Err(ContractError::InvalidRevealBid {
+            reason: "reveal period is over".to_string(),
         });
     }
 
-    let balance = query_balance(&deps.querier, env.contract.address, cfg.base_denom.clone())?;
-    let host_portion = balance * cfg.host_portion;
-
-    let base_denom_info = AssetInfo::Native(cfg.base_denom);
-    let host_withdraw_asset = Asset {
-        info: base_denom_info.clone(),
-        amount: host_portion,
-    };
-    let admin_withdraw_asset = Asset {
-        info: base_denom_info,
-        amount: balance - host_portion,
+    let mut deposit_info = DEPOSITS
+        .may_load(deps.storage, &info.sender)?
+        .unwrap_or_default();
+    let commitment = match deposit_info.commitment.take() {
+        Some(commitment) => commitment,
+        None => {
+            return Err(ContractError::InvalidRevealBid {
+                reason: "no committed bid found".to_string(),
+            })
+        }
     };
 
-    let mut msgs: Vec<CosmosMsg> = vec![];
-    // because host_portion could be 0.0, check
-    if !host_withdraw_asset.amount.is_zero() {
-        msgs.push(host_withdraw_asset.transfer_msg(cfg.host_portion_receiver)?);
+    if bid_commitment(&info.sender, amount, &salt) != commitment {
+        return Err(ContractError::InvalidRevealBid {
+            reason: "bid does not match the commitment".to_string(),
+        });
     }
 
-    // send remaining amount to receiver
-    msgs.push(admin_withdraw_asset.transfer_msg(cfg.receiver)?);
+    if amount.is_zero() || amount > deposit_info.escrow {
+        return Err(ContractError::InvalidRevealBid {
+            reason: format!(
+                "bid amount must be positive and not above the escrow ({})",
+                deposit_info.escrow
+            ),
+        });
+    }
+
+    // the revealed amount becomes a regular deposit, the rest of the escrow goes back
+    let escrow = deposit_info.escrow;
+    deposit_info.amount += amount;
+    deposit_info.escrow = Uint128::zero();
+    DEPOSITS.save(deps.storage, &info.sender, &deposit_info)?;
+
+    TOTAL_ESCROW.update(deps.storage, |curr| -> StdResult<Uint128> {
+        Ok(curr - escrow)
+    })?;
+    TOTAL_DEPOSIT.update(deps.storage, |curr| -> StdResult<Uint128> {
+        Ok(curr + amount)
+    })?;
+
+    let mut msgs: Vec<CosmosMsg> = vec![];
+    if escrow > amount {
+        let excess_asset = Asset {
+            info: AssetInfo::Native(cfg.base_denom),
+            amount: escrow - amount,
+        };
+        msgs.push(excess_asset.transfer_msg(info.sender)?);
+    }
 
     Ok(Response::new().add_messages(msgs).add_attributes(vec![
-        attr("action", "admin_withdraw"),
-        attr("total_withdraw_amount", balance.to_string()),
-        attr("host_amount", host_withdraw_asset.amount.to_string()),
-        attr("remaining_amount", admin_withdraw_asset.amount.to_string()),
+        attr("action", "reveal_bid"),
+        attr("bid_amount", amount.to_string()),
     ]))
 }
 
-#[cfg_attr(not(feature = "library"), entry_point)]
-pub fn query(deps: Deps, env: Env, msg: QueryMsg) -> StdResult<Binary> {
-    match msg {
-        QueryMsg::Config {} => to_binary(&query_config(deps)?),
-        QueryMsg::DepositInfo { address } => to_binary(&query_deposit_info(deps, env, address)?),
+// Returns the escrow of a bid that was not revealed, once the reveal period is over
+pub fn reclaim_escrow(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+) -> Result<Response, ContractError> {
+    let cfg = CONFIG.load(deps.storage)?;
+    let launch_cfg = cfg.launch_config.unwrap();
+
+    if env.block.time.seconds() < launch_cfg.phase2_end {
+        return Err(ContractError::InvalidReclaimEscrow {
+            reason: "reveal period is not over yet".to_string(),
+        });
     }
-}
 
-pub fn query_config(deps: Deps) -> StdResult<ConfigResponse> {
-    let cfg = CONFIG.load(deps.storage)?;
+    let mut deposit_info = DEPOSITS
+        .may_load(deps.storage, &info.sender)?
+        .unwrap_or_default();
+    if deposit_info.commitment.is_none() || deposit_info.escrow.is_zero() {
+        return Err(ContractError::InvalidReclaimEscrow {
+            reason: "no unrevealed escrow found".to_string(),
+        });
+    }
 
-    cfg.as_res()
+    // claims refund the escrow together with the allocation
+    if deposit_info.tokens_claimed {
+        return Err(ContractError::InvalidReclaimEscrow {
+            reason: "escrow was already refunded by the claim".to_string(),
+        });
+    }
+
+    // the bid is dropped, so the escrow is no longer refunded at claim time
+    let escrow = deposit_info.escrow;
+    deposit_info.commitment = None;
+    deposit_info.escrow = Uint128::zero();
+    DEPOSITS.save(deps.storage, &info.sender, &deposit_info)?;
+
+    TOTAL_ESCROW.update(deps.storage, |curr| -> StdResult<Uint128> {
+        Ok(curr - escrow)
+    })?;
+
+    let to_send = Asset {
+        info: AssetInfo::Native(cfg.base_denom),
+        amount: escrow,
+    };
+    Ok(Response::new()
+        .add_message(to_send.transfer_msg(info.sender)?)
+        .add_attributes(vec![
+            attr("action", "reclaim_escrow"),
+            attr("escrow_amount", escrow.to_string()),
+        ]))
 }
 
-pub fn query_deposit_info(deps: Deps, env: Env, address: String) -> StdResult<DepositResponse> {
-    let addr = deps.api.addr_validate(&address)?;
+pub fn withdraw(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    amount: Option<Uint128>,
+    recipient: Option<String>,
+    depositor: Option<String>,
+) -> Result<Response, ContractError> {
     let cfg = CONFIG.load(deps.storage)?;
     let launch_config = cfg.launch_config.clone().unwrap();
-    let deposit_info = DEPOSITS.load(deps.storage, &addr).unwrap_or_default();
     let current_time = env.block.time.seconds();
 
-    let withdrawable_amount =
-        if current_time > launch_config.phase2_start && !deposit_info.amount.is_zero() {
-            if deposit_info.withdrew_phase2 || current_time >= launch_config.phase2_end {
-                Uint128::zero()
-            } else {
-                let current_slot =
-                    (launch_config.phase2_end - current_time) / launch_config.phase2_slot_period;
-                let total_slots = (launch_config.phase2_end - launch_config.phase2_start)
-                    / launch_config.phase2_slot_period;
+    if current_time >= launch_config.phase2_end {
+        return Err(ContractError::InvalidWithdraw {
+            reason: "withdraw period is over".to_string(),
+        });
+    }
+
+    if cfg.receipt_token.is_some() {
+        return Err(ContractError::InvalidWithdraw {
+            reason: "deposits are withdrawn by sending the receipt tokens".to_string(),
+        });
+    }
+
+    // the position stays with the depositor, only the payout goes to the recipient
+    let depositor = resolve_depositor(
+        deps.as_ref(),
+        &env,
+        &info.sender,
+        depositor,
+        Permission::Withdraw,
+    )?;
+    let recipient = payout_recipient(deps.api, &info.sender, &depositor, recipient)?;
+
+    if let SaleMode::BondingCurve { .. } = launch_config.sale_mode {
+        return sell_tokens(deps, env, cfg, depositor, amount, recipient);
+    }
+
+    // tokens bought on a dutch auction are final
+    if let SaleMode::DutchAuction { .. } = launch_config.sale_mode {
+        return Err(ContractError::InvalidWithdraw {
+            reason: "deposits can not be withdrawn on a dutch auction".to_string(),
+        });
+    }
+
+    // revealed bids are binding, unrevealed escrows are returned after phase 2
+    if launch_config.sale_mode == (SaleMode::SealedBid {}) {
+        return Err(ContractError::InvalidWithdraw {
+            reason: "sealed bids can not be withdrawn".to_string(),
+        });
+    }
 
-                let withdrawable_portion =
-                    Decimal::from_ratio(current_slot + 1u64, total_slots).min(Decimal::one());
+    let mut deposit_info = DEPOSITS.load(deps.storage, &depositor).unwrap_or_default();
 
-                deposit_info.amount * withdrawable_portion
+    if deposit_info.amount == Uint128::zero() {
+        return Err(ContractError::InvalidWithdraw {
+            reason: "no funds available to withdraw".to_string(),
+        });
+    }
+
+    let unlocked_amount = deposit_info.amount - deposit_info.locked_amount;
+    let is_phase2 = current_time > launch_config.phase2_start;
+    if is_phase2 && unlocked_amount.is_zero() {
+        return Err(ContractError::InvalidWithdraw {
+            reason: "locked deposits can not be withdrawn on phase 2".to_string(),
+        });
+    }
+
+    let withdrawable_amount = if is_phase2 {
+        // check if user already withdrew on phase 2
+        if deposit_info.withdrew_phase2 {
+            return Err(ContractError::InvalidWithdraw {
+                reason: "a withdraw was already executed on phase 2".to_string(),
+            });
+        }
+
+        // on phase 2 can only withraw one time, so flag the position
+        deposit_info.withdrew_phase2 = true;
+
+        unlocked_amount * phase2_withdrawable_portion(&launch_config, current_time)
+    } else {
+        deposit_info.amount
+    };
+
+    let withdraw_amount = match amount {
+        None => withdrawable_amount,
+        Some(requested_amount) => {
+            if requested_amount > withdrawable_amount {
+                return Err(ContractError::InvalidWithdraw {
+                    reason: format!(
+                        "can not withdraw more than current withdrawable amount ({})",
+                        withdrawable_amount
+                    ),
+                });
+            }
+            if requested_amount == Uint128::zero() {
+                return Err(ContractError::InvalidWithdraw {
+                    reason: "withdraw amount must be bigger than 0".to_string(),
+                });
             }
+
+            requested_amount
+        }
+    };
+
+    // keep the batch auction bid indices in sync with the deposit
+    if let Some(max_price) = deposit_info.max_price {
+        update_bid(
+            deps.storage,
+            &depositor,
+            max_price,
+            deposit_info.amount,
+            deposit_info.amount - withdraw_amount,
+        )?;
+    }
+
+    if !deposit_info.weight.is_zero() {
+        let removed_weight = if launch_config.sale_mode == (SaleMode::TimeWeighted {}) {
+            // the withdrawn amount takes the weight of the last deposits with it
+            remove_weight(deps.storage, &depositor, withdraw_amount)?
         } else {
-            deposit_info.amount
+            // bonus weight is burnt pro-rata, rounding the remaining weight down, and phase 1
+            // withdraws above the unlocked amount release the last locks
+            let unlocked_withdraw = withdraw_amount.min(unlocked_amount);
+            let unlocked_weight = deposit_info.weight - deposit_info.locked_weight;
+            let mut removed_weight = if unlocked_withdraw.is_zero() {
+                Uint128::zero()
+            } else {
+                unlocked_weight
+                    - unlocked_weight
+                        .multiply_ratio(unlocked_amount - unlocked_withdraw, unlocked_amount)
+            };
+            let locked_withdraw = withdraw_amount - unlocked_withdraw;
+            if !locked_withdraw.is_zero() {
+                let released_weight = release_locks(deps.storage, &depositor, locked_withdraw)?;
+                deposit_info.locked_amount -= locked_withdraw;
+                deposit_info.locked_weight -= released_weight;
+                removed_weight += released_weight;
+            }
+            removed_weight
         };
+        deposit_info.weight -= removed_weight;
+        TOTAL_WEIGHT.update(deps.storage, |curr| -> StdResult<Uint128> {
+            Ok(curr - removed_weight)
+        })?;
+    }
 
-    let total_deposit = TOTAL_DEPOSIT.load(deps.storage)?;
-    let tokens_to_claim = if !total_deposit.is_zero() {
-        launch_config
-            .amount
-            .multiply_ratio(deposit_info.amount, total_deposit)
-    } else {
-        Uint128::zero()
+    // keep the deposit brought in by the referrer in sync with the deposit
+    if let Some(referrer) = &deposit_info.referrer {
+        update_referral(
+            deps.storage,
+            referrer,
+            deposit_info.amount,
+            deposit_info.amount - withdraw_amount,
+        )?;
+    }
+
+    // update user deposit amount
+    deposit_info.amount -= withdraw_amount;
+
+    // what is left of the position no longer shares the loyalty bonus
+    if is_phase2 {
+        NON_LOYAL_DEPOSIT.update(deps.storage, |curr| -> StdResult<Uint128> {
+            Ok(curr + deposit_info.amount)
+        })?;
+    }
+
+    DEPOSITS.save(deps.storage, &depositor, &deposit_info)?;
+
+    TOTAL_DEPOSIT.update(deps.storage, |curr| -> StdResult<Uint128> {
+        Ok(curr - withdraw_amount)
+    })?;
+
+    let withdraw_asset = Asset {
+        info: AssetInfo::Native(cfg.base_denom),
+        amount: withdraw_amount,
     };
+    Ok(Response::new()
+        .add_message(withdraw_asset.transfer_msg(recipient.clone())?)
+        .add_attributes(vec![
+            attr("action", "withdraw"),
+            attr("withdraw_amount", withdraw_asset.amount.to_string()),
+            attr("recipient", recipient),
+        ]))
+}
 
-    Ok(DepositResponse {
-        deposit: deposit_info.amount,
-        total_deposit,
-        withdrawable_amount,
-        tokens_to_claim,
-        can_claim: current_time >= launch_config.phase2_end
-            && !tokens_to_claim.is_zero()
-            && cfg.tokens_released
-            && !deposit_info.tokens_claimed,
-    })
+// Sells tokens bought on the bonding curve back along the curve, minus the spread
+fn sell_tokens(
+    deps: DepsMut,
+    env: Env,
+    cfg: Config,
+    depositor: Addr,
+    amount: Option<Uint128>,
+    recipient: Addr,
+) -> Result<Response, ContractError> {
+    let launch_config = cfg.launch_config.unwrap();
+    let (curve, spread) = match launch_config.sale_mode {
+        SaleMode::BondingCurve { curve, spread } => (curve, spread),
+        _ => return Err(StdError::generic_err("launch is not a bonding curve").into()),
+    };
+
+    if env.block.time.seconds() >= launch_config.phase2_start {
+        return Err(ContractError::InvalidWithdraw {
+            reason: "tokens can only be sold back during phase 1".to_string(),
+        });
+    }
+
+    let mut deposit_info = DEPOSITS.load(deps.storage, &depositor).unwrap_or_default();
+    if deposit_info.tokens_bought.is_zero() {
+        return Err(ContractError::InvalidWithdraw {
+            reason: "no tokens available to sell".to_string(),
+        });
+    }
+
+    // on bonding curves the amount is the number of tokens to sell
+    let tokens = amount.unwrap_or(deposit_info.tokens_bought);
+    if tokens > deposit_info.tokens_bought {
+        return Err(ContractError::InvalidWithdraw {
+            reason: format!(
+                "can not sell more than the bought tokens ({})",
+                deposit_info.tokens_bought
+            ),
+        });
+    }
+    if tokens.is_zero() {
+        return Err(ContractError::InvalidWithdraw {
+            reason: "withdraw amount must be bigger than 0".to_string(),
+        });
+    }
+
+    let supply = BONDING_CURVE_SUPPLY.load(deps.storage)?;
+    let proceeds = curve.sell_proceeds(launch_config.amount, supply, tokens);
+    let withdraw_amount = proceeds - mul_ceil(proceeds, spread);
+
+    // the deposit keeps the cost of the tokens that were not sold, the difference between the
+    // cost removed and the proceeds is booked on the curve sells
+    let cost = deposit_info
+        .amount
+        .multiply_ratio(tokens, deposit_info.tokens_bought);
+    deposit_info.amount -= cost;
+    deposit_info.tokens_bought -= tokens;
+    DEPOSITS.save(deps.storage, &depositor, &deposit_info)?;
+
+    BONDING_CURVE_SUPPLY.save(deps.storage, &(supply - tokens))?;
+    TOTAL_DEPOSIT.update(deps.storage, |curr| -> StdResult<Uint128> {
+        Ok(curr - cost)
+    })?;
+    let mut sells = BONDING_CURVE_SELLS
+        .may_load(deps.storage)?
+        .unwrap_or_default();
+    sells.cost += cost;
+    sells.proceeds += withdraw_amount;
+    BONDING_CURVE_SELLS.save(deps.storage, &sells)?;
+
+    let mut msgs: Vec<CosmosMsg> = vec![];
+    if !withdraw_amount.is_zero() {
+        let withdraw_asset = Asset {
+            info: AssetInfo::Native(cfg.base_denom.clone()),
+            amount: withdraw_amount,
+        };
+        msgs.push(withdraw_asset.transfer_msg(recipient.clone())?);
+    }
+
+    Ok(Response::new().add_messages(msgs).add_attributes(vec![
+        attr("action", "withdraw"),
+        attr("withdraw_amount", withdraw_amount.to_string()),
+        attr("sold_tokens", tokens.to_string()),
+        attr("recipient", recipient),
+    ]))
+}
+
+// Moves part or all of the deposit of the depositor to the recipient position
+pub fn transfer_position(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    recipient: String,
+    amount: Option<Uint128>,
+    depositor: Option<String>,
+) -> Result<Response, ContractError> {
+    let cfg = CONFIG.load(deps.storage)?;
+    let launch_config = cfg.launch_config.unwrap();
+
+    // withdraws are unrestricted on phase 1, so neither position has withdrew_phase2 or
+    // tokens_claimed set yet and a transfer can not be used to withdraw twice on phase 2
+    if env.block.time.seconds() >= launch_config.phase2_start {
+        return Err(ContractError::InvalidTransferPosition {
+            reason: "positions can only be transferred during phase 1".to_string(),
+        });
+    }
+
+    if launch_config.sale_mode == (SaleMode::SealedBid {}) {
+        return Err(ContractError::InvalidTransferPosition {
+            reason: "sealed bids can not be transferred".to_string(),
+        });
+    }
+
+    if cfg.receipt_token.is_some() {
+        return Err(ContractError::InvalidTransferPosition {
+            reason: "deposits are transferred with the receipt tokens".to_string(),
+        });
+    }
+
+    let depositor = resolve_depositor(
+        deps.as_ref(),
+        &env,
+        &info.sender,
+        depositor,
+        Permission::Transfer,
+    )?;
+    let recipient = deps.api.addr_validate(&recipient)?;
+    if recipient == depositor {
+        return Err(ContractError::InvalidTransferPosition {
+            reason: "can not transfer to yourself".to_string(),
+        });
+    }
+
+    // delegates can only transfer to the recipients allowed by the depositor
+    if info.sender != depositor {
+        let delegation = DELEGATES.load(deps.storage, (&depositor, &info.sender))?;
+        if !delegation.transfer_recipients.contains(&recipient) {
+            return Err(ContractError::InvalidTransferPosition {
+                reason: "recipient is not allowed by the delegation".to_string(),
+            });
+        }
+    }
+
+    let mut sender_info = DEPOSITS
+        .may_load(deps.storage, &depositor)?
+        .unwrap_or_default();
+
+    // lock positions stay with the depositor that locked them
+    let transferable_amount = sender_info.amount - sender_info.locked_amount;
+    if transferable_amount.is_zero() {
+        return Err(ContractError::InvalidTransferPosition {
+            reason: "no funds available to transfer".to_string(),
+        });
+    }
+
+    let transfer_amount = match amount {
+        None => transferable_amount,
+        Some(requested_amount) => {
+            if requested_amount > transferable_amount {
+                return Err(ContractError::InvalidTransferPosition {
+                    reason: format!(
+                        "can not transfer more than current transferable amount ({})",
+                        transferable_amount
+                    ),
+                });
+            }
+            if requested_amount.is_zero() {
+                return Err(ContractError::InvalidTransferPosition {
+                    reason: "transfer amount must be bigger than 0".to_string(),
+                });
+            }
+
+            requested_amount
+        }
+    };
+
+    let mut recipient_info = DEPOSITS
+        .may_load(deps.storage, &recipient)?
+        .unwrap_or_default();
+
+    // the batch auction bid moves at its max price
+    if let Some(max_price) = sender_info.max_price {
+        if !recipient_info.amount.is_zero() && recipient_info.max_price != Some(max_price) {
+            return Err(ContractError::InvalidTransferPosition {
+                reason: "recipient has an open bid at a different max price".to_string(),
+            });
+        }
+
+        update_bid(
+            deps.storage,
+            &depositor,
+            max_price,
+            sender_info.amount,
+            sender_info.amount - transfer_amount,
+        )?;
+        update_bid(
+            deps.storage,
+            &recipient,
+            max_price,
+            recipient_info.amount,
+            recipient_info.amount + transfer_amount,
+        )?;
+        recipient_info.max_price = Some(max_price);
+    }
+
+    // tokens bought by the position follow the share of the deposit transferred
+    let transferred_tokens = sender_info.tokens_bought
+        - sender_info
+            .tokens_bought
+            .multiply_ratio(sender_info.amount - transfer_amount, sender_info.amount);
+
+    let transferred_weight = if sender_info.weight.is_zero() {
+        Uint128::zero()
+    } else if launch_config.sale_mode == (SaleMode::TimeWeighted {}) {
+        // the last deposits of the sender move on top of the recipient deposits
+        let moved = take_weighted_deposits(deps.storage, &depositor, transfer_amount)?;
+        let moved_weight = weight_of(&moved);
+
+        let mut weighted_deposits = WEIGHTED_DEPOSITS
+            .may_load(deps.storage, &recipient)?
+            .unwrap_or_default();
+        weighted_deposits.extend(moved.into_iter().rev());
+        WEIGHTED_DEPOSITS.save(deps.storage, &recipient, &weighted_deposits)?;
+
+        moved_weight
+    } else {
+        let unlocked_weight = sender_info.weight - sender_info.locked_weight;
+        unlocked_weight
+            - unlocked_weight
+                .multiply_ratio(transferable_amount - transfer_amount, transferable_amount)
+    };
+
+    // the transferred deposit leaves the sender referral and joins the recipient one
+    if let Some(referrer) = &sender_info.referrer {
+        update_referral(
+            deps.storage,
+            referrer,
+            sender_info.amount,
+            sender_info.amount - transfer_amount,
+        )?;
+    }
+    if let Some(referrer) = &recipient_info.referrer {
+        update_referral(
+            deps.storage,
+            referrer,
+            recipient_info.amount,
+            recipient_info.amount + transfer_amount,
+        )?;
+    }
+
+    sender_info.amount -= transfer_amount;
+    sender_info.tokens_bought -= transferred_tokens;
+    sender_info.weight -= transferred_weight;
+    DEPOSITS.save(deps.storage, &depositor, &sender_info)?;
+
+    recipient_info.amount += transfer_amount;
+    recipient_info.tokens_bought += transferred_tokens;
+    recipient_info.weight += transferred_weight;
+    DEPOSITS.save(deps.storage, &recipient, &recipient_info)?;
+
+    Ok(Response::new().add_attributes(vec![
+        attr("action", "transfer_position"),
+        attr("recipient", recipient),
+        attr("transfer_amount", transfer_amount.to_string()),
+    ]))
+}
+
+// Withdraws from the deposit of a position nft, only allowed to the owner of the nft
+pub fn withdraw_position(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    token_id: String,
+    amount: Option<Uint128>,
+    recipient: Option<String>,
+) -> Result<Response, ContractError> {
+    let cfg = CONFIG.load(deps.storage)?;
+    let launch_config = cfg.launch_config.clone().unwrap();
+    let current_time = env.block.time.seconds();
+
+    if current_time >= launch_config.phase2_end {
+        return Err(ContractError::InvalidWithdraw {
+            reason: "withdraw period is over".to_string(),
+        });
+    }
+
+    let mut position = load_owned_position(deps.as_ref(), &cfg, &token_id, &info.sender)?;
+    if position.amount.is_zero() {
+        return Err(ContractError::InvalidWithdraw {
+            reason: "no funds available to withdraw".to_string(),
+        });
+    }
+
+    let recipient = match recipient {
+        Some(recipient) => deps.api.addr_validate(&recipient)?,
+        None => info.sender.clone(),
+    };
+
+    // the one withdraw of phase 2 is tracked on the position, so it moves with the nft
+    let withdrawable_amount = if current_time > launch_config.phase2_start {
+        if position.withdrew_phase2 {
+            return Err(ContractError::InvalidWithdraw {
+                reason: "a withdraw was already executed on phase 2".to_string(),
+            });
+        }
+        position.withdrew_phase2 = true;
+
+        position.amount * phase2_withdrawable_portion(&launch_config, current_time)
+    } else {
+        position.amount
+    };
+
+    let withdraw_amount = match amount {
+        None => withdrawable_amount,
+        Some(requested_amount) => {
+            if requested_amount > withdrawable_amount {
+                return Err(ContractError::InvalidWithdraw {
+                    reason: format!(
+                        "can not withdraw more than current withdrawable amount ({})",
+                        withdrawable_amount
+                    ),
+                });
+            }
+            if requested_amount.is_zero() {
+                return Err(ContractError::InvalidWithdraw {
+                    reason: "withdraw amount must be bigger than 0".to_string(),
+                });
+            }
+
+            requested_amount
+        }
+    };
+
+    position.amount -= withdraw_amount;
+    POSITIONS.save(deps.storage, &token_id, &position)?;
+
+    TOTAL_DEPOSIT.update(deps.storage, |curr| -> StdResult<Uint128> {
+        Ok(curr - withdraw_amount)
+    })?;
+
+    let withdraw_asset = Asset {
+        info: AssetInfo::Native(cfg.base_denom),
+        amount: withdraw_amount,
+    };
+
+    Ok(Response::new()
+        .add_message(withdraw_asset.transfer_msg(recipient.clone())?)
+        .add_attributes(vec![
+            attr("action", "withdraw"),
+            attr("token_id", token_id),
+            attr("withdraw_amount", withdraw_amount.to_string()),
+            attr("recipient", recipient),
+        ]))
+}
+
+// Claims the allocation of a position nft, only allowed to the owner of the nft
+pub fn claim_position(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    token_id: String,
+    recipient: Option<String>,
+) -> Result<Response, ContractError> {
+    let cfg = CONFIG.load(deps.storage)?;
+    let launch_cfg = cfg.launch_config.clone().unwrap();
+
+    if env.block.time.seconds() < launch_cfg.phase2_end || !cfg.tokens_released {
+        return Err(ContractError::InvalidWithdrawTokens {
+            reason: "cannot withdraw tokens yet".to_string(),
+        });
+    }
+
+    if cfg.tokens_swept {
+        return Err(ContractError::InvalidWithdrawTokens {
+            reason: "claim deadline is over, unclaimed tokens were swept".to_string(),
+        });
+    }
+
+    let mut position = load_owned_position(deps.as_ref(), &cfg, &token_id, &info.sender)?;
+    if position.tokens_claimed {
+        return Err(ContractError::InvalidWithdrawTokens {
+            reason: "tokens were already claimed".to_string(),
+        });
+    }
+
+    let recipient = match recipient {
+        Some(recipient) => deps.api.addr_validate(&recipient)?,
+        None => info.sender.clone(),
+    };
+
+    let deposit_info = position.deposit_info();
+    let sale_result = SaleResult::load(deps.storage, &launch_cfg)?;
+    let allocation = sale_result.allocation(&deposit_info);
+    if allocation.is_empty() {
+        return Err(ContractError::InvalidWithdrawTokens {
+            reason: "no tokens available for withdraw".to_string(),
+        });
+    }
+
+    position.tokens_claimed = true;
+    POSITIONS.save(deps.storage, &token_id, &position)?;
+
+    let msgs = pay_allocation(
+        deps.storage,
+        &cfg,
+        &deposit_info,
+        &allocation,
+        &Payout::to(recipient.clone()),
+    )?;
+
+    Ok(Response::new().add_messages(msgs).add_attributes(vec![
+        attr("action", "withdraw_tokens"),
+        attr("token_id", token_id),
+        attr("withdraw_amount", allocation.total_tokens().to_string()),
+        attr("refund_amount", allocation.refund.to_string()),
+        attr("recipient", recipient),
+    ]))
+}
+
+pub fn withdraw_tokens(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    recipient: Option<String>,
+    depositor: Option<String>,
+    destination: ClaimDestination,
+) -> Result<Response, ContractError> {
+    let cfg = CONFIG.load(deps.storage)?;
+    ensure_tokens_claimable(&env, &cfg)?;
+
+    let depositor = resolve_depositor(
+        deps.as_ref(),
+        &env,
+        &info.sender,
+        depositor,
+        Permission::Claim,
+    )?;
+    let recipient = payout_recipient(deps.api, &info.sender, &depositor, recipient)?;
+
+    match &destination {
+        ClaimDestination::Recipient => {}
+        ClaimDestination::Hook { contract, .. } => {
+            // the hook decides who gets the tokens, so delegates can not set it
+            if info.sender != depositor {
+                return Err(ContractError::Unauthorized {});
+            }
+            if !CLAIM_TARGETS.has(deps.storage, contract) {
+                return Err(ContractError::InvalidWithdrawTokens {
+                    reason: "contract is not an allowed claim target".to_string(),
+                });
+            }
+        }
+        ClaimDestination::Stake => {
+            if cfg.staking_contract.is_none() {
+                return Err(ContractError::InvalidWithdrawTokens {
+                    reason: "launch has no staking contract".to_string(),
+                });
+            }
+        }
+    }
+
+    let (mut deposit_info, claim, allocation) =
+        unclaimed_allocation(deps.storage, &env, &cfg, &depositor)?;
+    let payout = Payout {
+        recipient: recipient.clone(),
+        destination,
+        tip: None,
+    };
+    let msgs = claim_allocation(
+        deps.storage,
+        &cfg,
+        &depositor,
+        &mut deposit_info,
+        &claim,
+        &allocation,
+        &payout,
+    )?;
+
+    Ok(Response::new().add_messages(msgs).add_attributes(vec![
+        attr("action", "withdraw_tokens"),
+        attr("withdraw_amount", allocation.total_tokens().to_string()),
+        attr("refund_amount", allocation.refund.to_string()),
+        attr("recipient", recipient),
+    ]))
+}
+
+// Claims the tokens of the depositor on its behalf, paying the tip set by the depositor to the sender
+pub fn claim_for(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    address: String,
+) -> Result<Response, ContractError> {
+    let cfg = CONFIG.load(deps.storage)?;
+    ensure_tokens_claimable(&env, &cfg)?;
+
+    let depositor = deps.api.addr_validate(&address)?;
+    let (mut deposit_info, claim, allocation) =
+        unclaimed_allocation(deps.storage, &env, &cfg, &depositor)?;
+
+    let tip = allocation.total_tokens() * deposit_info.claim_tip;
+    let payout = Payout {
+        recipient: depositor.clone(),
+        destination: ClaimDestination::Recipient,
+        tip: Some((info.sender, tip)),
+    };
+    let msgs = claim_allocation(
+        deps.storage,
+        &cfg,
+        &depositor,
+        &mut deposit_info,
+        &claim,
+        &allocation,
+        &payout,
+    )?;
+
+    Ok(Response::new().add_messages(msgs).add_attributes(vec![
+        attr("action", "claim_for"),
+        attr("depositor", depositor),
+        attr(
+            "withdraw_amount",
+            (allocation.total_tokens() - tip).to_string(),
+        ),
+        attr("refund_amount", allocation.refund.to_string()),
+        attr("tip_amount", tip.to_string()),
+    ]))
+}
+
+// Sets the share of the claimed tokens paid to whoever claims on behalf of the sender
+pub fn set_claim_tip(
+    deps: DepsMut,
+    info: MessageInfo,
+    tip: Decimal,
+) -> Result<Response, ContractError> {
+    if tip > Decimal::percent(MAX_CLAIM_TIP_PERCENT) {
+        return Err(ContractError::InvalidClaimTip {
+            reason: format!("tip can not be bigger than {}%", MAX_CLAIM_TIP_PERCENT),
+        });
+    }
+
+    let mut deposit_info =
+        DEPOSITS
+            .load(deps.storage, &info.sender)
+            .map_err(|_| ContractError::InvalidClaimTip {
+                reason: "deposit information not found".to_string(),
+            })?;
+    if deposit_info.tokens_claimed {
+        return Err(ContractError::InvalidClaimTip {
+            reason: "tokens were already claimed".to_string(),
+        });
+    }
+
+    deposit_info.claim_tip = tip;
+    DEPOSITS.save(deps.storage, &info.sender, &deposit_info)?;
+
+    Ok(Response::new().add_attributes(vec![
+        attr("action", "set_claim_tip"),
+        attr("tip", tip.to_string()),
+    ]))
+}
+
+// Lets the delegate act on the position of the sender with the given permissions
+pub fn authorize(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    delegate: String,
+    permissions: Vec<Permission>,
+    expires: Option<Expiration>,
+    transfer_recipients: Vec<String>,
+) -> Result<Response, ContractError> {
+    let delegate = deps.api.addr_validate(&delegate)?;
+    if delegate == info.sender {
+        return Err(ContractError::InvalidAuthorize {
+            reason: "can not delegate to yourself".to_string(),
+        });
+    }
+
+    if permissions.is_empty() {
+        return Err(ContractError::InvalidAuthorize {
+            reason: "at least one permission is required".to_string(),
+        });
+    }
+
+    let expires = expires.unwrap_or(Expiration::Never {});
+    if expires.is_expired(&env.block) {
+        return Err(ContractError::InvalidAuthorize {
+            reason: "delegation is already expired".to_string(),
+        });
+    }
+
+    // the transfer permission only moves the position to the recipients chosen by the depositor
+    let transfer_recipients = transfer_recipients
+        .iter()
+        .map(|recipient| deps.api.addr_validate(recipient))
+        .collect::<StdResult<Vec<_>>>()?;
+    if permissions.contains(&Permission::Transfer) == transfer_recipients.is_empty() {
+        return Err(ContractError::InvalidAuthorize {
+            reason: "transfer recipients must be set with the transfer permission only".to_string(),
+        });
+    }
+    if transfer_recipients
+        .iter()
+        .any(|recipient| *recipient == delegate || *recipient == info.sender)
+    {
+        return Err(ContractError::InvalidAuthorize {
+            reason: "transfer recipients can not be the delegate or the depositor".to_string(),
+        });
+    }
+
+    // authorizing an existing delegate replaces its delegation
+    let delegation = Delegation {
+        permissions,
+        expires,
+        transfer_recipients,
+    };
+    DELEGATES.save(deps.storage, (&info.sender, &delegate), &delegation)?;
+
+    Ok(Response::new().add_attributes(vec![
+        attr("action", "authorize"),
+        attr("delegate", delegate),
+    ]))
+}
+
+pub fn revoke(
+    deps: DepsMut,
+    info: MessageInfo,
+    delegate: String,
+) -> Result<Response, ContractError> {
+    let delegate = deps.api.addr_validate(&delegate)?;
+    if !DELEGATES.has(deps.storage, (&info.sender, &delegate)) {
+        return Err(ContractError::InvalidRevoke {
+            reason: "delegate not found".to_string(),
+        });
+    }
+
+    DELEGATES.remove(deps.storage, (&info.sender, &delegate));
+
+    Ok(Response::new().add_attributes(vec![attr("action", "revoke"), attr("delegate", delegate)]))
+}
+
+pub fn receive_cw20(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    cw20_msg: Cw20ReceiveMsg,
+) -> Result<Response, ContractError> {
+    let cfg = CONFIG.load(deps.storage)?;
+
+    // only receipts are accepted
+    let receipt_token = match &cfg.receipt_token {
+        Some(receipt_token) if *receipt_token == info.sender => receipt_token.clone(),
+        _ => return Err(ContractError::Unauthorized {}),
+    };
+
+    match from_binary(&cw20_msg.msg)? {
+        Cw20HookMsg::Redeem {} => {
+            let sender = deps.api.addr_validate(&cw20_msg.sender)?;
+            redeem(deps, env, cfg, receipt_token, sender, cw20_msg.amount)
+        }
+        Cw20HookMsg::Withdraw {} => {
+            let sender = deps.api.addr_validate(&cw20_msg.sender)?;
+            withdraw_receipts(deps, env, cfg, receipt_token, sender, cw20_msg.amount)
+        }
+    }
+}
+
+// Burns the receipts and returns the deposit they were minted for. Receipts can change hands, so
+// they can not keep the single phase 2 withdraw of a position and are only withdrawn on phase 1
+fn withdraw_receipts(
+    deps: DepsMut,
+    env: Env,
+    cfg: Config,
+    receipt_token: Addr,
+    sender: Addr,
+    amount: Uint128,
+) -> Result<Response, ContractError> {
+    let launch_cfg = cfg.launch_config.unwrap();
+
+    if env.block.time.seconds() > launch_cfg.phase2_start {
+        return Err(ContractError::InvalidWithdraw {
+            reason: "receipts can only be withdrawn on phase 1".to_string(),
+        });
+    }
+
+    TOTAL_DEPOSIT.update(deps.storage, |curr| -> StdResult<Uint128> {
+        Ok(curr - amount)
+    })?;
+
+    let withdraw_asset = Asset {
+        info: AssetInfo::Native(cfg.base_denom),
+        amount,
+    };
+    Ok(Response::new()
+        .add_message(withdraw_asset.transfer_msg(sender.clone())?)
+        .add_message(receipt_token_msg(
+            &receipt_token,
+            Cw20ExecuteMsg::Burn { amount },
+        )?)
+        .add_attributes(vec![
+            attr("action", "withdraw"),
+            attr("withdraw_amount", amount.to_string()),
+            attr("recipient", sender),
+        ]))
+}
+
+// Burns the receipts and pays out the allocation of a deposit of the same amount
+fn redeem(
+    deps: DepsMut,
+    env: Env,
+    cfg: Config,
+    receipt_token: Addr,
+    sender: Addr,
+    amount: Uint128,
+) -> Result<Response, ContractError> {
+    let launch_cfg = cfg.launch_config.clone().unwrap();
+
+    if env.block.time.seconds() < launch_cfg.phase2_end || !cfg.tokens_released {
+        return Err(ContractError::InvalidRedeem {
+            reason: "cannot redeem receipts yet".to_string(),
+        });
+    }
+
+    if cfg.tokens_swept {
+        return Err(ContractError::InvalidRedeem {
+            reason: "claim deadline is over, unclaimed tokens were swept".to_string(),
+        });
+    }
+
+    let deposit_info = DepositInfo {
+        amount,
+        ..Default::default()
+    };
+    let sale_result = SaleResult::load(deps.storage, &launch_cfg)?;
+    let allocation = sale_result.allocation(&deposit_info);
+
+    let mut msgs = vec![receipt_token_msg(
+        &receipt_token,
+        Cw20ExecuteMsg::Burn { amount },
+    )?];
+    msgs.extend(pay_allocation(
+        deps.storage,
+        &cfg,
+        &deposit_info,
+        &allocation,
+        &Payout::to(sender.clone()),
+    )?);
+
+    Ok(Response::new().add_messages(msgs).add_attributes(vec![
+        attr("action", "redeem"),
+        attr("redeem_amount", amount.to_string()),
+        attr("withdraw_amount", allocation.total_tokens().to_string()),
+        attr("refund_amount", allocation.refund.to_string()),
+    ]))
+}
+
+pub fn claim_referral_rewards(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+) -> Result<Response, ContractError> {
+    let cfg = CONFIG.load(deps.storage)?;
+    let launch_cfg = cfg.launch_config.clone().unwrap();
+
+    if env.block.time.seconds() < launch_cfg.phase2_end || !cfg.tokens_released {
+        return Err(ContractError::InvalidClaimReferralRewards {
+            reason: "cannot claim referral rewards yet".to_string(),
+        });
+    }
+
+    if cfg.tokens_swept {
+        return Err(ContractError::InvalidClaimReferralRewards {
+            reason: "claim deadline is over, unclaimed tokens were swept".to_string(),
+        });
+    }
+
+    let mut referral = REFERRALS
+        .may_load(deps.storage, &info.sender)?
+        .ok_or_else(|| ContractError::InvalidClaimReferralRewards {
+            reason: "referral information not found".to_string(),
+        })?;
+    if referral.rewards_claimed {
+        return Err(ContractError::InvalidClaimReferralRewards {
+            reason: "referral rewards were already claimed".to_string(),
+        });
+    }
+
+    let sale_result = SaleResult::load(deps.storage, &launch_cfg)?;
+    let rewards = sale_result
+        .rewards
+        .referral
+        .share(referral.referred_deposit);
+    if rewards.is_zero() {
+        return Err(ContractError::InvalidClaimReferralRewards {
+            reason: "no referral rewards available for claim".to_string(),
+        });
+    }
+
+    referral.rewards_claimed = true;
+    REFERRALS.save(deps.storage, &info.sender, &referral)?;
+    record_referral_claim(deps.storage, &referral, rewards)?;
+
+    let to_send = Asset {
+        info: AssetInfo::Cw20(cfg.token),
+        amount: rewards,
+    };
+    Ok(Response::new()
+        .add_message(to_send.transfer_msg(info.sender)?)
+        .add_attributes(vec![
+            attr("action", "claim_referral_rewards"),
+            attr("rewards_amount", rewards.to_string()),
+        ]))
+}
+
+// Pays the refund of the sender's deposit once its tokens were swept, the refund is not swept
+pub fn claim_refund(deps: DepsMut, info: MessageInfo) -> Result<Response, ContractError> {
+    let cfg = CONFIG.load(deps.storage)?;
+    let launch_cfg = cfg.launch_config.clone().unwrap();
+
+    if !cfg.tokens_swept {
+        return Err(ContractError::InvalidClaimRefund {
+            reason: "refunds are paid with the tokens until they are swept".to_string(),
+        });
+    }
+
+    let mut deposit_info = DEPOSITS
+        .may_load(deps.storage, &info.sender)?
+        .ok_or_else(|| ContractError::InvalidClaimRefund {
+            reason: "deposit information not found".to_string(),
+        })?;
+    if deposit_info.tokens_claimed {
+        return Err(ContractError::InvalidClaimRefund {
+            reason: "tokens were already claimed".to_string(),
+        });
+    }
+
+    let sale_result = SaleResult::load(deps.storage, &launch_cfg)?;
+    let refund = sale_result
+        .allocation(&unclaimed_part(&deposit_info))
+        .refund;
+    if refund.is_zero() {
+        return Err(ContractError::InvalidClaimRefund {
+            reason: "no refund available for claim".to_string(),
+        });
+    }
+
+    // the tokens of the position were swept, only the refund is left to record
+    deposit_info.tokens_claimed = true;
+    DEPOSITS.save(deps.storage, &info.sender, &deposit_info)?;
+    TOTAL_REFUNDED.update(deps.storage, |curr| -> StdResult<Uint128> {
+        Ok(curr + refund)
+    })?;
+
+    let to_send = Asset {
+        info: AssetInfo::Native(cfg.base_denom),
+        amount: refund,
+    };
+    Ok(Response::new()
+        .add_message(to_send.transfer_msg(info.sender)?)
+        .add_attributes(vec![
+            attr("action", "claim_refund"),
+            attr("refund_amount", refund.to_string()),
+        ]))
+}
+
+// Pays the allocation of the sender's deposit and its referral rewards in a single response
+pub fn withdraw_and_claim(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+) -> Result<Response, ContractError> {
+    let cfg = CONFIG.load(deps.storage)?;
+    ensure_tokens_claimable(&env, &cfg)?;
+    let sale_result = SaleResult::load(deps.storage, cfg.launch_config.as_ref().unwrap())?;
+
+    let payout = Payout::to(info.sender.clone());
+    let mut msgs = vec![];
+    let mut allocation = Allocation::default();
+    if let Some(mut deposit_info) = DEPOSITS.may_load(deps.storage, &info.sender)? {
+        if let Some(claim) = claimable_part(deps.storage, &env, &info.sender, &deposit_info)? {
+            allocation = sale_result.allocation(&claim.part);
+            // empty positions are skipped, same as on withdraw_tokens
+            if !allocation.is_empty() {
+                msgs.extend(claim_allocation(
+                    deps.storage,
+                    &cfg,
+                    &info.sender,
+                    &mut deposit_info,
+                    &claim,
+                    &allocation,
+                    &payout,
+                )?);
+            }
+        }
+    }
+
+    let mut rewards = Uint128::zero();
+    if let Some(mut referral) = REFERRALS.may_load(deps.storage, &info.sender)? {
+        if !referral.rewards_claimed {
+            rewards = sale_result
+                .rewards
+                .referral
+                .share(referral.referred_deposit);
+            if !rewards.is_zero() {
+                referral.rewards_claimed = true;
+                REFERRALS.save(deps.storage, &info.sender, &referral)?;
+                record_referral_claim(deps.storage, &referral, rewards)?;
+            }
+        }
+    }
+
+    if allocation.is_empty() && rewards.is_zero() {
+        return Err(ContractError::InvalidWithdrawAndClaim {
+            reason: "nothing left to settle".to_string(),
+        });
+    }
+
+    msgs.extend(payout_msgs(&cfg, &payout, rewards, Uint128::zero())?);
+
+    Ok(Response::new().add_messages(msgs).add_attributes(vec![
+        attr("action", "withdraw_and_claim"),
+        attr("withdraw_amount", allocation.total_tokens().to_string()),
+        attr("rewards_amount", rewards.to_string()),
+        attr("refund_amount", allocation.refund.to_string()),
+    ]))
+}
+
+pub fn distribute_tokens(
+    deps: DepsMut,
+    env: Env,
+    limit: Option<u32>,
+) -> Result<Response, ContractError> {
+    let cfg = CONFIG.load(deps.storage)?;
+    let launch_cfg = cfg.launch_config.clone().unwrap();
+
+    if env.block.time.seconds() < launch_cfg.phase2_end || !cfg.tokens_released {
+        return Err(ContractError::InvalidDistributeTokens {
+            reason: "cannot distribute tokens yet".to_string(),
+        });
+    }
+
+    if cfg.tokens_swept {
+        return Err(ContractError::InvalidDistributeTokens {
+            reason: "claim deadline is over, unclaimed tokens were swept".to_string(),
+        });
+    }
+
+    if cfg.receipt_token.is_some() {
+        return Err(ContractError::InvalidDistributeTokens {
+            reason: "tokens are claimed by redeeming the receipt tokens".to_string(),
+        });
+    }
+
+    // positions are only recorded under their NFT, whose owner has to claim them
+    if cfg.position_nft.is_some() {
+        return Err(ContractError::InvalidDistributeTokens {
+            reason: "tokens are claimed with the position NFTs".to_string(),
+        });
+    }
+
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start = DISTRIBUTION_CURSOR
+        .may_load(deps.storage)?
+        .map(|addr| Bound::exclusive(addr.as_bytes()));
+    let deposits = DEPOSITS
+        .range(deps.storage, start, None, Order::Ascending)
+        .take(limit)
+        .map(|item| {
+            let (key, deposit_info) = item?;
+            Ok((addr_from_key(key)?, deposit_info))
+        })
+        .collect::<StdResult<Vec<_>>>()?;
+
+    // once the cursor reached the end of the deposits, revisit the positions whose locks ended
+    let revisit = deposits.is_empty();
+    let deposits = if revisit {
+        skipped_positions(deps.storage, &env, limit)?
+    } else {
+        deposits
+    };
+
+    let sale_result = SaleResult::load(deps.storage, &launch_cfg)?;
+    let mut msgs: Vec<CosmosMsg> = vec![];
+    let mut distributed_count = 0u32;
+    let mut distributed_amount = Uint128::zero();
+    let mut refunded_amount = Uint128::zero();
+    let mut cursor = None;
+    for (depositor, mut deposit_info) in deposits {
+        // the locked part of a position is skipped until its last lock ends
+        if let Some(claim) = claimable_part(deps.storage, &env, &depositor, &deposit_info)? {
+            // parts rounded down to nothing are claimed without a payout, so their deposits no
+            // longer hold back the dust sweep
+            let allocation = sale_result.allocation(&claim.part);
+            msgs.extend(claim_allocation(
+                deps.storage,
+                &cfg,
+                &depositor,
+                &mut deposit_info,
+                &claim,
+                &allocation,
+                &Payout::to(depositor.clone()),
+            )?);
+            if !allocation.is_empty() {
+                distributed_count += 1;
+                distributed_amount += allocation.total_tokens();
+                refunded_amount += allocation.refund;
+            }
+        }
+
+        if deposit_info.tokens_claimed {
+            DISTRIBUTION_SKIPPED.remove(deps.storage, &depositor);
+        } else {
+            DISTRIBUTION_SKIPPED.save(deps.storage, &depositor, &Empty {})?;
+        }
+        cursor = Some(depositor);
+    }
+
+    // the cursor only moves over the deposits, revisited positions are tracked as skipped
+    if !revisit {
+        if let Some(cursor) = cursor {
+            DISTRIBUTION_CURSOR.save(deps.storage, &cursor)?;
+        }
+    }
+
+    Ok(Response::new().add_messages(msgs).add_attributes(vec![
+        attr("action", "distribute_tokens"),
+        attr("distributed_count", distributed_count.to_string()),
+        attr("distributed_amount", distributed_amount.to_string()),
+        attr("refunded_amount", refunded_amount.to_string()),
+    ]))
+}
+
+pub fn settle(deps: DepsMut, env: Env, limit: Option<u32>) -> Result<Response, ContractError> {
+    let cfg = CONFIG.load(deps.storage)?;
+    let launch_cfg = cfg.launch_config.unwrap();
+
+    if launch_cfg.sale_mode != (SaleMode::BatchAuction {}) {
+        return Err(ContractError::InvalidSettle {
+            reason: "launch is not a batch auction".to_string(),
+        });
+    }
+
+    if env.block.time.seconds() < launch_cfg.phase2_end {
+        return Err(ContractError::InvalidSettle {
+            reason: "cannot settle the auction yet".to_string(),
+        });
+    }
+
+    let mut auction = BATCH_AUCTION.may_load(deps.storage)?.unwrap_or_default();
+    if auction.settled {
+        return Err(ContractError::InvalidSettle {
+            reason: "auction is already settled".to_string(),
+        });
+    }
+
+    // price levels and bids share the limit, first the clearing price is found walking the
+    // levels from the highest price, then the winning bids get their tokens allocated
+    let mut limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    if auction.clearing_price.is_none() {
+        limit -= find_clearing_price(deps.storage, &launch_cfg, &mut auction, limit)?;
+    }
+    if auction.clearing_price.is_some() && limit > 0 {
+        allocate_bids(deps.storage, &launch_cfg, &mut auction, limit)?;
+    }
+
+    BATCH_AUCTION.save(deps.storage, &auction)?;
+
+    Ok(Response::new().add_attributes(vec![
+        attr("action", "settle"),
+        attr(
+            "clearing_price",
+            auction
+                .clearing_price
+                .map(|price| price.to_string())
+                .unwrap_or_default(),
+        ),
+        attr("settled", auction.settled.to_string()),
+    ]))
+}
+
+pub fn release_tokens(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+) -> Result<Response, ContractError> {
+    let mut cfg = CONFIG.load(deps.storage)?;
+    let launch_cfg = cfg.launch_config.clone().unwrap();
+
+    if info.sender != cfg.operator {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    if env.block.time.seconds() < launch_cfg.phase2_end {
+        return Err(ContractError::InvalidReleaseTokens {
+            reason: "cannot release tokens yet".to_string(),
+        });
+    }
+
+    if cfg.tokens_released {
+        return Err(ContractError::InvalidReleaseTokens {
+            reason: "tokens are already released".to_string(),
+        });
+    }
+
+    let sale_result = SaleResult::load(deps.storage, &launch_cfg)?;
+    if !sale_result.settled {
+        return Err(ContractError::InvalidReleaseTokens {
+            reason: "auction is not settled yet".to_string(),
+        });
+    }
+
+    cfg.tokens_released = true;
+
+    CONFIG.save(deps.storage, &cfg)?;
+
+    // tokens that were not sold go back to the operator
+    let unsold_amount = sale_result.unsold_tokens(&launch_cfg);
+
+    let mut msgs: Vec<CosmosMsg> = vec![];
+    if !unsold_amount.is_zero() {
+        let to_send = Asset {
+            info: AssetInfo::Cw20(cfg.token),
+            amount: unsold_amount,
+        };
+        msgs.push(to_send.transfer_msg(cfg.operator)?);
+    }
+
+    Ok(Response::new().add_messages(msgs).add_attributes(vec![
+        attr("action", "release_tokens"),
+        attr("unsold_amount", unsold_amount.to_string()),
+    ]))
+}
+
+pub fn sweep_unclaimed_tokens(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+) -> Result<Response, ContractError> {
+    let mut cfg = CONFIG.load(deps.storage)?;
+    let launch_cfg = cfg.launch_config.clone().unwrap();
+
+    if info.sender != cfg.operator {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    let claim_deadline =
+        launch_cfg
+            .claim_deadline
+            .ok_or_else(|| ContractError::InvalidSweepUnclaimedTokens {
+                reason: "launch has no claim deadline".to_string(),
+            })?;
+    if env.block.time.seconds() < claim_deadline {
+        return Err(ContractError::InvalidSweepUnclaimedTokens {
+            reason: "claim deadline is not over yet".to_string(),
+        });
+    }
+
+    if !cfg.tokens_released {
+        return Err(ContractError::InvalidSweepUnclaimedTokens {
+            reason: "tokens are not released yet".to_string(),
+        });
+    }
+
+    if cfg.tokens_swept {
+        return Err(ContractError::InvalidSweepUnclaimedTokens {
+            reason: "unclaimed tokens are already swept".to_string(),
+        });
+    }
+
+    // only sweep what depositors did not claim so far
+    let unclaimed_amount = compute_unclaimed_tokens(deps.storage, &cfg)?;
+    let total_swept = TOTAL_SWEPT.load(deps.storage)?;
+
+    cfg.tokens_swept = true;
+    CONFIG.save(deps.storage, &cfg)?;
+    TOTAL_SWEPT.save(deps.storage, &(total_swept + unclaimed_amount))?;
+
+    let mut msgs: Vec<CosmosMsg> = vec![];
+    if !unclaimed_amount.is_zero() {
+        let to_send = Asset {
+            info: AssetInfo::Cw20(cfg.token),
+            amount: unclaimed_amount,
+        };
+        msgs.push(to_send.transfer_msg(cfg.unclaimed_tokens_receiver)?);
+    }
+
+    Ok(Response::new().add_messages(msgs).add_attributes(vec![
+        attr("action", "sweep_unclaimed_tokens"),
+        attr("swept_amount", unclaimed_amount.to_string()),
+    ]))
+}
+
+pub fn sweep_dust(deps: DepsMut, env: Env, info: MessageInfo) -> Result<Response, ContractError> {
+    let cfg = CONFIG.load(deps.storage)?;
+    let launch_cfg = cfg.launch_config.clone().unwrap();
+
+    if info.sender != cfg.operator {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    if env.block.time.seconds() < launch_cfg.phase2_end || !cfg.tokens_released {
+        return Err(ContractError::InvalidSweepDust {
+            reason: "cannot sweep dust yet".to_string(),
+        });
+    }
+
+    if cfg.tokens_swept {
+        return Err(ContractError::InvalidSweepDust {
+            reason: "unclaimed tokens are already swept".to_string(),
+        });
+    }
+
+    let accounting = compute_token_accounting(deps.as_ref(), &launch_cfg)?;
+    if accounting.allocated > accounting.claimed {
+        return Err(ContractError::InvalidSweepDust {
+            reason: "not all depositors claimed their tokens yet".to_string(),
+        });
+    }
+
+    // dust that was already swept is not in the contract anymore
+    let dust_amount = accounting.dust - accounting.swept;
+    if dust_amount.is_zero() {
+        return Err(ContractError::InvalidSweepDust {
+            reason: "no dust available to sweep".to_string(),
+        });
+    }
+
+    TOTAL_SWEPT.save(deps.storage, &(accounting.swept + dust_amount))?;
+
+    let to_send = Asset {
+        info: AssetInfo::Cw20(cfg.token),
+        amount: dust_amount,
+    };
+    Ok(Response::new()
+        .add_message(to_send.transfer_msg(cfg.unclaimed_tokens_receiver)?)
+        .add_attributes(vec![
+            attr("action", "sweep_dust"),
+            attr("swept_amount", dust_amount.to_string()),
+        ]))
+}
+
+pub fn recover_asset(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    asset_info: AssetInfoUnchecked,
+    recipient: String,
+) -> Result<Response, ContractError> {
+    let cfg = CONFIG.load(deps.storage)?;
+
+    if info.sender != cfg.operator {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    let asset_info = asset_info.check(deps.api)?;
+    let recipient = deps.api.addr_validate(&recipient)?;
+
+    // sale tokens and base denom owed to users or receivers can never be recovered
+    let reserved_amount = if asset_info == AssetInfo::Cw20(cfg.token.clone()) {
+        compute_unclaimed_tokens(deps.storage, &cfg)?
+    } else if asset_info == AssetInfo::Native(cfg.base_denom.clone()) {
+        compute_accounted_balance(deps.storage)?
+    } else {
+        Uint128::zero()
+    };
+
+    let balance = asset_info.query_balance(&deps.querier, env.contract.address)?;
+    let recover_amount = balance.saturating_sub(reserved_amount);
+    if recover_amount.is_zero() {
+        return Err(ContractError::InvalidRecoverAsset {
+            reason: format!("no {} available to recover", asset_info),
+        });
+    }
+
+    let recover_asset = Asset {
+        info: asset_info,
+        amount: recover_amount,
+    };
+    Ok(Response::new()
+        .add_message(recover_asset.transfer_msg(recipient)?)
+        .add_attributes(vec![
+            attr("action", "recover_asset"),
+            attr("recovered_asset", recover_asset.to_string()),
+        ]))
+}
+
+// Adds and removes the contracts allowed to receive claimed tokens with a hook msg
+pub fn update_claim_targets(
+    deps: DepsMut,
+    info: MessageInfo,
+    add: Vec<String>,
+    remove: Vec<String>,
+) -> Result<Response, ContractError> {
+    let cfg = CONFIG.load(deps.storage)?;
+
+    if info.sender != cfg.operator {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    for target in &add {
+        let target = deps.api.addr_validate(target)?;
+        CLAIM_TARGETS.save(deps.storage, &target, &Empty {})?;
+    }
+    for target in &remove {
+        let target = deps.api.addr_validate(target)?;
+        CLAIM_TARGETS.remove(deps.storage, &target);
+    }
+
+    Ok(Response::new().add_attributes(vec![
+        attr("action", "update_claim_targets"),
+        attr("added", add.join(",")),
+        attr("removed", remove.join(",")),
+    ]))
+}
+
+pub fn admin_withdraw(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+) -> Result<Response, ContractError> {
+    let cfg = CONFIG.load(deps.storage)?;
+    let launch_cfg = cfg.launch_config.unwrap();
+
+    if info.sender != cfg.operator {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    if env.block.time.seconds() < launch_cfg.phase2_end {
+        return Err(ContractError::InvalidAdminWithdraw {
+            reason: "cannot withdraw funds yet".to_string(),
+        });
+    }
+
+    // deposits are final after phase 2, so the sale result is final once settled
+    let sale_result = SaleResult::load(deps.storage, &launch_cfg)?;
+    if !sale_result.settled {
+        return Err(ContractError::InvalidAdminWithdraw {
+            reason: "auction is not settled yet".to_string(),
+        });
+    }
+    let total_raised = sale_result.total_raised;
+    let admin_withdrawn = ADMIN_WITHDRAWN.load(deps.storage)?;
+    let withdraw_amount = total_raised - admin_withdrawn;
+    if withdraw_amount.is_zero() {
+        return Err(ContractError::InvalidAdminWithdraw {
+            reason: "no funds available to withdraw".to_string(),
+        });
+    }
+
+    ADMIN_WITHDRAWN.save(deps.storage, &(admin_withdrawn + withdraw_amount))?;
+
+    let host_portion = withdraw_amount * cfg.host_portion;
+
+    let base_denom_info = AssetInfo::Native(cfg.base_denom);
+    let host_withdraw_asset = Asset {
+        info: base_denom_info.clone(),
+        amount: host_portion,
+    };
+    let admin_withdraw_asset = Asset {
+        info: base_denom_info,
+        amount: withdraw_amount - host_portion,
+    };
+
+    let mut msgs: Vec<CosmosMsg> = vec![];
+    // because host_portion could be 0.0, check
+    if !host_withdraw_asset.amount.is_zero() {
+        msgs.push(host_withdraw_asset.transfer_msg(cfg.host_portion_receiver)?);
+    }
+
+    // send remaining amount to receiver
+    msgs.push(admin_withdraw_asset.transfer_msg(cfg.receiver)?);
+
+    Ok(Response::new().add_messages(msgs).add_attributes(vec![
+        attr("action", "admin_withdraw"),
+        attr("total_withdraw_amount", withdraw_amount.to_string()),
+        attr("host_amount", host_withdraw_asset.amount.to_string()),
+        attr("remaining_amount", admin_withdraw_asset.amount.to_string()),
+    ]))
+}
+
+pub fn recover_stray_funds(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    recipient: String,
+) -> Result<Response, ContractError> {
+    let cfg = CONFIG.load(deps.storage)?;
+
+    if info.sender != cfg.operator {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    let recipient = deps.api.addr_validate(&recipient)?;
+    let balance = query_balance(&deps.querier, env.contract.address, cfg.base_denom.clone())?;
+    let accounted_balance = compute_accounted_balance(deps.storage)?;
+    let stray_amount = balance.saturating_sub(accounted_balance);
+    if stray_amount.is_zero() {
+        return Err(ContractError::InvalidRecoverStrayFunds {
+            reason: "no stray funds available to recover".to_string(),
+        });
+    }
+
+    let stray_asset = Asset {
+        info: AssetInfo::Native(cfg.base_denom),
+        amount: stray_amount,
+    };
+    Ok(Response::new()
+        .add_message(stray_asset.transfer_msg(recipient)?)
+        .add_attributes(vec![
+            attr("action", "recover_stray_funds"),
+            attr("recovered_amount", stray_amount.to_string()),
+        ]))
+}
+
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn query(deps: Deps, env: Env, msg: QueryMsg) -> StdResult<Binary> {
+    match msg {
+        QueryMsg::Config {} => to_binary(&query_config(deps)?),
+        QueryMsg::DepositInfo { address } => to_binary(&query_deposit_info(deps, env, address)?),
+        QueryMsg::TokenAccounting {} => to_binary(&query_token_accounting(deps)?),
+        QueryMsg::DutchAuction {} => to_binary(&query_dutch_auction(deps, env)?),
+        QueryMsg::Referral { address } => to_binary(&query_referral(deps, address)?),
+        QueryMsg::Locks { address } => to_binary(&query_locks(deps, address)?),
+        QueryMsg::Position { token_id } => to_binary(&query_position(deps, env, token_id)?),
+        QueryMsg::Delegates {
+            address,
+            start_after,
+            limit,
+        } => to_binary(&query_delegates(deps, address, start_after, limit)?),
+        QueryMsg::ClaimTargets { start_after, limit } => {
+            to_binary(&query_claim_targets(deps, start_after, limit)?)
+        }
+    }
+}
+
+pub fn query_config(deps: Deps) -> StdResult<ConfigResponse> {
+    let cfg = CONFIG.load(deps.storage)?;
+
+    cfg.as_res()
+}
+
+pub fn query_deposit_info(deps: Deps, env: Env, address: String) -> StdResult<DepositResponse> {
+    let addr = deps.api.addr_validate(&address)?;
+    let cfg = CONFIG.load(deps.storage)?;
+    let launch_config = cfg.launch_config.clone().unwrap();
+    let deposit_info = DEPOSITS.load(deps.storage, &addr).unwrap_or_default();
+    let current_time = env.block.time.seconds();
+
+    let withdrawable_amount =
+        if let SaleMode::BondingCurve { curve, spread } = &launch_config.sale_mode {
+            // bought tokens can be sold back during phase 1
+            if current_time < launch_config.phase2_start && !deposit_info.tokens_bought.is_zero() {
+                let supply = BONDING_CURVE_SUPPLY.load(deps.storage)?;
+                let proceeds =
+                    curve.sell_proceeds(launch_config.amount, supply, deposit_info.tokens_bought);
+                proceeds - mul_ceil(proceeds, *spread)
+            } else {
+                Uint128::zero()
+            }
+        } else if matches!(
+            launch_config.sale_mode,
+            SaleMode::DutchAuction { .. } | SaleMode::SealedBid {}
+        ) {
+            Uint128::zero()
+        } else if current_time > launch_config.phase2_start && !deposit_info.amount.is_zero() {
+            if deposit_info.withdrew_phase2 || current_time >= launch_config.phase2_end {
+                Uint128::zero()
+            } else {
+                // locked deposits can not be withdrawn on phase 2
+                (deposit_info.amount - deposit_info.locked_amount)
+                    * phase2_withdrawable_portion(&launch_config, current_time)
+            }
+        } else {
+            deposit_info.amount
+        };
+
+    let sale_result = SaleResult::load(deps.storage, &launch_config)?;
+    let allocation = sale_result.allocation(&unclaimed_part(&deposit_info));
+    let claimable_allocation = claimable_part(deps.storage, &env, &addr, &deposit_info)?
+        .map(|claim| sale_result.allocation(&claim.part))
+        .unwrap_or_default();
+    let referral = REFERRALS.may_load(deps.storage, &addr)?.unwrap_or_default();
+    let referral_rewards_to_claim = if referral.rewards_claimed {
+        Uint128::zero()
+    } else {
+        sale_result
+            .rewards
+            .referral
+            .share(referral.referred_deposit)
+    };
+
+    Ok(DepositResponse {
+        deposit: deposit_info.amount,
+        total_deposit: sale_result.total_deposit,
+        withdrawable_amount,
+        tokens_to_claim: allocation.tokens,
+        refund_to_claim: allocation.refund,
+        bonus_to_claim: allocation.bonus,
+        referral_rewards_to_claim,
+        can_claim: current_time >= launch_config.phase2_end
+            && !claimable_allocation.is_empty()
+            && cfg.tokens_released
+            && !cfg.tokens_swept
+            // positions backed by receipts are claimed by redeeming the receipts
+            && cfg.receipt_token.is_none(),
+        settled: cfg.tokens_released
+            && (deposit_info.tokens_claimed || allocation.is_empty())
+            && referral_rewards_to_claim.is_zero(),
+        weight: deposit_info.weight,
+        bonus_tiers: launch_config.bonus_tiers,
+    })
+}
+
+pub fn query_token_accounting(deps: Deps) -> StdResult<TokenAccountingResponse> {
+    let cfg = CONFIG.load(deps.storage)?;
+    let launch_config = cfg.launch_config.unwrap();
+
+    compute_token_accounting(deps, &launch_config)
+}
+
+pub fn query_referral(deps: Deps, address: String) -> StdResult<ReferralResponse> {
+    let addr = deps.api.addr_validate(&address)?;
+    let cfg = CONFIG.load(deps.storage)?;
+    let launch_config = cfg.launch_config.unwrap();
+    let deposit_info = DEPOSITS.may_load(deps.storage, &addr)?.unwrap_or_default();
+    let referral = REFERRALS.may_load(deps.storage, &addr)?.unwrap_or_default();
+
+    let sale_result = SaleResult::load(deps.storage, &launch_config)?;
+    let rewards_to_claim = if referral.rewards_claimed {
+        Uint128::zero()
+    } else {
+        sale_result
+            .rewards
+            .referral
+            .share(referral.referred_deposit)
+    };
+
+    Ok(ReferralResponse {
+        referrer: deposit_info.referrer.map(|referrer| referrer.to_string()),
+        referred_deposit: referral.referred_deposit,
+        total_referred_deposit: sale_result.rewards.referral.total_shares,
+        rewards_to_claim,
+        rewards_claimed: referral.rewards_claimed,
+    })
+}
+
+pub fn query_locks(deps: Deps, address: String) -> StdResult<LocksResponse> {
+    let addr = deps.api.addr_validate(&address)?;
+    let locks = LOCKS.may_load(deps.storage, &addr)?.unwrap_or_default();
+
+    Ok(LocksResponse {
+        locks: locks
+            .into_iter()
+            .map(|lock| LockPositionResponse {
+                amount: lock.amount,
+                multiplier: lock.multiplier,
+                unlock_time: lock.unlock_time,
+            })
+            .collect(),
+    })
+}
+
+pub fn query_position(deps: Deps, env: Env, token_id: String) -> StdResult<PositionResponse> {
+    let cfg = CONFIG.load(deps.storage)?;
+    let launch_config = cfg.launch_config.clone().unwrap();
+    let position_nft = cfg
+        .position_nft
+        .as_ref()
+        .ok_or_else(|| StdError::generic_err("launch has no position nfts"))?;
+    let position = POSITIONS.load(deps.storage, &token_id)?;
+    let owner = query_position_owner(&deps.querier, position_nft, &token_id)?;
+    let current_time = env.block.time.seconds();
+
+    let withdrawable_amount = if current_time >= launch_config.phase2_end {
+        Uint128::zero()
+    } else if current_time > launch_config.phase2_start {
+        if position.withdrew_phase2 {
+            Uint128::zero()
+        } else {
+            position.amount * phase2_withdrawable_portion(&launch_config, current_time)
+        }
+    } else {
+        position.amount
+    };
+
+    // positions never hold locks, lock options are rejected on launches with position nfts
+    let sale_result = SaleResult::load(deps.storage, &launch_config)?;
+    let allocation = sale_result.allocation(&position.deposit_info());
+
+    Ok(PositionResponse {
+        owner: owner.to_string(),
+        deposit: position.amount,
+        deposit_time: position.deposit_time,
+        withdrew_phase2: position.withdrew_phase2,
+        withdrawable_amount,
+        tokens_to_claim: allocation.tokens,
+        refund_to_claim: allocation.refund,
+        can_claim: current_time >= launch_config.phase2_end
+            && !allocation.is_empty()
+            && cfg.tokens_released
+            && !cfg.tokens_swept
+            && !position.tokens_claimed,
+    })
+}
+
+pub fn query_delegates(
+    deps: Deps,
+    address: String,
+    start_after: Option<String>,
+    limit: Option<u32>,
+) -> StdResult<DelegatesResponse> {
+    let addr = deps.api.addr_validate(&address)?;
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start = start_after
+        .map(|delegate| deps.api.addr_validate(&delegate))
+        .transpose()?
+        .map(|delegate| Bound::exclusive(delegate.as_bytes()));
+
+    let delegates = DELEGATES
+        .prefix(&addr)
+        .range(deps.storage, start, None, Order::Ascending)
+        .take(limit)
+        .map(|item| {
+            let (key, delegation) = item?;
+            Ok(DelegateResponse {
+                delegate: String::from_utf8(key)
+                    .map_err(|e| StdError::invalid_utf8(e.to_string()))?,
+                permissions: delegation.permissions,
+                expires: delegation.expires,
+                transfer_recipients: delegation
+                    .transfer_recipients
+                    .iter()
+                    .map(|recipient| recipient.to_string())
+                    .collect(),
+            })
+        })
+        .collect::<StdResult<Vec<_>>>()?;
+
+    Ok(DelegatesResponse { delegates })
+}
+
+pub fn query_claim_targets(
+    deps: Deps,
+    start_after: Option<String>,
+    limit: Option<u32>,
+) -> StdResult<ClaimTargetsResponse> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start = start_after
+        .map(|target| deps.api.addr_validate(&target))
+        .transpose()?
+        .map(|target| Bound::exclusive(target.as_bytes()));
+
+    let targets = CLAIM_TARGETS
+        .keys(deps.storage, start, None, Order::Ascending)
+        .take(limit)
+        .map(|key| String::from_utf8(key).map_err(|e| StdError::invalid_utf8(e.to_string())))
+        .collect::<StdResult<Vec<_>>>()?;
+
+    Ok(ClaimTargetsResponse { targets })
+}
+
+pub fn query_dutch_auction(deps: Deps, env: Env) -> StdResult<DutchAuctionResponse> {
+    let cfg = CONFIG.load(deps.storage)?;
+    let launch_config = cfg.launch_config.unwrap();
+
+    let (start_price, floor_price) = match launch_config.sale_mode {
+        SaleMode::DutchAuction {
+            start_price,
+            floor_price,
+        } => (start_price, floor_price),
+        _ => return Err(StdError::generic_err("sale is not a dutch auction")),
+    };
+
+    let auction = DUTCH_AUCTION.may_load(deps.storage)?.unwrap_or_default();
+
+    Ok(DutchAuctionResponse {
+        current_price: dutch_auction_price(
+            &launch_config,
+            start_price,
+            floor_price,
+            env.block.time.seconds(),
+        ),
+        clearing_price: if auction.tokens_sold.is_zero() {
+            None
+        } else {
+            Some(auction.clearing_price)
+        },
+        tokens_sold: auction.tokens_sold,
+        remaining_supply: launch_config.amount - auction.tokens_sold,
+    })
+}
+
+fn compute_token_accounting(
+    deps: Deps,
+    launch_config: &LaunchConfig,
+) -> StdResult<TokenAccountingResponse> {
+    let sale_result = SaleResult::load(deps.storage, launch_config)?;
+    let claimed_deposit = TOTAL_CLAIMED_DEPOSIT.load(deps.storage)?;
+    let claimed_weight = TOTAL_CLAIMED_WEIGHT.load(deps.storage)?;
+    let claimed_loyal_deposit = TOTAL_CLAIMED_LOYAL_DEPOSIT.load(deps.storage)?;
+    let claimed_tokens = TOTAL_TOKEN.load(deps.storage)?;
+    let claimed_bonus = TOTAL_CLAIMED_BONUS.load(deps.storage)?;
+    let claimed_referred_deposit = TOTAL_CLAIMED_REFERRED_DEPOSIT.load(deps.storage)?;
+    let claimed_referral_rewards = TOTAL_CLAIMED_REFERRAL_REWARDS.load(deps.storage)?;
+    let swept = TOTAL_SWEPT.load(deps.storage)?;
+
+    let unclaimed = sale_result.unclaimed_tokens(claimed_tokens, claimed_deposit, claimed_weight)
+        + sale_result
+            .rewards
+            .loyalty_bonus
+            .unclaimed(claimed_loyal_deposit)
+        + sale_result
+            .rewards
+            .referral
+            .unclaimed(claimed_referred_deposit);
+    let claimed = claimed_tokens + claimed_bonus + claimed_referral_rewards;
+    let allocated = claimed + unclaimed;
+
+    Ok(TokenAccountingResponse {
+        amount: funded_amount(launch_config),
+        allocated,
+        claimed,
+        unsold: sale_result.unsold_tokens(launch_config),
+        dust: sale_result.tokens_sold + sale_result.rewards.distributed() - allocated,
+        swept,
+    })
+}
+
+// where the claimed tokens go, the refund always goes to the recipient
+pub enum ClaimDestination {
+    // transferred to the recipient
+    Recipient,
+    // sent to an allowed claim target with the hook msg
+    Hook { contract: Addr, msg: Binary },
+    // bonded to the recipient in the staking contract
+    Stake,
+}
+
+// payout of a claim
+struct Payout {
+    recipient: Addr,
+    destination: ClaimDestination,
+    // relayer paid this amount out of the tokens
+    tip: Option<(Addr, Uint128)>,
+}
+
+impl Payout {
+    fn to(recipient: Addr) -> Self {
+        Payout {
+            recipient,
+            destination: ClaimDestination::Recipient,
+            tip: None,
+        }
+    }
+}
+
+// part of a position claimed at once
+struct Claim {
+    part: DepositInfo,
+    // whether the claim settles the position, or only the part outside its running locks
+    settles: bool,
+}
+
+// Flags the claimed part of the position and returns the messages paying out its allocation
+fn claim_allocation(
+    storage: &mut dyn Storage,
+    cfg: &Config,
+    depositor: &Addr,
+    deposit_info: &mut DepositInfo,
+    claim: &Claim,
+    allocation: &Allocation,
+    payout: &Payout,
+) -> StdResult<Vec<CosmosMsg>> {
+    // update claimed flag, we don't delete storage to keep the record
+    if claim.settles {
+        deposit_info.tokens_claimed = true;
+    } else {
+        deposit_info.unlocked_claimed = true;
+    }
+    DEPOSITS.save(storage, depositor, deposit_info)?;
+
+    pay_allocation(storage, cfg, &claim.part, allocation, payout)
+}
+
+// Fails if the tokens can not be claimed with withdraw_tokens at the current time
+fn ensure_tokens_claimable(env: &Env, cfg: &Config) -> Result<(), ContractError> {
+    let launch_cfg = cfg.launch_config.as_ref().unwrap();
+
+    if env.block.time.seconds() < launch_cfg.phase2_end || !cfg.tokens_released {
+        return Err(ContractError::InvalidWithdrawTokens {
+            reason: "cannot withdraw tokens yet".to_string(),
+        });
+    }
+
+    if cfg.tokens_swept {
+        return Err(ContractError::InvalidWithdrawTokens {
+            reason: "claim deadline is over, unclaimed tokens were swept".to_string(),
+        });
+    }
+
+    if cfg.receipt_token.is_some() {
+        return Err(ContractError::InvalidWithdrawTokens {
+            reason: "tokens are claimed by redeeming the receipt tokens".to_string(),
+        });
+    }
+
+    Ok(())
+}
+
+// Returns whether a lock position of the depositor did not end yet
+fn is_locked(storage: &dyn Storage, env: &Env, depositor: &Addr) -> StdResult<bool> {
+    let locks = LOCKS.may_load(storage, depositor)?.unwrap_or_default();
+    Ok(locks
+        .iter()
+        .any(|lock| env.block.time.seconds() < lock.unlock_time))
+}
+
+// Part of the position that was not claimed yet, only the locked part once the rest was claimed
+fn unclaimed_part(deposit_info: &DepositInfo) -> DepositInfo {
+    if deposit_info.unlocked_claimed {
+        DepositInfo {
+            amount: deposit_info.locked_amount,
+            weight: deposit_info.locked_weight,
+            ..deposit_info.clone()
+        }
+    } else {
+        deposit_info.clone()
+    }
+}
+
+// Loads up to limit skipped positions whose locks ended, failing if there are none left to process
+fn skipped_positions(
+    storage: &dyn Storage,
+    env: &Env,
+    limit: usize,
+) -> Result<Vec<(Addr, DepositInfo)>, ContractError> {
+    let mut positions = vec![];
+    let mut still_locked = false;
+    for key in DISTRIBUTION_SKIPPED.keys(storage, None, None, Order::Ascending) {
+        if positions.len() == limit {
+            break;
+        }
+        let depositor = addr_from_key(key)?;
+        let deposit_info = DEPOSITS.load(storage, &depositor)?;
+        if deposit_info.tokens_claimed {
+            // claimed by the depositor since it was skipped, removed once it is revisited
+            positions.push((depositor, deposit_info));
+        } else if is_locked(storage, env, &depositor)? {
+            still_locked = true;
+        } else {
+            positions.push((depositor, deposit_info));
+        }
+    }
+
+    if positions.is_empty() {
+        let reason = if still_locked {
+            "the remaining positions are locked"
+        } else {
+            "all deposits were already processed"
+        };
+        return Err(ContractError::InvalidDistributeTokens {
+            reason: reason.to_string(),
+        });
+    }
+
+    Ok(positions)
+}
+
+fn addr_from_key(key: Vec<u8>) -> StdResult<Addr> {
+    Ok(Addr::unchecked(
+        String::from_utf8(key).map_err(|e| StdError::invalid_utf8(e.to_string()))?,
+    ))
+}
+
+// Part of the position that can be claimed now, the locked part is held until the last lock ends
+fn claimable_part(
+    storage: &dyn Storage,
+    env: &Env,
+    depositor: &Addr,
+    deposit_info: &DepositInfo,
+) -> StdResult<Option<Claim>> {
+    if deposit_info.tokens_claimed {
+        return Ok(None);
+    }
+    if deposit_info.locked_amount.is_zero() || !is_locked(storage, env, depositor)? {
+        return Ok(Some(Claim {
+            part: unclaimed_part(deposit_info),
+            settles: true,
+        }));
+    }
+    if deposit_info.unlocked_claimed {
+        return Ok(None);
+    }
+
+    Ok(Some(Claim {
+        part: DepositInfo {
+            amount: deposit_info.amount - deposit_info.locked_amount,
+            weight: deposit_info.weight - deposit_info.locked_weight,
+            locked_amount: Uint128::zero(),
+            locked_weight: Uint128::zero(),
+            ..deposit_info.clone()
+        },
+        settles: false,
+    }))
+}
+
+// Loads the deposit of the depositor with the part claimable now and its allocation, failing if
+// there is nothing to claim
+fn unclaimed_allocation(
+    storage: &dyn Storage,
+    env: &Env,
+    cfg: &Config,
+    depositor: &Addr,
+) -> Result<(DepositInfo, Claim, Allocation), ContractError> {
+    let deposit_info =
+        DEPOSITS
+            .load(storage, depositor)
+            .map_err(|_| ContractError::InvalidWithdrawTokens {
+                reason: "deposit information not found".to_string(),
+            })?;
+    if deposit_info.tokens_claimed {
+        return Err(ContractError::InvalidWithdrawTokens {
+            reason: "tokens were already claimed".to_string(),
+        });
+    }
+
+    let locked_err = || ContractError::InvalidWithdrawTokens {
+        reason: "tokens are locked until the last lock ends".to_string(),
+    };
+    let claim = claimable_part(storage, env, depositor, &deposit_info)?.ok_or_else(locked_err)?;
+
+    let sale_result = SaleResult::load(storage, cfg.launch_config.as_ref().unwrap())?;
+    let allocation = sale_result.allocation(&claim.part);
+    if allocation.is_empty() {
+        if !claim.settles {
+            return Err(locked_err());
+        }
+        return Err(ContractError::InvalidWithdrawTokens {
+            reason: "no tokens available for withdraw".to_string(),
+        });
+    }
+
+    Ok((deposit_info, claim, allocation))
+}
+
+// Records the claim of the deposit and returns the messages paying out its allocation
+fn pay_allocation(
+    storage: &mut dyn Storage,
+    cfg: &Config,
+    deposit_info: &DepositInfo,
+    allocation: &Allocation,
+    payout: &Payout,
+) -> StdResult<Vec<CosmosMsg>> {
+    record_claim(storage, deposit_info, allocation)?;
+    payout_msgs(cfg, payout, allocation.total_tokens(), allocation.refund)
+}
+
+// Adds the claimed allocation of the deposit to the claim totals
+fn record_claim(
+    storage: &mut dyn Storage,
+    deposit_info: &DepositInfo,
+    allocation: &Allocation,
+) -> StdResult<()> {
+    TOTAL_TOKEN.update(storage, |curr| -> StdResult<Uint128> {
+        Ok(curr + allocation.tokens)
+    })?;
+    TOTAL_CLAIMED_BONUS.update(storage, |curr| -> StdResult<Uint128> {
+        Ok(curr + allocation.bonus)
+    })?;
+    TOTAL_REFUNDED.update(storage, |curr| -> StdResult<Uint128> {
+        Ok(curr + allocation.refund)
+    })?;
+    TOTAL_CLAIMED_DEPOSIT.update(storage, |curr| -> StdResult<Uint128> {
+        Ok(curr + deposit_info.amount)
+    })?;
+    TOTAL_CLAIMED_WEIGHT.update(storage, |curr| -> StdResult<Uint128> {
+        Ok(curr + deposit_info.weight)
+    })?;
+    if !deposit_info.withdrew_phase2 {
+        TOTAL_CLAIMED_LOYAL_DEPOSIT.update(storage, |curr| -> StdResult<Uint128> {
+            Ok(curr + deposit_info.amount)
+        })?;
+    }
+
+    Ok(())
+}
+
+// Adds the claimed referral rewards to the claim totals
+fn record_referral_claim(
+    storage: &mut dyn Storage,
+    referral: &ReferralInfo,
+    rewards: Uint128,
+) -> StdResult<()> {
+    TOTAL_CLAIMED_REFERRAL_REWARDS
+        .update(storage, |curr| -> StdResult<Uint128> { Ok(curr + rewards) })?;
+    TOTAL_CLAIMED_REFERRED_DEPOSIT.update(storage, |curr| -> StdResult<Uint128> {
+        Ok(curr + referral.referred_deposit)
+    })?;
+
+    Ok(())
+}
+
+// Returns the messages paying the tokens minus the tip and the refund to the payout
+fn payout_msgs(
+    cfg: &Config,
+    payout: &Payout,
+    tokens: Uint128,
+    refund: Uint128,
+) -> StdResult<Vec<CosmosMsg>> {
+    let mut msgs: Vec<CosmosMsg> = vec![];
+    let tip = payout.tip.as_ref().map(|(_, tip)| *tip).unwrap_or_default();
+    let tokens = tokens - tip;
+    if !tokens.is_zero() {
+        let hook = match &payout.destination {
+            ClaimDestination::Recipient => None,
+            ClaimDestination::Hook { contract, msg } => Some((contract.clone(), msg.clone())),
+            // delegates can not change the recipient, so they only stake for the depositor
+            ClaimDestination::Stake => {
+                let staking_contract = cfg
+                    .staking_contract
+                    .clone()
+                    .ok_or_else(|| StdError::generic_err("launch has no staking contract"))?;
+                Some((staking_contract, bond_hook(&payout.recipient)?))
+            }
+        };
+        match hook {
+            Some((contract, msg)) => msgs.push(CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr: cfg.token.to_string(),
+                msg: to_binary(&Cw20ExecuteMsg::Send {
+                    contract: contract.to_string(),
+                    amount: tokens,
+                    msg,
+                })?,
+                funds: vec![],
+            })),
+            None => {
+                let to_send = Asset {
+                    info: AssetInfo::Cw20(cfg.token.clone()),
+                    amount: tokens,
+                };
+                msgs.push(to_send.transfer_msg(&payout.recipient)?);
+            }
+        }
+    }
+    if let Some((relayer, tip)) = &payout.tip {
+        if !tip.is_zero() {
+            let to_tip = Asset {
+                info: AssetInfo::Cw20(cfg.token.clone()),
+                amount: *tip,
+            };
+            msgs.push(to_tip.transfer_msg(relayer)?);
+        }
+    }
+    if !refund.is_zero() {
+        let to_refund = Asset {
+            info: AssetInfo::Native(cfg.base_denom.clone()),
+            amount: refund,
+        };
+        msgs.push(to_refund.transfer_msg(&payout.recipient)?);
+    }
+
+    Ok(msgs)
+}
+
+// Returns the depositor whose position the sender acts on, the sender itself by default
+fn resolve_depositor(
+    deps: Deps,
+    env: &Env,
+    sender: &Addr,
+    depositor: Option<String>,
+    permission: Permission,
+) -> Result<Addr, ContractError> {
+    let depositor = match depositor {
+        Some(depositor) => deps.api.addr_validate(&depositor)?,
+        None => return Ok(sender.clone()),
+    };
+    if depositor == *sender {
+        return Ok(depositor);
+    }
+
+    let delegation = DELEGATES
+        .may_load(deps.storage, (&depositor, sender))?
+        .ok_or(ContractError::Unauthorized {})?;
+    if delegation.expires.is_expired(&env.block) || !delegation.permissions.contains(&permission) {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    Ok(depositor)
+}
+
+// Returns the address paid by an action on the position, delegates always pay the depositor
+fn payout_recipient(
+    api: &dyn Api,
+    sender: &Addr,
+    depositor: &Addr,
+    recipient: Option<String>,
+) -> Result<Addr, ContractError> {
+    match recipient {
+        None => Ok(depositor.clone()),
+        Some(_) if sender != depositor => Err(ContractError::Unauthorized {}),
+        Some(recipient) => Ok(api.addr_validate(&recipient)?),
+    }
+}
+
+// Loads the position of the nft, failing unless the sender owns the nft
+fn load_owned_position(
+    deps: Deps,
+    cfg: &Config,
+    token_id: &str,
+    sender: &Addr,
+) -> Result<Position, ContractError> {
+    let position_nft = cfg
+        .position_nft
+        .as_ref()
+        .ok_or_else(|| StdError::generic_err("launch has no position nfts"))?;
+    let position = POSITIONS.load(deps.storage, token_id)?;
+
+    if query_position_owner(&deps.querier, position_nft, token_id)? != *sender {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    Ok(position)
+}
+
+// Portion of the deposit that can be withdrawn on phase 2, it decreases every slot
+fn phase2_withdrawable_portion(launch_config: &LaunchConfig, current_time: u64) -> Decimal {
+    let current_slot = (launch_config.phase2_end - current_time) / launch_config.phase2_slot_period;
+    let total_slots =
+        (launch_config.phase2_end - launch_config.phase2_start) / launch_config.phase2_slot_period;
+
+    Decimal::from_ratio(current_slot + 1u64, total_slots).min(Decimal::one())
+}
+
+// Returns the amount of sale tokens held by the contract that were not claimed or swept yet
+fn compute_unclaimed_tokens(storage: &dyn Storage, cfg: &Config) -> StdResult<Uint128> {
+    let launch_config = match &cfg.launch_config {
+        Some(launch_config) => launch_config,
+        None => return Ok(Uint128::zero()),
+    };
+    let total_claimed = TOTAL_TOKEN.load(storage)?
+        + TOTAL_CLAIMED_BONUS.load(storage)?
+        + TOTAL_CLAIMED_REFERRAL_REWARDS.load(storage)?;
+    let total_swept = TOTAL_SWEPT.load(storage)?;
+
+    // unsold tokens leave the contract when the tokens are released
+    let unsold = if cfg.tokens_released {
+        SaleResult::load(storage, launch_config)?.unsold_tokens(launch_config)
+    } else {
+        Uint128::zero()
+    };
+
+    Ok(funded_amount(launch_config) - total_claimed - total_swept - unsold)
+}
+
+// Moves the deposit brought in by the referrer and the total referred deposit to the new amount
+fn update_referral(
+    storage: &mut dyn Storage,
+    referrer: &Addr,
+    old_amount: Uint128,
+    new_amount: Uint128,
+) -> StdResult<()> {
+    let mut referral = REFERRALS.may_load(storage, referrer)?.unwrap_or_default();
+    referral.referred_deposit = referral.referred_deposit + new_amount - old_amount;
+    REFERRALS.save(storage, referrer, &referral)?;
+
+    TOTAL_REFERRED_DEPOSIT.update(storage, |curr| -> StdResult<Uint128> {
+        Ok(curr + new_amount - old_amount)
+    })?;
+
+    Ok(())
+}
+
+// Takes amount from the last locks of the depositor and returns the weight released
+fn release_locks(
+    storage: &mut dyn Storage,
+    depositor: &Addr,
+    amount: Uint128,
+) -> StdResult<Uint128> {
+    let mut locks = LOCKS.load(storage, depositor)?;
+
+    let mut remaining = amount;
+    let mut released_weight = Uint128::zero();
+    while !remaining.is_zero() {
+        let last = locks
+            .last_mut()
+            .ok_or_else(|| StdError::generic_err("withdraw amount exceeds locks"))?;
+
+        let released = remaining.min(last.amount);
+        let weight = last.weight
+            - last
+                .weight
+                .multiply_ratio(last.amount - released, last.amount);
+        released_weight += weight;
+        remaining -= released;
+        last.amount -= released;
+        last.weight -= weight;
+        if last.amount.is_zero() {
+            locks.pop();
+        }
+    }
+
+    LOCKS.save(storage, depositor, &locks)?;
+
+    Ok(released_weight)
+}
+
+// Removes amount from the last deposits of the depositor and returns the weight removed
+fn remove_weight(
+    storage: &mut dyn Storage,
+    depositor: &Addr,
+    amount: Uint128,
+) -> StdResult<Uint128> {
+    let removed = take_weighted_deposits(storage, depositor, amount)?;
+    Ok(weight_of(&removed))
+}
+
+// Takes amount from the last deposits of the depositor, returns the parts taken from last to first
+fn take_weighted_deposits(
+    storage: &mut dyn Storage,
+    depositor: &Addr,
+    amount: Uint128,
+) -> StdResult<Vec<WeightedDeposit>> {
+    let mut weighted_deposits = WEIGHTED_DEPOSITS.load(storage, depositor)?;
+
+    let mut remaining = amount;
+    let mut taken = vec![];
+    while !remaining.is_zero() {
+        let last = weighted_deposits
+            .last_mut()
+            .ok_or_else(|| StdError::generic_err("withdraw amount exceeds weighted deposits"))?;
+
+        let removed = remaining.min(last.amount);
+        taken.push(WeightedDeposit {
+            amount: removed,
+            time_remaining: last.time_remaining,
+        });
+        remaining -= removed;
+        last.amount -= removed;
+        if last.amount.is_zero() {
+            weighted_deposits.pop();
+        }
+    }
+
+    WEIGHTED_DEPOSITS.save(storage, depositor, &weighted_deposits)?;
+
+    Ok(taken)
+}
+
+// Returns the total weight of the weighted deposits
+fn weight_of(weighted_deposits: &[WeightedDeposit]) -> Uint128 {
+    weighted_deposits
+        .iter()
+        .fold(Uint128::zero(), |acc, deposit| {
+            acc + deposit.amount * Uint128::from(deposit.time_remaining)
+        })
+}
+
+// Moves the batch auction bid of the depositor to its new amount in the sorted indices
+fn update_bid(
+    storage: &mut dyn Storage,
+    depositor: &Addr,
+    max_price: Decimal,
+    amount_before: Uint128,
+    amount_after: Uint128,
+) -> StdResult<()> {
+    let price_key = U128Key::new(max_price.numerator());
+
+    let level_deposit = BID_LEVELS
+        .may_load(storage, price_key.clone())?
+        .unwrap_or_default()
+        + amount_after
+        - amount_before;
+    if level_deposit.is_zero() {
+        BID_LEVELS.remove(storage, price_key.clone());
+    } else {
+        BID_LEVELS.save(storage, price_key.clone(), &level_deposit)?;
+    }
+
+    if amount_after.is_zero() {
+        BIDS.remove(storage, (price_key, depositor));
+    } else {
+        BIDS.save(storage, (price_key, depositor), &Empty {})?;
+    }
+
+    Ok(())
+}
+
+// Walks at most limit price levels looking for the clearing price, returns the levels processed
+fn find_clearing_price(
+    storage: &dyn Storage,
+    launch_config: &LaunchConfig,
+    auction: &mut BatchAuction,
+    limit: usize,
+) -> StdResult<usize> {
+    let end = auction
+        .level_cursor
+        .map(|price| Bound::exclusive(U128Key::new(price.numerator()).joined_key()));
+    let levels = BID_LEVELS
+        .range(storage, None, end, Order::Descending)
+        .take(limit)
+        .collect::<StdResult<Vec<_>>>()?;
+
+    let supply = launch_config.amount;
+    for (processed, (key, level_deposit)) in levels.iter().enumerate() {
+        let price = decimal_from_key(key)?;
+        auction.marginal_price = price;
+        auction.marginal_deposit = *level_deposit;
+
+        // demand at this price reaches the supply
+        if auction.cumulative_deposit + *level_deposit >= supply * price {
+            // price at which the bids above this level buy the whole supply
+            let upper_price = price_ceil(auction.cumulative_deposit, supply);
+            if upper_price >= price {
+                // bids above clear the supply on their own, the level is left out
+                auction.clearing_price = Some(upper_price);
+                auction.marginal_tokens = Uint128::zero();
+            } else {
+                // the level gets what the bids above leave
+                auction.clearing_price = Some(price);
+                auction.marginal_tokens =
+                    supply - tokens_at_price(auction.cumulative_deposit, price);
+            }
+
+            return Ok(processed + 1);
+        }
+
+        auction.cumulative_deposit += *level_deposit;
+        auction.level_cursor = Some(price);
+    }
+
+    if levels.len() < limit {
+        // the supply is not sold out, every bid wins at the lowest bid price
+        auction.clearing_price = Some(auction.marginal_price);
+        auction.marginal_tokens = if auction.marginal_price.is_zero() {
+            Uint128::zero()
+        } else {
+            tokens_at_price(auction.marginal_deposit, auction.marginal_price)
+        };
+    }
+
+    Ok(levels.len())
+}
+
+// Allocates tokens to at most limit winning bids, from the highest price
+fn allocate_bids(
+    storage: &mut dyn Storage,
+    launch_config: &LaunchConfig,
+    auction: &mut BatchAuction,
+    limit: usize,
+) -> StdResult<()> {
+    let clearing_price = auction.clearing_price.unwrap_or_default();
+    let end = auction.bid_cursor.as_ref().map(|(price, addr)| {
+        Bound::exclusive((U128Key::new(price.numerator()), addr).joined_key())
+    });
+    let bids = BIDS
+        .range(storage, None, end, Order::Descending)
+        .take(limit)
+        .collect::<StdResult<Vec<_>>>()?;
+
+    for (key, _) in bids.iter() {
+        let (price, depositor) = parse_bid_key(key)?;
+        if price < auction.marginal_price {
+            // the remaining bids lost, they keep no tokens and get a full refund
+            auction.settled = true;
+            return Ok(());
+        }
+
+        let mut deposit_info = DEPOSITS.load(storage, &depositor)?;
+        let mut tokens = tokens_at_price(deposit_info.amount, clearing_price);
+        if price == auction.marginal_price {
+            tokens = tokens.min(
+                auction
+                    .marginal_tokens
+                    .multiply_ratio(deposit_info.amount, auction.marginal_deposit),
+            );
+        }
+        // rounding can never sell more than the supply, the last winning bid gets the remainder
+        tokens = tokens.min(launch_config.amount - auction.tokens_sold);
+
+        deposit_info.tokens_bought = tokens;
+        DEPOSITS.save(storage, &depositor, &deposit_info)?;
+
+        auction.tokens_sold += tokens;
+        auction.total_raised += mul_ceil(tokens, clearing_price);
+        auction.bid_cursor = Some((price, depositor));
+    }
+
+    if bids.len() < limit {
+        auction.settled = true;
+    }
+
+    Ok(())
+}
+
+fn decimal_from_key(key: &[u8]) -> StdResult<Decimal> {
+    let bytes: [u8; 16] = key
+        .try_into()
+        .map_err(|_| StdError::generic_err("invalid price key"))?;
+
+    Ok(Decimal::from_ratio(
+        u128::from_be_bytes(bytes),
+        Decimal::one().denominator(),
+    ))
+}
+
+fn parse_bid_key(key: &[u8]) -> StdResult<(Decimal, Addr)> {
+    // the price is prefixed with its length as a big endian u16 by the composite key encoding
+    if key.len() < 2 {
+        return Err(StdError::generic_err("invalid bid key"));
+    }
+    let price_end = 2 + u16::from_be_bytes([key[0], key[1]]) as usize;
+    if key.len() < price_end {
+        return Err(StdError::generic_err("invalid bid key"));
+    }
+    let price = decimal_from_key(&key[2..price_end])?;
+    let depositor = String::from_utf8(key[price_end..].to_vec())
+        .map_err(|e| StdError::invalid_utf8(e.to_string()))?;
+
+    Ok((price, Addr::unchecked(depositor)))
+}
+
+// Returns the base denom balance owed to depositors and proceeds receivers
+fn compute_accounted_balance(storage: &dyn Storage) -> StdResult<Uint128> {
+    let total_deposit = TOTAL_DEPOSIT.load(storage)?;
+    let total_escrow = TOTAL_ESCROW.load(storage)?;
+    let total_refunded = TOTAL_REFUNDED.load(storage)?;
+    let admin_withdrawn = ADMIN_WITHDRAWN.load(storage)?;
+    let sells = BONDING_CURVE_SELLS.may_load(storage)?.unwrap_or_default();
+
+    Ok(total_deposit + sells.cost + total_escrow
+        - sells.proceeds
+        - total_refunded
+        - admin_withdrawn)
+}
+
+// Hash committed by a sealed bid, the depositor is included so commitments can not be copied
+pub fn bid_commitment(depositor: &Addr, amount: Uint128, salt: &str) -> Binary {
+    let preimage = format!("{}:{}:{}", depositor, amount, salt);
+
+    Binary::from(Sha256::digest(preimage.as_bytes()).as_slice())
 }
@@ -0,0 +1,86 @@
+use cosmwasm_std::{Decimal, Fraction, Uint128};
+
+use crate::msg::Curve;
+use crate::sale::mul_ceil;
+
+// highest exponent accepted for polynomial curves
+pub const MAX_EXPONENT: u32 = 8;
+
+impl Curve {
+    fn params(&self) -> (Decimal, Decimal, u32) {
+        match self {
+            Curve::Linear { base_price, slope } => (*base_price, *slope, 1),
+            Curve::Polynomial {
+                base_price,
+                slope,
+                exponent,
+            } => (*base_price, *slope, *exponent),
+        }
+    }
+
+    // Price of the next token once supply tokens out of amount are sold
+    pub fn spot_price(&self, amount: Uint128, supply: Uint128) -> Decimal {
+        let (base_price, slope, exponent) = self.params();
+        let sold = Decimal::from_ratio(supply, amount);
+
+        base_price + mul(slope, pow(sold, exponent))
+    }
+
+    // Base denom needed to buy tokens on top of supply, rounded up
+    pub fn buy_cost(&self, amount: Uint128, supply: Uint128, tokens: Uint128) -> Uint128 {
+        mul_ceil(amount, self.area(amount, supply, supply + tokens))
+    }
+
+    // Base denom paid for selling tokens back from supply, rounded down
+    pub fn sell_proceeds(&self, amount: Uint128, supply: Uint128, tokens: Uint128) -> Uint128 {
+        amount * self.area(amount, supply - tokens, supply)
+    }
+
+    // Returns the most tokens the funds can buy on top of supply, without going above amount,
+    // together with their cost
+    pub fn tokens_for_funds(
+        &self,
+        amount: Uint128,
+        supply: Uint128,
+        funds: Uint128,
+    ) -> (Uint128, Uint128) {
+        // the cost grows with the tokens, so bisect the last affordable amount
+        let mut low = 0u128;
+        let mut high = (amount - supply).u128();
+        while low < high {
+            let mid = high - (high - low) / 2;
+            if self.buy_cost(amount, supply, Uint128::from(mid)) <= funds {
+                low = mid;
+            } else {
+                high = mid - 1;
+            }
+        }
+
+        let tokens = Uint128::from(low);
+        (tokens, self.buy_cost(amount, supply, tokens))
+    }
+
+    // Area under the price curve between the two supplies, per token of amount
+    fn area(&self, amount: Uint128, from: Uint128, to: Uint128) -> Decimal {
+        self.integral(Decimal::from_ratio(to, amount))
+            - self.integral(Decimal::from_ratio(from, amount))
+    }
+
+    // Integral of the price between 0 and the sold fraction of the amount
+    fn integral(&self, sold: Decimal) -> Decimal {
+        let (base_price, slope, exponent) = self.params();
+
+        mul(base_price, sold) + mul(slope, pow(sold, exponent + 1)) / Uint128::from(exponent + 1)
+    }
+}
+
+fn mul(a: Decimal, b: Decimal) -> Decimal {
+    Decimal::from_ratio(
+        Uint128::from(a.numerator()).multiply_ratio(b.numerator(), b.denominator()),
+        a.denominator(),
+    )
+}
+
+fn pow(base: Decimal, exponent: u32) -> Decimal {
+    (0..exponent).fold(Decimal::one(), |acc, _| mul(acc, base))
+}
@@ -27,12 +27,63 @@ pub enum ContractError {
     #[error("Invalid withdraw tokens: {reason}")]
     InvalidWithdrawTokens { reason: String },
 
+    #[error("Invalid transfer position: {reason}")]
+    InvalidTransferPosition { reason: String },
+
+    #[error("Invalid redeem: {reason}")]
+    InvalidRedeem { reason: String },
+
+    #[error("Invalid authorize: {reason}")]
+    InvalidAuthorize { reason: String },
+
+    #[error("Invalid revoke: {reason}")]
+    InvalidRevoke { reason: String },
+
+    #[error("Invalid claim tip: {reason}")]
+    InvalidClaimTip { reason: String },
+
+    #[error("Invalid withdraw and claim: {reason}")]
+    InvalidWithdrawAndClaim { reason: String },
+
+    #[error("Invalid claim refund: {reason}")]
+    InvalidClaimRefund { reason: String },
+
+    #[error("Invalid reclaim escrow: {reason}")]
+    InvalidReclaimEscrow { reason: String },
+
     #[error("Invalid admin withdraw: {reason}")]
     InvalidAdminWithdraw { reason: String },
 
     #[error("Invalid release tokens: {reason}")]
     InvalidReleaseTokens { reason: String },
 
+    #[error("Invalid distribute tokens: {reason}")]
+    InvalidDistributeTokens { reason: String },
+
+    #[error("Invalid claim referral rewards: {reason}")]
+    InvalidClaimReferralRewards { reason: String },
+
+    #[error("Invalid commit bid: {reason}")]
+    InvalidCommitBid { reason: String },
+
+    #[error("Invalid reveal bid: {reason}")]
+    InvalidRevealBid { reason: String },
+
+    #[error("Invalid settle: {reason}")]
+    InvalidSettle { reason: String },
+
+    #[error("Invalid sweep unclaimed tokens: {reason}")]
+    InvalidSweepUnclaimedTokens { reason: String },
+
+    #[error("Invalid sweep dust: {reason}")]
+    InvalidSweepDust { reason: String },
+
+    #[error("Invalid recover stray funds: {reason}")]
+    InvalidRecoverStrayFunds { reason: String },
+
+    #[error("Invalid recover asset: {reason}")]
+    InvalidRecoverAsset { reason: String },
+
     #[error("Fee can not be bigger than 1")]
     InvalidFee {},
 }
@@ -1,7 +1,12 @@
 pub mod contract;
+mod curve;
 mod error;
 pub mod msg;
+mod position_nft;
 mod querier;
+mod receipt;
+mod sale;
+mod staking;
 pub mod state;
 
 #[cfg(test)]
@@ -1,4 +1,6 @@
-use cosmwasm_std::{Decimal, Uint128};
+use cosmwasm_std::{Binary, Decimal, Uint128};
+use cw20::{Cw20ReceiveMsg, Expiration};
+use cw_asset::AssetInfoUnchecked;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
@@ -12,6 +14,91 @@ pub struct LaunchConfig {
     pub phase2_end: u64,
     // time in seconds for each slot in phase2
     pub phase2_slot_period: u64,
+    // optional deadline to claim tokens, after it the operator can sweep the unclaimed tokens
+    pub claim_deadline: Option<u64>,
+    // defaults to pro-rata, the only sale mode of launches configured before sale modes were added
+    #[serde(default)]
+    pub sale_mode: SaleMode,
+    // optional bonus multipliers for early deposits sorted by time, only on pro-rata sales
+    #[serde(default)]
+    pub bonus_tiers: Vec<BonusTier>,
+    // optional bonus tokens funded on top of amount, shared by the depositors that did not
+    // withdraw during phase 2 pro-rata to their deposits
+    pub loyalty_bonus: Option<Uint128>,
+    // optional lock durations in seconds and their allocation multipliers, only on pro-rata sales
+    #[serde(default)]
+    pub lock_options: Vec<LockOption>,
+    // optional portion of amount set aside for the referrers, shared pro-rata to the deposits
+    // they brought in, only on pro-rata and time weighted sales
+    pub referral_portion: Option<Decimal>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct LockOption {
+    pub duration: u64,
+    pub multiplier: Decimal,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct BonusTier {
+    // deposits made before this time get the multiplier
+    pub until_timestamp: u64,
+    pub multiplier: Decimal,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum SaleMode {
+    // amount is split between depositors pro-rata to their deposits
+    ProRata {},
+    // amount is sold at a fixed price in base denom per token, oversubscribed deposits are
+    // partially refunded and unsold tokens are returned to the operator
+    FixedPrice {
+        price: Decimal,
+    },
+    // price falls linearly from start_price to floor_price over phase 1, each deposit buys tokens
+    // at the current price until the amount is sold out and all buyers settle at the last price
+    DutchAuction {
+        start_price: Decimal,
+        floor_price: Decimal,
+    },
+    // each deposit is a bid with a max price per token, after phase 2 the auction is settled at
+    // a uniform clearing price, winning bids pay it and losing bids are fully refunded
+    BatchAuction {},
+    // bids are committed as a hash with an escrow during phase 1 and revealed during phase 2,
+    // revealed amounts are split pro-rata and unrevealed escrows are refunded
+    SealedBid {},
+    // tokens are bought along the bonding curve during phase 1 and can be sold back during phase 1
+    // minus the spread, claimable tokens are fixed when bought and unsold tokens are returned
+    BondingCurve {
+        curve: Curve,
+        spread: Decimal,
+    },
+    // amount is split between depositors pro-rata to their weight, each deposit weighs its amount
+    // times the seconds left in phase 1 and withdrawals remove the weight of the last deposits
+    TimeWeighted {},
+}
+
+impl Default for SaleMode {
+    fn default() -> Self {
+        SaleMode::ProRata {}
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Curve {
+    // price grows linearly from base_price to base_price + slope once the whole amount is sold
+    Linear {
+        base_price: Decimal,
+        slope: Decimal,
+    },
+    // price is base_price + slope * (sold / amount) ^ exponent
+    Polynomial {
+        base_price: Decimal,
+        slope: Decimal,
+        exponent: u32,
+    },
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
@@ -22,24 +109,179 @@ pub struct InstantiateMsg {
     pub base_denom: String,
     pub host_portion: Decimal,
     pub host_portion_receiver: String,
+    pub unclaimed_tokens_receiver: String,
+    // optional cw20 receipt token instantiated by the forge, minted 1:1 with the deposits,
+    // only on pro-rata sales without bonus tiers, lock options or rewards
+    pub receipt_token: Option<ReceiptTokenInfo>,
+    // optional cw721 contract instantiated by the forge, each deposit mints a position nft whose
+    // owner controls its withdraw and claim, with the same restrictions as the receipt token
+    pub position_nft: Option<PositionNftInfo>,
+    // optional staking contract the claimed tokens can be bonded to with WithdrawTokens
+    pub staking_contract: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Permission {
+    Withdraw,
+    Claim,
+    Transfer,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Cw20HookMsg {
+    // burns the receipts sent and pays their pro-rata tokens and refund to the sender
+    Redeem {},
+    // burns the receipts sent and returns the deposit they were minted for to the sender, only
+    // allowed during phase 1
+    Withdraw {},
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ReceiptTokenInfo {
+    // code id of the cw20-base contract
+    pub code_id: u64,
+    pub name: String,
+    pub symbol: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct PositionNftInfo {
+    // code id of the cw721-base contract
+    pub code_id: u64,
+    pub name: String,
+    pub symbol: String,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 pub enum ExecuteMsg {
-    Deposit {},
-    Withdraw { amount: Option<Uint128> },
-    WithdrawTokens {},
-    PostInitialize { launch_config: LaunchConfig },
+    Receive(Cw20ReceiveMsg),
+    Deposit {
+        max_price: Option<Decimal>,
+        referrer: Option<String>,
+        lock_duration: Option<u64>,
+        // address credited with the deposit, the sender by default
+        recipient: Option<String>,
+    },
+    // the optional depositor lets a delegate act on the position of the depositor
+    Withdraw {
+        amount: Option<Uint128>,
+        recipient: Option<String>,
+        depositor: Option<String>,
+    },
+    // the optional contract and msg send the tokens to an allowed claim target with the msg hook,
+    // stake bonds them to the depositor in the staking contract instead
+    WithdrawTokens {
+        recipient: Option<String>,
+        depositor: Option<String>,
+        contract: Option<String>,
+        msg: Option<Binary>,
+        #[serde(default)]
+        stake: bool,
+    },
+    TransferPosition {
+        recipient: String,
+        amount: Option<Uint128>,
+        depositor: Option<String>,
+    },
+    Authorize {
+        delegate: String,
+        permissions: Vec<Permission>,
+        // never expires by default
+        expires: Option<Expiration>,
+        // only addresses the delegate can transfer the position to, required by the transfer
+        // permission so the delegate can not take the position for itself
+        #[serde(default)]
+        transfer_recipients: Vec<String>,
+    },
+    Revoke {
+        delegate: String,
+    },
+    // share of the claimed tokens paid to whoever claims on behalf of the sender
+    SetClaimTip {
+        tip: Decimal,
+    },
+    ClaimFor {
+        address: String,
+    },
+    WithdrawPosition {
+        token_id: String,
+        amount: Option<Uint128>,
+        recipient: Option<String>,
+    },
+    ClaimPosition {
+        token_id: String,
+        recipient: Option<String>,
+    },
+    ClaimReferralRewards {},
+    // pays the tokens, refund and referral rewards owed to the sender at once
+    WithdrawAndClaim {},
+    // pays the refund of the sender's deposit after the unclaimed tokens were swept
+    ClaimRefund {},
+    CommitBid {
+        commitment: Binary,
+    },
+    RevealBid {
+        amount: Uint128,
+        salt: String,
+    },
+    // returns the escrow of a bid that was not revealed once phase 2 is over
+    ReclaimEscrow {},
+    PostInitialize {
+        launch_config: LaunchConfig,
+    },
     AdminWithdraw {},
     ReleaseTokens {},
+    DistributeTokens {
+        limit: Option<u32>,
+    },
+    Settle {
+        limit: Option<u32>,
+    },
+    SweepUnclaimedTokens {},
+    SweepDust {},
+    RecoverStrayFunds {
+        recipient: String,
+    },
+    RecoverAsset {
+        asset_info: AssetInfoUnchecked,
+        recipient: String,
+    },
+    UpdateClaimTargets {
+        add: Vec<String>,
+        remove: Vec<String>,
+    },
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 pub enum QueryMsg {
     Config {},
-    DepositInfo { address: String },
+    DepositInfo {
+        address: String,
+    },
+    TokenAccounting {},
+    DutchAuction {},
+    Referral {
+        address: String,
+    },
+    Locks {
+        address: String,
+    },
+    Position {
+        token_id: String,
+    },
+    Delegates {
+        address: String,
+        start_after: Option<String>,
+        limit: Option<u32>,
+    },
+    ClaimTargets {
+        start_after: Option<String>,
+        limit: Option<u32>,
+    },
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
@@ -52,6 +294,11 @@ pub struct ConfigResponse {
     pub tokens_released: bool,
     pub host_portion: Decimal,
     pub host_portion_receiver: String,
+    pub unclaimed_tokens_receiver: String,
+    pub tokens_swept: bool,
+    pub receipt_token: Option<String>,
+    pub position_nft: Option<String>,
+    pub staking_contract: Option<String>,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
@@ -60,5 +307,92 @@ pub struct DepositResponse {
     pub total_deposit: Uint128,
     pub withdrawable_amount: Uint128,
     pub tokens_to_claim: Uint128,
+    pub refund_to_claim: Uint128,
+    // loyalty bonus tokens expected if the position does not withdraw during phase 2
+    pub bonus_to_claim: Uint128,
+    // referral rewards of the address not claimed yet
+    pub referral_rewards_to_claim: Uint128,
+    pub can_claim: bool,
+    // true once the tokens are released and nothing is owed to the address anymore
+    pub settled: bool,
+    // allocation weight of the position, only used on time weighted sales or with bonus tiers
+    pub weight: Uint128,
+    pub bonus_tiers: Vec<BonusTier>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct TokenAccountingResponse {
+    // total amount of tokens distributed by the launch
+    pub amount: Uint128,
+    // tokens allocated to depositors, claimed or not
+    pub allocated: Uint128,
+    pub claimed: Uint128,
+    // tokens not sold by the launch, returned to the operator on release
+    pub unsold: Uint128,
+    // rounding remainder that is not allocated to any depositor (amount - unsold - allocated)
+    pub dust: Uint128,
+    // tokens sent to the unclaimed tokens receiver, either unclaimed tokens or dust
+    pub swept: Uint128,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ReferralResponse {
+    // referrer of the address deposits
+    pub referrer: Option<String>,
+    // current deposits of the positions referred by the address
+    pub referred_deposit: Uint128,
+    pub total_referred_deposit: Uint128,
+    pub rewards_to_claim: Uint128,
+    pub rewards_claimed: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct LocksResponse {
+    pub locks: Vec<LockPositionResponse>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct LockPositionResponse {
+    pub amount: Uint128,
+    pub multiplier: Decimal,
+    pub unlock_time: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct DelegatesResponse {
+    pub delegates: Vec<DelegateResponse>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ClaimTargetsResponse {
+    pub targets: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct DelegateResponse {
+    pub delegate: String,
+    pub permissions: Vec<Permission>,
+    pub expires: Expiration,
+    pub transfer_recipients: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct PositionResponse {
+    pub owner: String,
+    pub deposit: Uint128,
+    pub deposit_time: u64,
+    pub withdrew_phase2: bool,
+    pub withdrawable_amount: Uint128,
+    pub tokens_to_claim: Uint128,
+    pub refund_to_claim: Uint128,
     pub can_claim: bool,
 }
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct DutchAuctionResponse {
+    pub current_price: Decimal,
+    // price of the last deposit, None until the first deposit
+    pub clearing_price: Option<Decimal>,
+    pub tokens_sold: Uint128,
+    pub remaining_supply: Uint128,
+}
@@ -0,0 +1,91 @@
+use cosmwasm_std::{
+    to_binary, Addr, CosmosMsg, QuerierWrapper, QueryRequest, StdResult, SubMsg, WasmMsg, WasmQuery,
+};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::msg::PositionNftInfo;
+
+// id of the reply to the position nft instantiation
+pub const POSITION_NFT_REPLY_ID: u64 = 2;
+
+// Instantiate message of the cw721-base contract used for the position nfts
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct PositionNftInstantiateMsg {
+    pub name: String,
+    pub symbol: String,
+    pub minter: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum PositionNftExecuteMsg {
+    Mint(MintMsg),
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct MintMsg {
+    pub token_id: String,
+    pub owner: String,
+    pub name: String,
+    pub description: Option<String>,
+    pub image: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum PositionNftQueryMsg {
+    OwnerOf {
+        token_id: String,
+        include_expired: Option<bool>,
+    },
+}
+
+// the approvals of the cw721 response are not needed
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct OwnerOfResponse {
+    pub owner: String,
+}
+
+// Instantiates the position nft with the forge as its minter, replying on success
+pub fn instantiate_position_nft(info: &PositionNftInfo, minter: &Addr) -> StdResult<SubMsg> {
+    let msg = WasmMsg::Instantiate {
+        admin: None,
+        code_id: info.code_id,
+        msg: to_binary(&PositionNftInstantiateMsg {
+            name: info.name.clone(),
+            symbol: info.symbol.clone(),
+            minter: minter.to_string(),
+        })?,
+        funds: vec![],
+        label: format!("{} position nft", info.symbol),
+    };
+
+    Ok(SubMsg::reply_on_success(msg, POSITION_NFT_REPLY_ID))
+}
+
+// Mints a position nft, the forge is the only minter
+pub fn mint_position_nft(position_nft: &Addr, mint_msg: MintMsg) -> StdResult<CosmosMsg> {
+    Ok(CosmosMsg::Wasm(WasmMsg::Execute {
+        contract_addr: position_nft.to_string(),
+        msg: to_binary(&PositionNftExecuteMsg::Mint(mint_msg))?,
+        funds: vec![],
+    }))
+}
+
+// Returns the current owner of the position nft
+pub fn query_position_owner(
+    querier: &QuerierWrapper,
+    position_nft: &Addr,
+    token_id: &str,
+) -> StdResult<Addr> {
+    let res: OwnerOfResponse = querier.query(&QueryRequest::Wasm(WasmQuery::Smart {
+        contract_addr: position_nft.to_string(),
+        msg: to_binary(&PositionNftQueryMsg::OwnerOf {
+            token_id: token_id.to_string(),
+            include_expired: None,
+        })?,
+    }))?;
+
+    Ok(Addr::unchecked(res.owner))
+}
@@ -0,0 +1,63 @@
+use cosmwasm_std::{to_binary, Addr, CosmosMsg, Event, StdResult, SubMsg, WasmMsg};
+use cw20::{Cw20Coin, Cw20ExecuteMsg, MinterResponse};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::msg::ReceiptTokenInfo;
+
+// id of the reply to the receipt token instantiation
+pub const RECEIPT_TOKEN_REPLY_ID: u64 = 1;
+
+// receipts are minted 1:1 with base denom, so they share its decimals
+const RECEIPT_TOKEN_DECIMALS: u8 = 6;
+
+// Instantiate message of the cw20-base contract used for the receipt token
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ReceiptTokenInstantiateMsg {
+    pub name: String,
+    pub symbol: String,
+    pub decimals: u8,
+    pub initial_balances: Vec<Cw20Coin>,
+    pub mint: Option<MinterResponse>,
+}
+
+// Instantiates the receipt token with the forge as its only minter, replying on success
+pub fn instantiate_receipt_token(info: &ReceiptTokenInfo, minter: &Addr) -> StdResult<SubMsg> {
+    let msg = WasmMsg::Instantiate {
+        admin: None,
+        code_id: info.code_id,
+        msg: to_binary(&ReceiptTokenInstantiateMsg {
+            name: info.name.clone(),
+            symbol: info.symbol.clone(),
+            decimals: RECEIPT_TOKEN_DECIMALS,
+            initial_balances: vec![],
+            mint: Some(MinterResponse {
+                minter: minter.to_string(),
+                cap: None,
+            }),
+        })?,
+        funds: vec![],
+        label: format!("{} receipt token", info.symbol),
+    };
+
+    Ok(SubMsg::reply_on_success(msg, RECEIPT_TOKEN_REPLY_ID))
+}
+
+// Finds the address of the instantiated contract in the events of the reply
+pub fn parse_instantiated_address(events: &[Event]) -> Option<String> {
+    events
+        .iter()
+        .filter(|event| event.ty == "instantiate_contract")
+        .flat_map(|event| event.attributes.iter())
+        .find(|attr| attr.key == "contract_address")
+        .map(|attr| attr.value.clone())
+}
+
+// Executes msg on the receipt token
+pub fn receipt_token_msg(receipt_token: &Addr, msg: Cw20ExecuteMsg) -> StdResult<CosmosMsg> {
+    Ok(CosmosMsg::Wasm(WasmMsg::Execute {
+        contract_addr: receipt_token.to_string(),
+        msg: to_binary(&msg)?,
+        funds: vec![],
+    }))
+}
@@ -0,0 +1,389 @@
+use cosmwasm_std::{Decimal, Fraction, StdResult, Storage, Uint128};
+
+use crate::msg::{LaunchConfig, SaleMode};
+use crate::state::{
+    DepositInfo, BATCH_AUCTION, BONDING_CURVE_SELLS, BONDING_CURVE_SUPPLY, DUTCH_AUCTION,
+    NON_LOYAL_DEPOSIT, TOTAL_DEPOSIT, TOTAL_REFERRED_DEPOSIT, TOTAL_WEIGHT,
+};
+
+// Result of the sale for the current deposits, it is final once phase 2 is over
+pub struct SaleResult {
+    pub total_deposit: Uint128,
+    // amount of tokens allocated to the depositors
+    pub tokens_sold: Uint128,
+    // amount of base denom kept as proceeds, the rest of the deposits is refunded
+    pub total_raised: Uint128,
+    pub allocation_rule: AllocationRule,
+    // false while a batch auction waits for its settlement
+    pub settled: bool,
+    pub rewards: Rewards,
+}
+
+// Token pools shared on top of the sale allocation
+pub struct Rewards {
+    // funded on top of the launch amount, shared by the positions that did not withdraw during
+    // phase 2 pro-rata to their deposits
+    pub loyalty_bonus: RewardPool,
+    // set aside from the launch amount, shared by the referrers pro-rata to the deposits they
+    // brought in
+    pub referral: RewardPool,
+}
+
+impl Rewards {
+    pub fn distributed(&self) -> Uint128 {
+        self.loyalty_bonus.distributed() + self.referral.distributed()
+    }
+
+    pub fn undistributed(&self) -> Uint128 {
+        self.loyalty_bonus.undistributed() + self.referral.undistributed()
+    }
+}
+
+// Tokens shared pro-rata to the shares of their owners
+pub struct RewardPool {
+    pub amount: Uint128,
+    pub total_shares: Uint128,
+}
+
+impl RewardPool {
+    pub fn share(&self, shares: Uint128) -> Uint128 {
+        if self.total_shares.is_zero() {
+            return Uint128::zero();
+        }
+
+        self.amount.multiply_ratio(shares, self.total_shares)
+    }
+
+    // Tokens owed to the owners that did not claim yet
+    pub fn unclaimed(&self, claimed_shares: Uint128) -> Uint128 {
+        // same rounding argument as pro-rata, over the shares
+        self.share(self.total_shares - claimed_shares)
+    }
+
+    pub fn distributed(&self) -> Uint128 {
+        self.amount - self.undistributed()
+    }
+
+    // the pool goes back with the unsold tokens when nobody shares it
+    fn undistributed(&self) -> Uint128 {
+        if self.total_shares.is_zero() {
+            self.amount
+        } else {
+            Uint128::zero()
+        }
+    }
+}
+
+// How the tokens sold are split between the positions
+pub enum AllocationRule {
+    // tokens are split pro-rata to the deposits
+    ProRata,
+    // each position bought an exact amount of tokens and pays them at the uniform clearing price
+    ClearingPrice(Decimal),
+    // each position already paid its tokens when buying them
+    Purchased,
+    // tokens are split pro-rata to the weight of the positions, out of the total weight
+    Weighted(Uint128),
+}
+
+// Tokens, loyalty bonus tokens and base denom refund owed to a deposit
+#[derive(Default)]
+pub struct Allocation {
+    pub tokens: Uint128,
+    pub bonus: Uint128,
+    pub refund: Uint128,
+}
+
+impl Allocation {
+    pub fn is_empty(&self) -> bool {
+        self.tokens.is_zero() && self.bonus.is_zero() && self.refund.is_zero()
+    }
+
+    // Sale tokens paid out to the position, including the loyalty bonus
+    pub fn total_tokens(&self) -> Uint128 {
+        self.tokens + self.bonus
+    }
+}
+
+impl SaleResult {
+    pub fn load(storage: &dyn Storage, launch_config: &LaunchConfig) -> StdResult<Self> {
+        let total_deposit = TOTAL_DEPOSIT.load(storage)?;
+        let referral_pool = referral_pool(launch_config);
+        // the referral rewards are set aside from the tokens split between the depositors
+        let sale_amount = launch_config.amount - referral_pool;
+        let rewards = Rewards {
+            loyalty_bonus: RewardPool {
+                amount: launch_config.loyalty_bonus.unwrap_or_default(),
+                total_shares: total_deposit - NON_LOYAL_DEPOSIT.load(storage)?,
+            },
+            referral: RewardPool {
+                amount: referral_pool,
+                total_shares: TOTAL_REFERRED_DEPOSIT.load(storage)?,
+            },
+        };
+
+        let sale_result = match &launch_config.sale_mode {
+            SaleMode::ProRata {} if is_weighted_pro_rata(launch_config) => SaleResult {
+                total_deposit,
+                tokens_sold: sale_amount,
+                total_raised: total_deposit,
+                allocation_rule: AllocationRule::Weighted(TOTAL_WEIGHT.load(storage)?),
+                settled: true,
+                rewards,
+            },
+            SaleMode::ProRata {} | SaleMode::SealedBid {} => SaleResult {
+                total_deposit,
+                tokens_sold: sale_amount,
+                total_raised: total_deposit,
+                allocation_rule: AllocationRule::ProRata,
+                settled: true,
+                rewards,
+            },
+            SaleMode::FixedPrice { price } => {
+                // tokens that all deposits can buy at the fixed price
+                let demand = tokens_at_price(total_deposit, *price);
+                if demand >= launch_config.amount {
+                    // oversubscribed, the whole amount is sold and the excess is refunded
+                    SaleResult {
+                        total_deposit,
+                        tokens_sold: launch_config.amount,
+                        total_raised: launch_config.amount * *price,
+                        allocation_rule: AllocationRule::ProRata,
+                        settled: true,
+                        rewards,
+                    }
+                } else {
+                    SaleResult {
+                        total_deposit,
+                        tokens_sold: demand,
+                        total_raised: total_deposit,
+                        allocation_rule: AllocationRule::ProRata,
+                        settled: true,
+                        rewards,
+                    }
+                }
+            }
+            SaleMode::DutchAuction { .. } => {
+                let auction = DUTCH_AUCTION.may_load(storage)?.unwrap_or_default();
+                SaleResult {
+                    total_deposit,
+                    tokens_sold: auction.tokens_sold,
+                    // rounded up as the cost of each position, so refunds never exceed deposits
+                    total_raised: mul_ceil(auction.tokens_sold, auction.clearing_price),
+                    allocation_rule: AllocationRule::ClearingPrice(auction.clearing_price),
+                    settled: true,
+                    rewards,
+                }
+            }
+            SaleMode::TimeWeighted {} => SaleResult {
+                total_deposit,
+                tokens_sold: sale_amount,
+                total_raised: total_deposit,
+                allocation_rule: AllocationRule::Weighted(TOTAL_WEIGHT.load(storage)?),
+                settled: true,
+                rewards,
+            },
+            SaleMode::BondingCurve { .. } => {
+                // deposits keep the cost of their tokens, the raised amount is what the curve
+                // holds after paying the sell backs
+                let tokens_sold = BONDING_CURVE_SUPPLY.may_load(storage)?.unwrap_or_default();
+                let sells = BONDING_CURVE_SELLS.may_load(storage)?.unwrap_or_default();
+                SaleResult {
+                    total_deposit,
+                    tokens_sold,
+                    total_raised: total_deposit + sells.cost - sells.proceeds,
+                    allocation_rule: AllocationRule::Purchased,
+                    settled: true,
+                    rewards,
+                }
+            }
+            SaleMode::BatchAuction {} => {
+                // each bid gets its tokens and cost on settlement, so the totals are exact
+                let auction = BATCH_AUCTION.may_load(storage)?.unwrap_or_default();
+                SaleResult {
+                    total_deposit,
+                    tokens_sold: auction.tokens_sold,
+                    total_raised: auction.total_raised,
+                    allocation_rule: AllocationRule::ClearingPrice(
+                        auction.clearing_price.unwrap_or_default(),
+                    ),
+                    settled: auction.settled,
+                    rewards,
+                }
+            }
+        };
+
+        Ok(sale_result)
+    }
+
+    pub fn unsold_tokens(&self, launch_config: &LaunchConfig) -> Uint128 {
+        launch_config.amount - self.rewards.referral.amount - self.tokens_sold
+            + self.rewards.undistributed()
+    }
+
+    pub fn total_refund(&self) -> Uint128 {
+        self.total_deposit - self.total_raised
+    }
+
+    pub fn allocation(&self, deposit_info: &DepositInfo) -> Allocation {
+        let mut allocation = match self.allocation_rule {
+            AllocationRule::ProRata => self.pro_rata_allocation(deposit_info.amount),
+            // the position pays its tokens at the clearing price, the overpayment is refunded
+            AllocationRule::ClearingPrice(clearing_price) => Allocation {
+                tokens: deposit_info.tokens_bought,
+                bonus: Uint128::zero(),
+                refund: deposit_info.amount - mul_ceil(deposit_info.tokens_bought, clearing_price),
+            },
+            AllocationRule::Purchased => Allocation {
+                tokens: deposit_info.tokens_bought,
+                bonus: Uint128::zero(),
+                refund: Uint128::zero(),
+            },
+            AllocationRule::Weighted(total_weight) => Allocation {
+                tokens: self.weighted_tokens(deposit_info.weight, total_weight),
+                bonus: Uint128::zero(),
+                refund: Uint128::zero(),
+            },
+        };
+
+        // sealed bids that were not revealed get their escrow back
+        allocation.refund += deposit_info.escrow;
+
+        if !deposit_info.withdrew_phase2 {
+            allocation.bonus = self.rewards.loyalty_bonus.share(deposit_info.amount);
+        }
+
+        allocation
+    }
+
+    // Tokens allocated to the positions that did not claim yet
+    pub fn unclaimed_tokens(
+        &self,
+        claimed_tokens: Uint128,
+        claimed_deposit: Uint128,
+        claimed_weight: Uint128,
+    ) -> Uint128 {
+        match self.allocation_rule {
+            // the sum of the allocations rounded down is never above the allocation of the sum,
+            // and once every depositor claimed the unclaimed allocation is exactly zero
+            AllocationRule::ProRata => {
+                self.pro_rata_allocation(self.total_deposit - claimed_deposit)
+                    .tokens
+            }
+            AllocationRule::ClearingPrice(_) | AllocationRule::Purchased => {
+                self.tokens_sold - claimed_tokens
+            }
+            // same rounding argument as pro-rata, over the weights
+            AllocationRule::Weighted(total_weight) => {
+                self.weighted_tokens(total_weight - claimed_weight, total_weight)
+            }
+        }
+    }
+
+    fn weighted_tokens(&self, weight: Uint128, total_weight: Uint128) -> Uint128 {
+        if total_weight.is_zero() {
+            return Uint128::zero();
+        }
+
+        self.tokens_sold.multiply_ratio(weight, total_weight)
+    }
+
+    fn pro_rata_allocation(&self, deposit: Uint128) -> Allocation {
+        if self.total_deposit.is_zero() {
+            return Allocation {
+                tokens: Uint128::zero(),
+                bonus: Uint128::zero(),
+                refund: Uint128::zero(),
+            };
+        }
+
+        // both round down, so the sum of all allocations never exceeds the totals
+        Allocation {
+            tokens: self.tokens_sold.multiply_ratio(deposit, self.total_deposit),
+            bonus: Uint128::zero(),
+            refund: self
+                .total_refund()
+                .multiply_ratio(deposit, self.total_deposit),
+        }
+    }
+}
+
+// Sale tokens funded by the operator, the launch amount plus the optional loyalty bonus
+pub fn funded_amount(launch_config: &LaunchConfig) -> Uint128 {
+    launch_config.amount + launch_config.loyalty_bonus.unwrap_or_default()
+}
+
+// Tokens set aside from the launch amount for the referrers
+pub fn referral_pool(launch_config: &LaunchConfig) -> Uint128 {
+    launch_config
+        .referral_portion
+        .map_or_else(Uint128::zero, |portion| launch_config.amount * portion)
+}
+
+// Price of the dutch auction at the given time, it falls linearly from the start price at
+// phase 1 start to the floor price at phase 2 start
+pub fn dutch_auction_price(
+    launch_config: &LaunchConfig,
+    start_price: Decimal,
+    floor_price: Decimal,
+    time: u64,
+) -> Decimal {
+    if time <= launch_config.phase1_start {
+        return start_price;
+    }
+    if time >= launch_config.phase2_start {
+        return floor_price;
+    }
+
+    let price_drop = Uint128::from((start_price - floor_price).numerator()).multiply_ratio(
+        time - launch_config.phase1_start,
+        launch_config.phase2_start - launch_config.phase1_start,
+    );
+
+    start_price - Decimal::from_ratio(price_drop, start_price.denominator())
+}
+
+// Whether the pro-rata sale splits the tokens by the weight boosted by bonus tiers and locks
+pub fn is_weighted_pro_rata(launch_config: &LaunchConfig) -> bool {
+    launch_config.sale_mode == (SaleMode::ProRata {})
+        && !(launch_config.bonus_tiers.is_empty() && launch_config.lock_options.is_empty())
+}
+
+// Bonus multiplier of a deposit made at the given time, 1 once all the tiers are over
+pub fn bonus_multiplier(launch_config: &LaunchConfig, time: u64) -> Decimal {
+    launch_config
+        .bonus_tiers
+        .iter()
+        .find(|tier| time < tier.until_timestamp)
+        .map_or_else(Decimal::one, |tier| tier.multiplier)
+}
+
+// Tokens the amount buys at the price, rounded down
+pub fn tokens_at_price(amount: Uint128, price: Decimal) -> Uint128 {
+    amount.multiply_ratio(price.denominator(), price.numerator())
+}
+
+// Returns the price at which amount buys the supply, rounding up so it never buys more
+pub fn price_ceil(amount: Uint128, supply: Uint128) -> Decimal {
+    let floor = Decimal::from_ratio(amount, supply);
+    if supply * floor < amount {
+        floor + Decimal::from_ratio(1u128, Decimal::one().denominator())
+    } else {
+        floor
+    }
+}
+
+// Multiplies the amount by the price rounding up
+pub fn mul_ceil(amount: Uint128, price: Decimal) -> Uint128 {
+    let floor = amount * price;
+
+    // both remainders are below the denominator, so their product fits in u128
+    let remainder = (amount.u128() % price.denominator())
+        * (price.numerator() % price.denominator())
+        % price.denominator();
+    if remainder == 0 {
+        floor
+    } else {
+        floor + Uint128::from(1u8)
+    }
+}
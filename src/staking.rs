@@ -0,0 +1,18 @@
+use cosmwasm_std::{to_binary, Addr, Binary, StdResult};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+// Hook of the cw20 Send accepted by the staking contract
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum StakingHookMsg {
+    // bonds the tokens sent on behalf of the staker
+    Bond { staker: String },
+}
+
+// Returns the hook bonding the claimed tokens to the staker
+pub fn bond_hook(staker: &Addr) -> StdResult<Binary> {
+    to_binary(&StakingHookMsg::Bond {
+        staker: staker.to_string(),
+    })
+}
@@ -1,14 +1,84 @@
-use crate::msg::{ConfigResponse, LaunchConfig};
-use cosmwasm_std::{Addr, Decimal, StdResult, Uint128};
-use cw_storage_plus::{Item, Map};
+use crate::msg::{ConfigResponse, LaunchConfig, Permission};
+use cosmwasm_std::{Addr, Binary, Decimal, Empty, StdResult, Uint128};
+use cw20::Expiration;
+use cw_storage_plus::{Item, Map, U128Key};
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
 pub const CONFIG: Item<Config> = Item::new("config");
 
+// total amount of tokens claimed by depositors
 pub const TOTAL_TOKEN: Item<Uint128> = Item::new("total_token");
+// total deposit amount of the positions that claimed their tokens
+pub const TOTAL_CLAIMED_DEPOSIT: Item<Uint128> = Item::new("total_claimed_deposit");
+// total amount of tokens swept by the operator, either unclaimed tokens or rounding dust
+pub const TOTAL_SWEPT: Item<Uint128> = Item::new("total_swept");
 pub const TOTAL_DEPOSIT: Item<Uint128> = Item::new("total_deposit");
+// total allocation weight of the deposits, only used on time weighted sales
+pub const TOTAL_WEIGHT: Item<Uint128> = Item::new("total_weight");
+// total weight of the positions that claimed their tokens
+pub const TOTAL_CLAIMED_WEIGHT: Item<Uint128> = Item::new("total_claimed_weight");
 pub const DEPOSITS: Map<&Addr, DepositInfo> = Map::new("deposits");
+// deposits left in the positions that withdrew during phase 2, they do not share the loyalty bonus
+pub const NON_LOYAL_DEPOSIT: Item<Uint128> = Item::new("non_loyal_deposit");
+// total deposit of the loyal positions that claimed their tokens
+pub const TOTAL_CLAIMED_LOYAL_DEPOSIT: Item<Uint128> = Item::new("total_claimed_loyal_deposit");
+// total amount of loyalty bonus tokens claimed, not included in TOTAL_TOKEN
+pub const TOTAL_CLAIMED_BONUS: Item<Uint128> = Item::new("total_claimed_bonus");
+// deposits brought in by each referrer
+pub const REFERRALS: Map<&Addr, ReferralInfo> = Map::new("referrals");
+// total deposit of the referred positions
+pub const TOTAL_REFERRED_DEPOSIT: Item<Uint128> = Item::new("total_referred_deposit");
+// total deposit brought in by the referrers that claimed their rewards
+pub const TOTAL_CLAIMED_REFERRED_DEPOSIT: Item<Uint128> =
+    Item::new("total_claimed_referred_deposit");
+// total amount of referral reward tokens claimed, not included in TOTAL_TOKEN
+pub const TOTAL_CLAIMED_REFERRAL_REWARDS: Item<Uint128> =
+    Item::new("total_claimed_referral_rewards");
+// total amount of base denom refunded to depositors at claim time
+pub const TOTAL_REFUNDED: Item<Uint128> = Item::new("total_refunded");
+// total amount of base denom escrowed by sealed bids that are not revealed yet
+pub const TOTAL_ESCROW: Item<Uint128> = Item::new("total_escrow");
+// total amount of base denom paid out to the receivers by admin_withdraw
+pub const ADMIN_WITHDRAWN: Item<Uint128> = Item::new("admin_withdrawn");
+
+// last depositor processed by the batch token distribution
+pub const DISTRIBUTION_CURSOR: Item<Addr> = Item::new("distribution_cursor");
+// depositors whose locked part was held back by the batch token distribution, revisited once the
+// cursor reaches the end of the deposits
+pub const DISTRIBUTION_SKIPPED: Map<&Addr, Empty> = Map::new("distribution_skipped");
+
+// progress of the dutch auction, only used on dutch auction sales
+pub const DUTCH_AUCTION: Item<DutchAuction> = Item::new("dutch_auction");
+
+// deposits of each position in the order they were made, so withdrawals remove weight LIFO
+pub const WEIGHTED_DEPOSITS: Map<&Addr, Vec<WeightedDeposit>> = Map::new("weighted_deposits");
+
+// lock positions of each depositor in the order they were made, only used on pro-rata sales
+pub const LOCKS: Map<&Addr, Vec<LockPosition>> = Map::new("locks");
+
+// delegations of each depositor, keyed by depositor and delegate
+pub const DELEGATES: Map<(&Addr, &Addr), Delegation> = Map::new("delegates");
+
+// contracts allowed by the operator to receive claimed tokens with a hook msg
+pub const CLAIM_TARGETS: Map<&Addr, Empty> = Map::new("claim_targets");
+
+// positions of the launches with position nfts, keyed by token id
+pub const POSITIONS: Map<&str, Position> = Map::new("positions");
+// number of position nfts minted, the next token id
+pub const POSITION_COUNT: Item<u64> = Item::new("position_count");
+
+// tokens sold along the bonding curve, only used on bonding curve sales
+pub const BONDING_CURVE_SUPPLY: Item<Uint128> = Item::new("bonding_curve_supply");
+// cost and proceeds of the tokens sold back to the bonding curve
+pub const BONDING_CURVE_SELLS: Item<CurveSells> = Item::new("bonding_curve_sells");
+
+// settlement of the batch auction, only used on batch auction sales
+pub const BATCH_AUCTION: Item<BatchAuction> = Item::new("batch_auction");
+// total deposit of the batch auction bids at each max price
+pub const BID_LEVELS: Map<U128Key, Uint128> = Map::new("bid_levels");
+// batch auction bids sorted by max price
+pub const BIDS: Map<(U128Key, &Addr), Empty> = Map::new("bids");
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub struct Config {
@@ -20,6 +90,13 @@ pub struct Config {
     pub tokens_released: bool,
     pub host_portion: Decimal,
     pub host_portion_receiver: Addr,
+    pub unclaimed_tokens_receiver: Addr,
+    pub tokens_swept: bool,
+    // set by the reply to the receipt token instantiation
+    pub receipt_token: Option<Addr>,
+    // set by the reply to the position nft instantiation
+    pub position_nft: Option<Addr>,
+    pub staking_contract: Option<Addr>,
 }
 
 impl Config {
@@ -33,6 +110,11 @@ impl Config {
             tokens_released: self.tokens_released,
             host_portion: self.host_portion,
             host_portion_receiver: self.host_portion_receiver.to_string(),
+            unclaimed_tokens_receiver: self.unclaimed_tokens_receiver.to_string(),
+            tokens_swept: self.tokens_swept,
+            receipt_token: self.receipt_token.as_ref().map(|addr| addr.to_string()),
+            position_nft: self.position_nft.as_ref().map(|addr| addr.to_string()),
+            staking_contract: self.staking_contract.as_ref().map(|addr| addr.to_string()),
         };
         Ok(res)
     }
@@ -43,4 +125,113 @@ pub struct DepositInfo {
     pub amount: Uint128,
     pub withdrew_phase2: bool,
     pub tokens_claimed: bool,
+    // tokens bought by the position, only used when each deposit buys at its own price
+    pub tokens_bought: Uint128,
+    // max price per token of the batch auction bid
+    pub max_price: Option<Decimal>,
+    // hash of the sealed bid, cleared once revealed
+    pub commitment: Option<Binary>,
+    // base denom escrowed by the sealed bid, reclaimed or refunded at claim time if not revealed
+    pub escrow: Uint128,
+    // allocation weight of the position, only used on time weighted sales
+    pub weight: Uint128,
+    // referrer set on the first deposit, it can not change afterwards
+    pub referrer: Option<Addr>,
+    // part of the amount and weight held by lock positions, they can not be withdrawn on phase 2
+    pub locked_amount: Uint128,
+    pub locked_weight: Uint128,
+    // the part outside the locks was claimed while they were running, the locked part is
+    // claimed once the last lock ends
+    pub unlocked_claimed: bool,
+    // share of the claimed tokens paid to the relayer of ClaimFor, zero by default
+    pub claim_tip: Decimal,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Position {
+    pub amount: Uint128,
+    pub deposit_time: u64,
+    pub withdrew_phase2: bool,
+    pub tokens_claimed: bool,
+}
+
+impl Position {
+    // Deposit with the same allocation as the position
+    pub fn deposit_info(&self) -> DepositInfo {
+        DepositInfo {
+            amount: self.amount,
+            withdrew_phase2: self.withdrew_phase2,
+            tokens_claimed: self.tokens_claimed,
+            ..Default::default()
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct LockPosition {
+    pub amount: Uint128,
+    pub multiplier: Decimal,
+    // allocation weight of the lock, with its multiplier
+    pub weight: Uint128,
+    // end of the lock, the lock duration after phase 2 end
+    pub unlock_time: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Delegation {
+    pub permissions: Vec<Permission>,
+    pub expires: Expiration,
+    // recipients allowed for the transfers made by the delegate
+    pub transfer_recipients: Vec<Addr>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema, Default)]
+pub struct ReferralInfo {
+    // current deposits of the positions referred
+    pub referred_deposit: Uint128,
+    pub rewards_claimed: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct WeightedDeposit {
+    pub amount: Uint128,
+    // seconds left in phase 1 when deposited, each unit of amount weighs this much
+    pub time_remaining: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema, Default)]
+pub struct DutchAuction {
+    // tokens bought by all deposits so far
+    pub tokens_sold: Uint128,
+    // price of the last deposit, all buyers settle at it
+    pub clearing_price: Decimal,
+}
+
+// deposits keep their cost, the difference with the proceeds paid is the gain or loss of the curve
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema, Default)]
+pub struct CurveSells {
+    // deposit removed from the positions that sold
+    pub cost: Uint128,
+    // base denom paid to the positions that sold
+    pub proceeds: Uint128,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema, Default)]
+pub struct BatchAuction {
+    // deposits of the price levels above the last processed one
+    pub cumulative_deposit: Uint128,
+    // last price level processed while looking for the clearing price
+    pub level_cursor: Option<Decimal>,
+    // price paid by all winning bids, set once found
+    pub clearing_price: Option<Decimal>,
+    // bids above the marginal price are fully filled, bids below it are fully refunded
+    pub marginal_price: Decimal,
+    // tokens split between the bids at the marginal price, pro-rata to their deposits
+    pub marginal_tokens: Uint128,
+    pub marginal_deposit: Uint128,
+    // last bid that got its tokens allocated
+    pub bid_cursor: Option<(Decimal, Addr)>,
+    pub tokens_sold: Uint128,
+    pub total_raised: Uint128,
+    pub settled: bool,
 }
@@ -0,0 +1,152 @@
+use cosmwasm_std::{Decimal, Uint128};
+
+use crate::msg::Curve;
+
+const AMOUNT: u128 = 1_000_000;
+
+fn linear() -> Curve {
+    // 0.01 to 0.02 uusd per token
+    Curve::Linear {
+        base_price: Decimal::percent(1),
+        slope: Decimal::percent(1),
+    }
+}
+
+fn quadratic() -> Curve {
+    // 0.01 to 0.04 uusd per token
+    Curve::Polynomial {
+        base_price: Decimal::percent(1),
+        slope: Decimal::percent(3),
+        exponent: 2,
+    }
+}
+
+#[test]
+fn spot_price() {
+    let amount = Uint128::from(AMOUNT);
+
+    assert_eq!(
+        linear().spot_price(amount, Uint128::zero()),
+        Decimal::percent(1)
+    );
+    assert_eq!(
+        linear().spot_price(amount, Uint128::from(500_000u128)),
+        Decimal::permille(15)
+    );
+    assert_eq!(linear().spot_price(amount, amount), Decimal::percent(2));
+
+    // 0.01 + 0.03 * 0.5^2
+    assert_eq!(
+        quadratic().spot_price(amount, Uint128::from(500_000u128)),
+        Decimal::from_ratio(175u128, 10_000u128)
+    );
+    assert_eq!(quadratic().spot_price(amount, amount), Decimal::percent(4));
+}
+
+#[test]
+fn exact_buy_cost() {
+    let amount = Uint128::from(AMOUNT);
+
+    // 1M * (0.01 + 0.01 / 2)
+    assert_eq!(
+        linear().buy_cost(amount, Uint128::zero(), amount),
+        Uint128::from(15_000u128)
+    );
+    // 1M * (0.01 * 0.5 + 0.01 * 0.5^2 / 2)
+    assert_eq!(
+        linear().buy_cost(amount, Uint128::zero(), Uint128::from(500_000u128)),
+        Uint128::from(6_250u128)
+    );
+    assert_eq!(
+        linear().buy_cost(
+            amount,
+            Uint128::from(500_000u128),
+            Uint128::from(500_000u128)
+        ),
+        Uint128::from(8_750u128)
+    );
+
+    // 1M * (0.01 + 0.03 / 3)
+    assert_eq!(
+        quadratic().buy_cost(amount, Uint128::zero(), amount),
+        Uint128::from(20_000u128)
+    );
+    // 1M * (0.01 * 0.5 + 0.03 * 0.5^3 / 3)
+    assert_eq!(
+        quadratic().buy_cost(amount, Uint128::zero(), Uint128::from(500_000u128)),
+        Uint128::from(6_250u128)
+    );
+}
+
+#[test]
+fn rounding_favors_the_curve() {
+    let amount = Uint128::from(AMOUNT);
+    let one = Uint128::from(1u128);
+
+    // a single token costs 0.01000001 uusd, buying rounds up and selling rounds down
+    assert_eq!(linear().buy_cost(amount, Uint128::zero(), one), one);
+    assert_eq!(linear().sell_proceeds(amount, one, one), Uint128::zero());
+
+    // selling right after buying never returns more than was paid
+    for tokens in [1u128, 7, 999, 123_457, 500_000, 1_000_000] {
+        let tokens = Uint128::from(tokens);
+        for curve in [linear(), quadratic()] {
+            let cost = curve.buy_cost(amount, Uint128::zero(), tokens);
+            let proceeds = curve.sell_proceeds(amount, tokens, tokens);
+            assert!(proceeds <= cost);
+            assert!(cost - proceeds <= one);
+        }
+    }
+}
+
+#[test]
+fn split_buys_cost_at_least_one_buy() {
+    let amount = Uint128::from(AMOUNT);
+
+    for curve in [linear(), quadratic()] {
+        let at_once = curve.buy_cost(amount, Uint128::zero(), Uint128::from(500_000u128));
+
+        let mut split = Uint128::zero();
+        let mut supply = Uint128::zero();
+        for tokens in [123_457u128, 76_543, 299_999, 1] {
+            split += curve.buy_cost(amount, supply, Uint128::from(tokens));
+            supply += Uint128::from(tokens);
+        }
+
+        // each buy rounds up at most one uusd
+        assert!(split >= at_once);
+        assert!(split - at_once <= Uint128::from(4u128));
+    }
+}
+
+#[test]
+fn tokens_for_funds() {
+    let amount = Uint128::from(AMOUNT);
+
+    assert_eq!(
+        linear().tokens_for_funds(amount, Uint128::zero(), Uint128::from(6_250u128)),
+        (Uint128::from(500_000u128), Uint128::from(6_250u128))
+    );
+
+    // the funds buy as many tokens as they can afford
+    for funds in [1u128, 10, 6_251, 9_999, 13_949] {
+        let funds = Uint128::from(funds);
+        for curve in [linear(), quadratic()] {
+            let supply = Uint128::from(100_000u128);
+            let (tokens, cost) = curve.tokens_for_funds(amount, supply, funds);
+            assert!(cost <= funds);
+            assert_eq!(cost, curve.buy_cost(amount, supply, tokens));
+            assert!(curve.buy_cost(amount, supply, tokens + Uint128::from(1u128)) > funds);
+        }
+    }
+
+    // never above the remaining supply
+    assert_eq!(
+        linear().tokens_for_funds(
+            amount,
+            Uint128::from(900_000u128),
+            Uint128::from(1_000_000u128)
+        ),
+        (Uint128::from(100_000u128), Uint128::from(1_950u128))
+    );
+}
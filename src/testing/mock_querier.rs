@@ -0,0 +1,129 @@
+use std::collections::HashMap;
+
+use cosmwasm_std::testing::{MockApi, MockQuerier, MockStorage, MOCK_CONTRACT_ADDR};
+use cosmwasm_std::{
+    from_binary, from_slice, to_binary, Coin, ContractResult, Empty, OwnedDeps, Querier,
+    QuerierResult, QueryRequest, SystemError, SystemResult, Uint128, WasmQuery,
+};
+use cw20::{BalanceResponse as Cw20BalanceResponse, Cw20QueryMsg};
+
+use crate::position_nft::{OwnerOfResponse, PositionNftQueryMsg};
+
+// mock_dependencies is a drop-in replacement for cosmwasm_std::testing::mock_dependencies
+// this uses our CustomQuerier.
+pub fn mock_dependencies(
+    contract_balance: &[Coin],
+) -> OwnedDeps<MockStorage, MockApi, WasmMockQuerier> {
+    let custom_querier: WasmMockQuerier =
+        WasmMockQuerier::new(MockQuerier::new(&[(MOCK_CONTRACT_ADDR, contract_balance)]));
+
+    OwnedDeps {
+        storage: MockStorage::default(),
+        api: MockApi::default(),
+        querier: custom_querier,
+    }
+}
+
+pub struct WasmMockQuerier {
+    base: MockQuerier<Empty>,
+    token_balances: HashMap<String, HashMap<String, Uint128>>,
+    nft_owners: HashMap<String, HashMap<String, String>>,
+}
+
+impl Querier for WasmMockQuerier {
+    fn raw_query(&self, bin_request: &[u8]) -> QuerierResult {
+        let request: QueryRequest<Empty> = match from_slice(bin_request) {
+            Ok(v) => v,
+            Err(e) => {
+                return SystemResult::Err(SystemError::InvalidRequest {
+                    error: format!("Parsing query request: {}", e),
+                    request: bin_request.into(),
+                })
+            }
+        };
+        self.handle_query(&request)
+    }
+}
+
+impl WasmMockQuerier {
+    pub fn new(base: MockQuerier<Empty>) -> Self {
+        WasmMockQuerier {
+            base,
+            token_balances: HashMap::new(),
+            nft_owners: HashMap::new(),
+        }
+    }
+
+    pub fn handle_query(&self, request: &QueryRequest<Empty>) -> QuerierResult {
+        match &request {
+            QueryRequest::Wasm(WasmQuery::Smart { contract_addr, msg }) => {
+                if let Ok(Cw20QueryMsg::Balance { address }) = from_binary(msg) {
+                    let balance = self
+                        .token_balances
+                        .get(contract_addr)
+                        .and_then(|balances| balances.get(&address))
+                        .cloned()
+                        .unwrap_or_default();
+                    return SystemResult::Ok(ContractResult::Ok(
+                        to_binary(&Cw20BalanceResponse { balance }).unwrap(),
+                    ));
+                }
+
+                if let Ok(PositionNftQueryMsg::OwnerOf { token_id, .. }) = from_binary(msg) {
+                    return match self
+                        .nft_owners
+                        .get(contract_addr)
+                        .and_then(|owners| owners.get(&token_id))
+                    {
+                        Some(owner) => SystemResult::Ok(ContractResult::Ok(
+                            to_binary(&OwnerOfResponse {
+                                owner: owner.clone(),
+                            })
+                            .unwrap(),
+                        )),
+                        None => SystemResult::Ok(ContractResult::Err(format!(
+                            "token {} not found",
+                            token_id
+                        ))),
+                    };
+                }
+
+                SystemResult::Err(SystemError::UnsupportedRequest {
+                    kind: format!("unknown query to {}", contract_addr),
+                })
+            }
+            _ => self.base.handle_query(request),
+        }
+    }
+
+    // update the native balance of an address
+    pub fn update_balance(&mut self, addr: impl Into<String>, balance: Vec<Coin>) {
+        self.base.update_balance(addr, balance);
+    }
+
+    // configure the cw20 token balances
+    pub fn with_token_balances(&mut self, balances: &[(&str, &[(&str, Uint128)])]) {
+        for (contract_addr, balances) in balances.iter() {
+            let contract_balances = self
+                .token_balances
+                .entry(contract_addr.to_string())
+                .or_default();
+            for (addr, balance) in balances.iter() {
+                contract_balances.insert(addr.to_string(), *balance);
+            }
+        }
+    }
+
+    // configure the owners of the nfts
+    pub fn with_nft_owners(&mut self, owners: &[(&str, &[(&str, &str)])]) {
+        for (contract_addr, owners) in owners.iter() {
+            let contract_owners = self
+                .nft_owners
+                .entry(contract_addr.to_string())
+                .or_default();
+            for (token_id, owner) in owners.iter() {
+                contract_owners.insert(token_id.to_string(), owner.to_string());
+            }
+        }
+    }
+}
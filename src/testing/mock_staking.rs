@@ -0,0 +1,66 @@
+use std::collections::HashMap;
+
+use cosmwasm_std::{from_binary, CosmosMsg, StdError, StdResult, Uint128, WasmMsg};
+use cw20::{Cw20ExecuteMsg, Cw20ReceiveMsg};
+
+use crate::staking::StakingHookMsg;
+
+// Staking contract bonding the tokens of the cw20 Send messages it receives
+pub struct MockStaking {
+    address: String,
+    token: String,
+    bonded: HashMap<String, Uint128>,
+}
+
+impl MockStaking {
+    pub fn new(address: &str, token: &str) -> Self {
+        MockStaking {
+            address: address.to_string(),
+            token: token.to_string(),
+            bonded: HashMap::new(),
+        }
+    }
+
+    // Executes the cw20 Send of msg sent by sender, as the token would call the staking contract
+    pub fn execute(&mut self, sender: &str, msg: &CosmosMsg) -> StdResult<()> {
+        let (contract_addr, msg) = match msg {
+            CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr, msg, ..
+            }) => (contract_addr, msg),
+            _ => return Err(StdError::generic_err("not a wasm execute message")),
+        };
+        match from_binary(msg)? {
+            Cw20ExecuteMsg::Send {
+                contract,
+                amount,
+                msg,
+            } if contract == self.address => self.receive(
+                contract_addr,
+                Cw20ReceiveMsg {
+                    sender: sender.to_string(),
+                    amount,
+                    msg,
+                },
+            ),
+            _ => Err(StdError::generic_err("not a send to the staking contract")),
+        }
+    }
+
+    fn receive(&mut self, token: &str, cw20_msg: Cw20ReceiveMsg) -> StdResult<()> {
+        if token != self.token {
+            return Err(StdError::generic_err("unauthorized"));
+        }
+
+        match from_binary(&cw20_msg.msg)? {
+            StakingHookMsg::Bond { staker } => {
+                *self.bonded.entry(staker).or_default() += cw20_msg.amount;
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn bonded(&self, staker: &str) -> Uint128 {
+        self.bonded.get(staker).copied().unwrap_or_default()
+    }
+}
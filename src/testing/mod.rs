@@ -1 +1,4 @@
+mod curve_tests;
+mod mock_querier;
+mod mock_staking;
 mod tests;
@@ -1,21 +1,32 @@
-use cosmwasm_std::testing::{
-    mock_dependencies, mock_env, mock_info, MockApi, MockQuerier, MOCK_CONTRACT_ADDR,
-};
+use cosmwasm_std::testing::{mock_env, mock_info, MockApi, MOCK_CONTRACT_ADDR};
 use cosmwasm_std::{
-    attr, from_binary, to_binary, Addr, BankMsg, Coin, CosmosMsg, Decimal, Deps, DepsMut, Env,
-    MemoryStorage, MessageInfo, OwnedDeps, Response, StdResult, SubMsg, Uint128, WasmMsg,
+    attr, from_binary, from_slice, to_binary, Addr, BankMsg, Binary, Coin, ContractResult,
+    CosmosMsg, Decimal, Deps, DepsMut, Env, Event, MemoryStorage, MessageInfo, OwnedDeps, Reply,
+    Response, StdResult, SubMsg, SubMsgExecutionResponse, Uint128, WasmMsg,
 };
-use cw20::Cw20ExecuteMsg;
+use cw20::{Cw20ExecuteMsg, Cw20ReceiveMsg, Expiration, MinterResponse};
+use cw_asset::AssetInfoUnchecked;
 
-use crate::contract::{deposit, execute, instantiate, query, release_tokens};
+use crate::contract::{bid_commitment, execute, instantiate, query, release_tokens, reply};
 use crate::error::ContractError;
 use crate::msg::{
-    ConfigResponse, DepositResponse, ExecuteMsg, InstantiateMsg, LaunchConfig, QueryMsg,
+    BonusTier, ClaimTargetsResponse, ConfigResponse, Curve, Cw20HookMsg, DelegateResponse,
+    DelegatesResponse, DepositResponse, DutchAuctionResponse, ExecuteMsg, InstantiateMsg,
+    LaunchConfig, LockOption, LockPositionResponse, LocksResponse, Permission, PositionNftInfo,
+    PositionResponse, QueryMsg, ReceiptTokenInfo, ReferralResponse, SaleMode,
+    TokenAccountingResponse,
+};
+use crate::position_nft::{
+    MintMsg, PositionNftExecuteMsg, PositionNftInstantiateMsg, POSITION_NFT_REPLY_ID,
 };
+use crate::receipt::{ReceiptTokenInstantiateMsg, RECEIPT_TOKEN_REPLY_ID};
+use crate::state::{DEPOSITS, TOTAL_WEIGHT};
+use crate::testing::mock_querier::{mock_dependencies, WasmMockQuerier};
+use crate::testing::mock_staking::MockStaking;
 
 const SECONDS_PER_HOUR: u64 = 60 * 60;
 
-pub fn init(deps: &mut OwnedDeps<MemoryStorage, MockApi, MockQuerier>) {
+pub fn init(deps: &mut OwnedDeps<MemoryStorage, MockApi, WasmMockQuerier>) {
     let msg = InstantiateMsg {
         operator: "owner0001".to_string(),
         receiver: "receiver0000".to_string(),
@@ -23,6 +34,10 @@ pub fn init(deps: &mut OwnedDeps<MemoryStorage, MockApi, MockQuerier>) {
         base_denom: "uusd".to_string(),
         host_portion: Decimal::zero(),
         host_portion_receiver: "host0000".to_string(),
+        unclaimed_tokens_receiver: "receiver0000".to_string(),
+        receipt_token: None,
+        position_nft: None,
+        staking_contract: None,
     };
 
     let info = mock_info("owner0001", &[]);
@@ -30,7 +45,14 @@ pub fn init(deps: &mut OwnedDeps<MemoryStorage, MockApi, MockQuerier>) {
     instantiate(deps.as_mut(), env, info, msg).unwrap();
 }
 
-pub fn post_init(deps: &mut OwnedDeps<MemoryStorage, MockApi, MockQuerier>) {
+pub fn post_init(deps: &mut OwnedDeps<MemoryStorage, MockApi, WasmMockQuerier>) {
+    post_init_with_sale_mode(deps, SaleMode::ProRata {});
+}
+
+pub fn post_init_with_sale_mode(
+    deps: &mut OwnedDeps<MemoryStorage, MockApi, WasmMockQuerier>,
+    sale_mode: SaleMode,
+) {
     let info = mock_info("owner0001", &[]);
     let env = mock_env();
     let launch_config = LaunchConfig {
@@ -39,12 +61,28 @@ pub fn post_init(deps: &mut OwnedDeps<MemoryStorage, MockApi, MockQuerier>) {
         phase2_start: env.block.time.seconds() + 100,
         phase2_end: env.block.time.seconds() + 100 + SECONDS_PER_HOUR,
         phase2_slot_period: SECONDS_PER_HOUR,
+        claim_deadline: None,
+        sale_mode,
+        bonus_tiers: vec![],
+        lock_options: vec![],
+        loyalty_bonus: None,
+        referral_portion: None,
     };
     do_post_initialize(deps.as_mut(), env, info, launch_config).unwrap();
 }
 
 pub fn do_deposit(deps: DepsMut, env: Env, info: MessageInfo) -> Result<Response, ContractError> {
-    execute(deps, env, info, ExecuteMsg::Deposit {})
+    execute(
+        deps,
+        env,
+        info,
+        ExecuteMsg::Deposit {
+            max_price: None,
+            referrer: None,
+            lock_duration: None,
+            recipient: None,
+        },
+    )
 }
 
 pub fn do_post_initialize(
@@ -67,7 +105,16 @@ pub fn do_withdraw(
     info: MessageInfo,
     amount: Option<Uint128>,
 ) -> Result<Response, ContractError> {
-    execute(deps, env, info, ExecuteMsg::Withdraw { amount })
+    execute(
+        deps,
+        env,
+        info,
+        ExecuteMsg::Withdraw {
+            amount,
+            recipient: None,
+            depositor: None,
+        },
+    )
 }
 
 pub fn do_admin_withdraw(
@@ -83,7 +130,18 @@ pub fn do_withdraw_tokens(
     env: Env,
     info: MessageInfo,
 ) -> Result<Response, ContractError> {
-    execute(deps, env, info, ExecuteMsg::WithdrawTokens {})
+    execute(
+        deps,
+        env,
+        info,
+        ExecuteMsg::WithdrawTokens {
+            recipient: None,
+            depositor: None,
+            contract: None,
+            msg: None,
+            stake: false,
+        },
+    )
 }
 
 pub fn do_release_tokens(
@@ -94,6 +152,15 @@ pub fn do_release_tokens(
     execute(deps, env, info, ExecuteMsg::ReleaseTokens {})
 }
 
+pub fn do_distribute_tokens(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    limit: Option<u32>,
+) -> Result<Response, ContractError> {
+    execute(deps, env, info, ExecuteMsg::DistributeTokens { limit })
+}
+
 pub fn do_query_deposit_info(deps: Deps, env: Env, address: String) -> StdResult<DepositResponse> {
     from_binary(&query(deps, env, QueryMsg::DepositInfo { address }).unwrap())
 }
@@ -108,6 +175,10 @@ fn proper_initialize() {
         base_denom: "uusd".to_string(),
         host_portion: Decimal::percent(110),
         host_portion_receiver: "host0000".to_string(),
+        unclaimed_tokens_receiver: "receiver0000".to_string(),
+        receipt_token: None,
+        position_nft: None,
+        staking_contract: None,
     };
 
     let info = mock_info("owner0001", &[]);
@@ -134,6 +205,11 @@ fn proper_post_initialize() {
             tokens_released: false,
             host_portion: Decimal::zero(),
             host_portion_receiver: "host0000".to_string(),
+            unclaimed_tokens_receiver: "receiver0000".to_string(),
+            tokens_swept: false,
+            receipt_token: None,
+            position_nft: None,
+            staking_contract: None,
         }
     );
 
@@ -145,6 +221,12 @@ fn proper_post_initialize() {
         phase2_start: env.block.time.seconds() + 100,
         phase2_end: env.block.time.seconds() + 100 + SECONDS_PER_HOUR,
         phase2_slot_period: SECONDS_PER_HOUR,
+        claim_deadline: None,
+        sale_mode: SaleMode::ProRata {},
+        bonus_tiers: vec![],
+        lock_options: vec![],
+        loyalty_bonus: None,
+        referral_portion: None,
     };
 
     // unauthorized
@@ -237,6 +319,11 @@ fn proper_post_initialize() {
             tokens_released: false,
             host_portion: Decimal::zero(),
             host_portion_receiver: "host0000".to_string(),
+            unclaimed_tokens_receiver: "receiver0000".to_string(),
+            tokens_swept: false,
+            receipt_token: None,
+            position_nft: None,
+            staking_contract: None,
         }
     );
 
@@ -249,6 +336,34 @@ fn proper_post_initialize() {
     assert_eq!(err.unwrap_err(), ContractError::DuplicatePostInit {});
 }
 
+#[test]
+fn proper_post_initialize_without_sale_mode() {
+    let mut deps = mock_dependencies(&[]);
+    init(&mut deps);
+
+    // payloads from before sale modes were added default to pro-rata
+    let env = mock_env();
+    let msg: ExecuteMsg = from_slice(
+        format!(
+            r#"{{"post_initialize":{{"launch_config":{{"amount":"1000000","phase1_start":{},"phase2_start":{},"phase2_end":{},"phase2_slot_period":{},"claim_deadline":null}}}}}}"#,
+            env.block.time.seconds(),
+            env.block.time.seconds() + 100,
+            env.block.time.seconds() + 100 + SECONDS_PER_HOUR,
+            SECONDS_PER_HOUR,
+        )
+        .as_bytes(),
+    )
+    .unwrap();
+    execute(deps.as_mut(), env.clone(), mock_info("owner0001", &[]), msg).unwrap();
+
+    let config_response: ConfigResponse =
+        from_binary(&query(deps.as_ref(), env, QueryMsg::Config {}).unwrap()).unwrap();
+    assert_eq!(
+        config_response.launch_config.unwrap().sale_mode,
+        SaleMode::ProRata {}
+    );
+}
+
 #[test]
 fn proper_deposit() {
     let mut deps = mock_dependencies(&[]);
@@ -312,7 +427,13 @@ fn proper_deposit() {
             total_deposit: Uint128::from(1_000u128),
             withdrawable_amount: Uint128::from(1_000u128),
             tokens_to_claim: Uint128::from(1_000_000u64),
+            refund_to_claim: Uint128::zero(),
+            bonus_to_claim: Uint128::zero(),
+            referral_rewards_to_claim: Uint128::zero(),
             can_claim: false,
+            settled: false,
+            weight: Uint128::zero(),
+            bonus_tiers: vec![],
         }
     );
 
@@ -325,7 +446,13 @@ fn proper_deposit() {
             total_deposit: Uint128::from(1_000u128),
             withdrawable_amount: Uint128::zero(),
             tokens_to_claim: Uint128::zero(),
+            refund_to_claim: Uint128::zero(),
+            bonus_to_claim: Uint128::zero(),
+            referral_rewards_to_claim: Uint128::zero(),
             can_claim: false,
+            settled: false,
+            weight: Uint128::zero(),
+            bonus_tiers: vec![],
         }
     );
 
@@ -402,7 +529,13 @@ fn proper_withdraw() {
             total_deposit: Uint128::from(900u128),
             withdrawable_amount: Uint128::from(900u128),
             tokens_to_claim: Uint128::from(1_000_000u64),
+            refund_to_claim: Uint128::zero(),
+            bonus_to_claim: Uint128::zero(),
+            referral_rewards_to_claim: Uint128::zero(),
             can_claim: false,
+            settled: false,
+            weight: Uint128::zero(),
+            bonus_tiers: vec![],
         }
     );
 
@@ -433,7 +566,13 @@ fn proper_withdraw() {
             total_deposit: Uint128::zero(),
             withdrawable_amount: Uint128::zero(),
             tokens_to_claim: Uint128::zero(),
+            refund_to_claim: Uint128::zero(),
+            bonus_to_claim: Uint128::zero(),
+            referral_rewards_to_claim: Uint128::zero(),
             can_claim: false,
+            settled: false,
+            weight: Uint128::zero(),
+            bonus_tiers: vec![],
         }
     );
 
@@ -504,7 +643,13 @@ fn proper_withdraw() {
             total_deposit: Uint128::from(99_000_000u128),
             withdrawable_amount: Uint128::zero(),
             tokens_to_claim: Uint128::from(1_000_000u64),
+            refund_to_claim: Uint128::zero(),
+            bonus_to_claim: Uint128::zero(),
+            referral_rewards_to_claim: Uint128::zero(),
             can_claim: false, // phase 2 is over, but tokens not released, so cant claim yet
+            settled: false,
+            weight: Uint128::zero(),
+            bonus_tiers: vec![],
         }
     );
 }
@@ -522,6 +667,12 @@ fn proper_withdraw_phase3() {
         phase2_start: env.block.time.seconds() + 100,
         phase2_end: env.block.time.seconds() + 100 + 24 * SECONDS_PER_HOUR, // 24 hour phase 2
         phase2_slot_period: SECONDS_PER_HOUR,
+        claim_deadline: None,
+        sale_mode: SaleMode::ProRata {},
+        bonus_tiers: vec![],
+        lock_options: vec![],
+        loyalty_bonus: None,
+        referral_portion: None,
     };
     do_post_initialize(deps.as_mut(), mock_env(), info, launch_config).unwrap();
 
@@ -532,11 +683,11 @@ fn proper_withdraw_phase3() {
 
     // successful deposit with 3 accounts
     alice_info.funds = vec![Coin::new(100_000_000, "uusd")];
-    deposit(deps.as_mut(), env.clone(), alice_info.clone()).unwrap();
+    do_deposit(deps.as_mut(), env.clone(), alice_info.clone()).unwrap();
     bob_info.funds = vec![Coin::new(100_000_000, "uusd")];
-    deposit(deps.as_mut(), env.clone(), bob_info.clone()).unwrap();
+    do_deposit(deps.as_mut(), env.clone(), bob_info.clone()).unwrap();
     cindy_info.funds = vec![Coin::new(100_000_000, "uusd")];
-    deposit(deps.as_mut(), env.clone(), cindy_info).unwrap();
+    do_deposit(deps.as_mut(), env.clone(), cindy_info).unwrap();
 
     // fast forward to phase 2
     env.block.time = env.block.time.plus_seconds(101);
@@ -549,7 +700,13 @@ fn proper_withdraw_phase3() {
             total_deposit: Uint128::from(300_000_000u128),
             withdrawable_amount: Uint128::from(100_000_000u128),
             tokens_to_claim: Uint128::from(333333u128),
+            refund_to_claim: Uint128::zero(),
+            bonus_to_claim: Uint128::zero(),
+            referral_rewards_to_claim: Uint128::zero(),
             can_claim: false,
+            settled: false,
+            weight: Uint128::zero(),
+            bonus_tiers: vec![],
         }
     );
 
@@ -617,7 +774,13 @@ fn proper_withdraw_phase3() {
             total_deposit: Uint128::from(299_000_000u128),
             withdrawable_amount: Uint128::from(75_000_000u128), // 100M * 18/24 70833333
             tokens_to_claim: Uint128::from(334448u128),         // 100000000 / 299000000 * 1000000
+            refund_to_claim: Uint128::zero(),
+            bonus_to_claim: Uint128::zero(),
+            referral_rewards_to_claim: Uint128::zero(),
             can_claim: false,
+            settled: false,
+            weight: Uint128::zero(),
+            bonus_tiers: vec![],
         }
     );
     // valid withdraw all remaining
@@ -645,7 +808,13 @@ fn proper_withdraw_phase3() {
             total_deposit: Uint128::from(224000000u128),
             withdrawable_amount: Uint128::zero(), // can not withraw more, only one time
             tokens_to_claim: Uint128::from(111607u128), // 25000000 / 224000000 * 1000000
+            refund_to_claim: Uint128::zero(),
+            bonus_to_claim: Uint128::zero(),
+            referral_rewards_to_claim: Uint128::zero(),
             can_claim: false,
+            settled: false,
+            weight: Uint128::zero(),
+            bonus_tiers: vec![],
         }
     );
 
@@ -659,7 +828,13 @@ fn proper_withdraw_phase3() {
             total_deposit: Uint128::from(224000000u128),
             withdrawable_amount: Uint128::from(4166666u128), // 100000000 * 1 / 24
             tokens_to_claim: Uint128::from(446428u128),      // 100000000 / 224000000 * 1000000
+            refund_to_claim: Uint128::zero(),
+            bonus_to_claim: Uint128::zero(),
+            referral_rewards_to_claim: Uint128::zero(),
             can_claim: false,
+            settled: false,
+            weight: Uint128::zero(),
+            bonus_tiers: vec![],
         }
     );
 
@@ -673,7 +848,13 @@ fn proper_withdraw_phase3() {
             total_deposit: Uint128::from(224000000u128),
             withdrawable_amount: Uint128::zero(), // 100000000 * 0 / 24
             tokens_to_claim: Uint128::from(446428u128), // 100000000 / 224000000 * 1000000
-            can_claim: false,                     // tokens not released, cant claim tokens yet
+            refund_to_claim: Uint128::zero(),
+            bonus_to_claim: Uint128::zero(),
+            referral_rewards_to_claim: Uint128::zero(),
+            can_claim: false, // tokens not released, cant claim tokens yet
+            settled: false,
+            weight: Uint128::zero(),
+            bonus_tiers: vec![],
         }
     );
 }
@@ -985,6 +1166,17 @@ fn proper_admin_withdraw() {
         }))]
     );
 
+    // funds sent later can not be drained with a second withdraw
+    deps.querier
+        .update_balance(MOCK_CONTRACT_ADDR.to_string(), vec![Coin::new(500, "uusd")]);
+    let err = do_admin_withdraw(deps.as_mut(), env.clone(), owner_info.clone()).unwrap_err();
+    assert_eq!(
+        err,
+        ContractError::InvalidAdminWithdraw {
+            reason: "no funds available to withdraw".to_string()
+        }
+    );
+
     // check that users can not claim yet, even after withdraw admin
     let deposit_info = do_query_deposit_info(deps.as_ref(), env.clone(), "addr0001".to_string());
     assert_eq!(
@@ -994,7 +1186,13 @@ fn proper_admin_withdraw() {
             total_deposit: Uint128::from(6_000u128),
             withdrawable_amount: Uint128::zero(), // can not withdraw on phase 3
             tokens_to_claim: Uint128::from(166666u128), // 1000000 * 1000 / 6000
-            can_claim: false,                     // tokens not released, cant claim tokens yet
+            refund_to_claim: Uint128::zero(),
+            bonus_to_claim: Uint128::zero(),
+            referral_rewards_to_claim: Uint128::zero(),
+            can_claim: false, // tokens not released, cant claim tokens yet
+            settled: false,
+            weight: Uint128::zero(),
+            bonus_tiers: vec![],
         }
     );
 
@@ -1009,7 +1207,13 @@ fn proper_admin_withdraw() {
             total_deposit: Uint128::from(6_000u128),
             withdrawable_amount: Uint128::zero(), // can not withdraw on phase 3
             tokens_to_claim: Uint128::from(166666u128), // 1000000 * 1000 / 6000
-            can_claim: true,                      // now users can claim tokens
+            refund_to_claim: Uint128::zero(),
+            bonus_to_claim: Uint128::zero(),
+            referral_rewards_to_claim: Uint128::zero(),
+            can_claim: true, // now users can claim tokens
+            settled: false,
+            weight: Uint128::zero(),
+            bonus_tiers: vec![],
         }
     );
 }
@@ -1025,6 +1229,10 @@ fn proper_admin_withdraw_with_host() {
         base_denom: "uusd".to_string(),
         host_portion: Decimal::percent(10), // 10% host portion
         host_portion_receiver: "host0000".to_string(),
+        unclaimed_tokens_receiver: "receiver0000".to_string(),
+        receipt_token: None,
+        position_nft: None,
+        staking_contract: None,
     };
 
     let owner_info = mock_info("owner0001", &[]);
@@ -1089,3 +1297,4535 @@ fn proper_admin_withdraw_with_host() {
         ]
     );
 }
+
+#[test]
+fn proper_distribute_tokens() {
+    let mut deps = mock_dependencies(&[]);
+    init(&mut deps);
+    post_init(&mut deps);
+
+    let mut env = mock_env();
+
+    // successful deposits -- total 10,000 uusd
+    for (addr, amount) in [
+        ("addr0001", 1_000u128),
+        ("addr0002", 4_000),
+        ("addr0003", 5_000),
+    ] {
+        let info = mock_info(addr, &[Coin::new(amount, "uusd")]);
+        do_deposit(deps.as_mut(), env.clone(), info).unwrap();
+    }
+
+    // anyone can call, but not before tokens are released
+    let keeper_info = mock_info("keeper0000", &[]);
+    let err =
+        do_distribute_tokens(deps.as_mut(), env.clone(), keeper_info.clone(), None).unwrap_err();
+    assert_eq!(
+        err,
+        ContractError::InvalidDistributeTokens {
+            reason: "cannot distribute tokens yet".to_string()
+        }
+    );
+
+    // fast forward past phase 2 and release tokens
+    env.block.time = env.block.time.plus_seconds(100 + SECONDS_PER_HOUR);
+    let owner_info = mock_info("owner0001", &[]);
+    release_tokens(deps.as_mut(), env.clone(), owner_info).unwrap();
+
+    // addr0002 claims by itself before the distribution reaches it
+    do_withdraw_tokens(deps.as_mut(), env.clone(), mock_info("addr0002", &[])).unwrap();
+
+    // first batch only processes addr0001 and addr0002 (already claimed)
+    let res =
+        do_distribute_tokens(deps.as_mut(), env.clone(), keeper_info.clone(), Some(2)).unwrap();
+    assert_eq!(
+        res.messages,
+        vec![SubMsg::new(CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: "prism0001".to_string(),
+            msg: to_binary(&Cw20ExecuteMsg::Transfer {
+                recipient: "addr0001".to_string(),
+                amount: Uint128::from(100_000u128),
+            })
+            .unwrap(),
+            funds: vec![],
+        }))]
+    );
+    assert_eq!(
+        res.attributes,
+        vec![
+            attr("action", "distribute_tokens"),
+            attr("distributed_count", "1"),
+            attr("distributed_amount", "100000"),
+            attr("refunded_amount", "0"),
+        ]
+    );
+
+    // second batch continues from the stored cursor
+    let res =
+        do_distribute_tokens(deps.as_mut(), env.clone(), keeper_info.clone(), Some(2)).unwrap();
+    assert_eq!(
+        res.messages,
+        vec![SubMsg::new(CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: "prism0001".to_string(),
+            msg: to_binary(&Cw20ExecuteMsg::Transfer {
+                recipient: "addr0003".to_string(),
+                amount: Uint128::from(500_000u128),
+            })
+            .unwrap(),
+            funds: vec![],
+        }))]
+    );
+
+    // nothing left to process
+    let err = do_distribute_tokens(deps.as_mut(), env.clone(), keeper_info, None).unwrap_err();
+    assert_eq!(
+        err,
+        ContractError::InvalidDistributeTokens {
+            reason: "all deposits were already processed".to_string()
+        }
+    );
+
+    // distributed positions are flagged as claimed
+    let err =
+        do_withdraw_tokens(deps.as_mut(), env.clone(), mock_info("addr0003", &[])).unwrap_err();
+    assert_eq!(
+        err,
+        ContractError::InvalidWithdrawTokens {
+            reason: "tokens were already claimed".to_string()
+        }
+    );
+    let deposit_info = do_query_deposit_info(deps.as_ref(), env, "addr0001".to_string()).unwrap();
+    assert!(!deposit_info.can_claim);
+}
+
+#[test]
+fn proper_sweep_unclaimed_tokens() {
+    let mut deps = mock_dependencies(&[]);
+    init(&mut deps);
+
+    let owner_info = mock_info("owner0001", &[]);
+    let mut env = mock_env();
+    let phase2_end = env.block.time.seconds() + 100 + SECONDS_PER_HOUR;
+    let mut launch_config = LaunchConfig {
+        amount: Uint128::from(1_000_000u64),
+        phase1_start: env.block.time.seconds(),
+        phase2_start: env.block.time.seconds() + 100,
+        phase2_end,
+        phase2_slot_period: SECONDS_PER_HOUR,
+        claim_deadline: Some(phase2_end),
+        sale_mode: SaleMode::ProRata {},
+        bonus_tiers: vec![],
+        lock_options: vec![],
+        loyalty_bonus: None,
+        referral_portion: None,
+    };
+
+    // invalid launch config (claim deadline not after phase 2)
+    let err = do_post_initialize(
+        deps.as_mut(),
+        env.clone(),
+        owner_info.clone(),
+        launch_config.clone(),
+    )
+    .unwrap_err();
+    assert_eq!(err, ContractError::InvalidLaunchConfig {});
+
+    launch_config.claim_deadline = Some(phase2_end + 24 * SECONDS_PER_HOUR);
+    do_post_initialize(
+        deps.as_mut(),
+        env.clone(),
+        owner_info.clone(),
+        launch_config,
+    )
+    .unwrap();
+
+    // successful deposits -- total 6,000 uusd
+    let info1 = mock_info("addr0001", &[Coin::new(1_000, "uusd")]);
+    do_deposit(deps.as_mut(), env.clone(), info1.clone()).unwrap();
+    let info2 = mock_info("addr0002", &[Coin::new(5_000, "uusd")]);
+    do_deposit(deps.as_mut(), env.clone(), info2.clone()).unwrap();
+
+    // fast forward past phase 2, release tokens and claim for addr0001
+    env.block.time = env.block.time.plus_seconds(100 + SECONDS_PER_HOUR);
+    release_tokens(deps.as_mut(), env.clone(), owner_info.clone()).unwrap();
+    do_withdraw_tokens(deps.as_mut(), env.clone(), info1).unwrap();
+
+    // sweep before the claim deadline, expect error
+    let err = execute(
+        deps.as_mut(),
+        env.clone(),
+        owner_info.clone(),
+        ExecuteMsg::SweepUnclaimedTokens {},
+    )
+    .unwrap_err();
+    assert_eq!(
+        err,
+        ContractError::InvalidSweepUnclaimedTokens {
+            reason: "claim deadline is not over yet".to_string()
+        }
+    );
+
+    // fast forward past the claim deadline
+    env.block.time = env.block.time.plus_seconds(24 * SECONDS_PER_HOUR);
+
+    // unauthorized attempt
+    let err = execute(
+        deps.as_mut(),
+        env.clone(),
+        info2.clone(),
+        ExecuteMsg::SweepUnclaimedTokens {},
+    )
+    .unwrap_err();
+    assert_eq!(err, ContractError::Unauthorized {});
+
+    // valid attempt, sweeps everything except the 166666 claimed by addr0001
+    let res = execute(
+        deps.as_mut(),
+        env.clone(),
+        owner_info.clone(),
+        ExecuteMsg::SweepUnclaimedTokens {},
+    )
+    .unwrap();
+    assert_eq!(
+        res.messages,
+        vec![SubMsg::new(CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: "prism0001".to_string(),
+            msg: to_binary(&Cw20ExecuteMsg::Transfer {
+                recipient: "receiver0000".to_string(),
+                amount: Uint128::from(833_334u128),
+            })
+            .unwrap(),
+            funds: vec![],
+        }))]
+    );
+
+    // addr0002 can not claim anymore
+    let deposit_info = do_query_deposit_info(deps.as_ref(), env.clone(), "addr0002".to_string());
+    assert!(!deposit_info.unwrap().can_claim);
+    let err = do_withdraw_tokens(deps.as_mut(), env.clone(), info2).unwrap_err();
+    assert_eq!(
+        err,
+        ContractError::InvalidWithdrawTokens {
+            reason: "claim deadline is over, unclaimed tokens were swept".to_string()
+        }
+    );
+
+    // can only sweep once
+    let err = execute(
+        deps.as_mut(),
+        env,
+        owner_info,
+        ExecuteMsg::SweepUnclaimedTokens {},
+    )
+    .unwrap_err();
+    assert_eq!(
+        err,
+        ContractError::InvalidSweepUnclaimedTokens {
+            reason: "unclaimed tokens are already swept".to_string()
+        }
+    );
+}
+
+#[test]
+fn proper_sweep_dust() {
+    let mut deps = mock_dependencies(&[]);
+    init(&mut deps);
+    post_init(&mut deps);
+
+    let owner_info = mock_info("owner0001", &[]);
+    let mut env = mock_env();
+
+    // three equal deposits, 1M tokens can not be split exactly
+    for addr in ["addr0001", "addr0002", "addr0003"] {
+        let info = mock_info(addr, &[Coin::new(1_000, "uusd")]);
+        do_deposit(deps.as_mut(), env.clone(), info).unwrap();
+    }
+
+    // fast forward past phase 2 and release tokens
+    env.block.time = env.block.time.plus_seconds(100 + SECONDS_PER_HOUR);
+    release_tokens(deps.as_mut(), env.clone(), owner_info.clone()).unwrap();
+    do_withdraw_tokens(deps.as_mut(), env.clone(), mock_info("addr0001", &[])).unwrap();
+
+    let accounting: TokenAccountingResponse =
+        from_binary(&query(deps.as_ref(), env.clone(), QueryMsg::TokenAccounting {}).unwrap())
+            .unwrap();
+    assert_eq!(
+        accounting,
+        TokenAccountingResponse {
+            amount: Uint128::from(1_000_000u128),
+            allocated: Uint128::from(999_999u128), // 333333 + 666666
+            claimed: Uint128::from(333_333u128),
+            unsold: Uint128::zero(),
+            dust: Uint128::from(1u128),
+            swept: Uint128::zero(),
+        }
+    );
+
+    // not everyone claimed yet, expect error
+    let err = execute(
+        deps.as_mut(),
+        env.clone(),
+        owner_info.clone(),
+        ExecuteMsg::SweepDust {},
+    )
+    .unwrap_err();
+    assert_eq!(
+        err,
+        ContractError::InvalidSweepDust {
+            reason: "not all depositors claimed their tokens yet".to_string()
+        }
+    );
+
+    do_withdraw_tokens(deps.as_mut(), env.clone(), mock_info("addr0002", &[])).unwrap();
+    do_withdraw_tokens(deps.as_mut(), env.clone(), mock_info("addr0003", &[])).unwrap();
+
+    // unauthorized attempt
+    let err = execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("addr0001", &[]),
+        ExecuteMsg::SweepDust {},
+    )
+    .unwrap_err();
+    assert_eq!(err, ContractError::Unauthorized {});
+
+    // valid attempt, sweeps the remaining token
+    let res = execute(
+        deps.as_mut(),
+        env.clone(),
+        owner_info.clone(),
+        ExecuteMsg::SweepDust {},
+    )
+    .unwrap();
+    assert_eq!(
+        res.messages,
+        vec![SubMsg::new(CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: "prism0001".to_string(),
+            msg: to_binary(&Cw20ExecuteMsg::Transfer {
+                recipient: "receiver0000".to_string(),
+                amount: Uint128::from(1u128),
+            })
+            .unwrap(),
+            funds: vec![],
+        }))]
+    );
+
+    // claimed + swept reconciles with the launch amount
+    let accounting: TokenAccountingResponse =
+        from_binary(&query(deps.as_ref(), env.clone(), QueryMsg::TokenAccounting {}).unwrap())
+            .unwrap();
+    assert_eq!(
+        accounting,
+        TokenAccountingResponse {
+            amount: Uint128::from(1_000_000u128),
+            allocated: Uint128::from(999_999u128),
+            claimed: Uint128::from(999_999u128),
+            unsold: Uint128::zero(),
+            dust: Uint128::from(1u128),
+            swept: Uint128::from(1u128),
+        }
+    );
+
+    // dust can only be swept once
+    let err = execute(deps.as_mut(), env, owner_info, ExecuteMsg::SweepDust {}).unwrap_err();
+    assert_eq!(
+        err,
+        ContractError::InvalidSweepDust {
+            reason: "no dust available to sweep".to_string()
+        }
+    );
+}
+
+#[test]
+fn test_sweep_dust_with_empty_allocations() {
+    let mut deps = mock_dependencies(&[]);
+    init(&mut deps);
+
+    // a single token split between three equal deposits rounds down to nothing for each
+    let owner_info = mock_info("owner0001", &[]);
+    let mut env = mock_env();
+    let launch_config = LaunchConfig {
+        amount: Uint128::from(1u64),
+        phase1_start: env.block.time.seconds(),
+        phase2_start: env.block.time.seconds() + 100,
+        phase2_end: env.block.time.seconds() + 100 + SECONDS_PER_HOUR,
+        phase2_slot_period: SECONDS_PER_HOUR,
+        claim_deadline: None,
+        sale_mode: SaleMode::ProRata {},
+        bonus_tiers: vec![],
+        lock_options: vec![],
+        loyalty_bonus: None,
+        referral_portion: None,
+    };
+    do_post_initialize(
+        deps.as_mut(),
+        env.clone(),
+        owner_info.clone(),
+        launch_config,
+    )
+    .unwrap();
+    for addr in ["addr0001", "addr0002", "addr0003"] {
+        let info = mock_info(addr, &[Coin::new(1_000, "uusd")]);
+        do_deposit(deps.as_mut(), env.clone(), info).unwrap();
+    }
+
+    env.block.time = env.block.time.plus_seconds(100 + SECONDS_PER_HOUR);
+    release_tokens(deps.as_mut(), env.clone(), owner_info.clone()).unwrap();
+    let err =
+        do_withdraw_tokens(deps.as_mut(), env.clone(), mock_info("addr0001", &[])).unwrap_err();
+    assert_eq!(
+        err,
+        ContractError::InvalidWithdrawTokens {
+            reason: "no tokens available for withdraw".to_string()
+        }
+    );
+
+    // the positions can never claim, so the distribution settles them
+    let res = do_distribute_tokens(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("anyone0000", &[]),
+        None,
+    )
+    .unwrap();
+    assert_eq!(res.messages.len(), 0);
+
+    let accounting: TokenAccountingResponse =
+        from_binary(&query(deps.as_ref(), env.clone(), QueryMsg::TokenAccounting {}).unwrap())
+            .unwrap();
+    assert_eq!(
+        accounting,
+        TokenAccountingResponse {
+            amount: Uint128::from(1u128),
+            allocated: Uint128::zero(),
+            claimed: Uint128::zero(),
+            unsold: Uint128::zero(),
+            dust: Uint128::from(1u128),
+            swept: Uint128::zero(),
+        }
+    );
+
+    let res = execute(deps.as_mut(), env, owner_info, ExecuteMsg::SweepDust {}).unwrap();
+    assert_eq!(
+        res.messages,
+        vec![SubMsg::new(CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: "prism0001".to_string(),
+            msg: to_binary(&Cw20ExecuteMsg::Transfer {
+                recipient: "receiver0000".to_string(),
+                amount: Uint128::from(1u128),
+            })
+            .unwrap(),
+            funds: vec![],
+        }))]
+    );
+}
+
+#[test]
+fn proper_recover_stray_funds() {
+    let mut deps = mock_dependencies(&[]);
+    init(&mut deps);
+    post_init(&mut deps);
+
+    let owner_info = mock_info("owner0001", &[]);
+    let mut env = mock_env();
+
+    // successful deposits -- total 6,000 uusd
+    let info1 = mock_info("addr0001", &[Coin::new(1_000, "uusd")]);
+    do_deposit(deps.as_mut(), env.clone(), info1.clone()).unwrap();
+    let info2 = mock_info("addr0002", &[Coin::new(5_000, "uusd")]);
+    do_deposit(deps.as_mut(), env.clone(), info2).unwrap();
+
+    // no stray funds, balance matches the deposits
+    deps.querier.update_balance(
+        MOCK_CONTRACT_ADDR.to_string(),
+        vec![Coin::new(6_000, "uusd")],
+    );
+    let err = execute(
+        deps.as_mut(),
+        env.clone(),
+        owner_info.clone(),
+        ExecuteMsg::RecoverStrayFunds {
+            recipient: "addr0003".to_string(),
+        },
+    )
+    .unwrap_err();
+    assert_eq!(
+        err,
+        ContractError::InvalidRecoverStrayFunds {
+            reason: "no stray funds available to recover".to_string()
+        }
+    );
+
+    // 500 uusd sent with a plain bank send
+    deps.querier.update_balance(
+        MOCK_CONTRACT_ADDR.to_string(),
+        vec![Coin::new(6_500, "uusd")],
+    );
+
+    // unauthorized attempt
+    let err = execute(
+        deps.as_mut(),
+        env.clone(),
+        info1,
+        ExecuteMsg::RecoverStrayFunds {
+            recipient: "addr0003".to_string(),
+        },
+    )
+    .unwrap_err();
+    assert_eq!(err, ContractError::Unauthorized {});
+
+    // fast forward past phase 2, admin withdraw only pays out the deposits
+    env.block.time = env.block.time.plus_seconds(100 + SECONDS_PER_HOUR);
+    let res = do_admin_withdraw(deps.as_mut(), env.clone(), owner_info.clone()).unwrap();
+    assert_eq!(
+        res.messages,
+        vec![SubMsg::new(CosmosMsg::Bank(BankMsg::Send {
+            to_address: "receiver0000".to_string(),
+            amount: vec![Coin::new(6_000, "uusd")],
+        }))]
+    );
+    deps.querier
+        .update_balance(MOCK_CONTRACT_ADDR.to_string(), vec![Coin::new(500, "uusd")]);
+
+    // valid attempt, stray funds go to the given recipient
+    let res = execute(
+        deps.as_mut(),
+        env,
+        owner_info,
+        ExecuteMsg::RecoverStrayFunds {
+            recipient: "addr0003".to_string(),
+        },
+    )
+    .unwrap();
+    assert_eq!(
+        res.messages,
+        vec![SubMsg::new(CosmosMsg::Bank(BankMsg::Send {
+            to_address: "addr0003".to_string(),
+            amount: vec![Coin::new(500, "uusd")],
+        }))]
+    );
+    assert_eq!(
+        res.attributes,
+        vec![
+            attr("action", "recover_stray_funds"),
+            attr("recovered_amount", "500"),
+        ]
+    );
+}
+
+#[test]
+fn proper_recover_asset() {
+    let mut deps = mock_dependencies(&[]);
+    init(&mut deps);
+    post_init(&mut deps);
+
+    let owner_info = mock_info("owner0001", &[]);
+    let env = mock_env();
+
+    // deposit 6,000 uusd, the contract also holds stray coins and tokens
+    let info = mock_info("addr0001", &[Coin::new(6_000, "uusd")]);
+    do_deposit(deps.as_mut(), env.clone(), info.clone()).unwrap();
+    deps.querier.update_balance(
+        MOCK_CONTRACT_ADDR.to_string(),
+        vec![Coin::new(6_100, "uusd"), Coin::new(700, "ukrw")],
+    );
+    deps.querier.with_token_balances(&[
+        (
+            "prism0001",
+            &[(MOCK_CONTRACT_ADDR, Uint128::from(1_000_000u128))],
+        ),
+        ("other0000", &[(MOCK_CONTRACT_ADDR, Uint128::from(300u128))]),
+    ]);
+
+    // unauthorized attempt
+    let err = execute(
+        deps.as_mut(),
+        env.clone(),
+        info,
+        ExecuteMsg::RecoverAsset {
+            asset_info: AssetInfoUnchecked::Native("ukrw".to_string()),
+            recipient: "addr0001".to_string(),
+        },
+    )
+    .unwrap_err();
+    assert_eq!(err, ContractError::Unauthorized {});
+
+    // sale tokens are all reserved for depositors
+    let err = execute(
+        deps.as_mut(),
+        env.clone(),
+        owner_info.clone(),
+        ExecuteMsg::RecoverAsset {
+            asset_info: AssetInfoUnchecked::Cw20("prism0001".to_string()),
+            recipient: "addr0001".to_string(),
+        },
+    )
+    .unwrap_err();
+    assert_eq!(
+        err,
+        ContractError::InvalidRecoverAsset {
+            reason: "no cw20:prism0001 available to recover".to_string()
+        }
+    );
+
+    // only uusd above the deposits can be recovered
+    let res = execute(
+        deps.as_mut(),
+        env.clone(),
+        owner_info.clone(),
+        ExecuteMsg::RecoverAsset {
+            asset_info: AssetInfoUnchecked::Native("uusd".to_string()),
+            recipient: "addr0001".to_string(),
+        },
+    )
+    .unwrap();
+    assert_eq!(
+        res.messages,
+        vec![SubMsg::new(CosmosMsg::Bank(BankMsg::Send {
+            to_address: "addr0001".to_string(),
+            amount: vec![Coin::new(100, "uusd")],
+        }))]
+    );
+
+    // foreign coins and tokens can be recovered freely
+    let res = execute(
+        deps.as_mut(),
+        env.clone(),
+        owner_info.clone(),
+        ExecuteMsg::RecoverAsset {
+            asset_info: AssetInfoUnchecked::Native("ukrw".to_string()),
+            recipient: "addr0001".to_string(),
+        },
+    )
+    .unwrap();
+    assert_eq!(
+        res.messages,
+        vec![SubMsg::new(CosmosMsg::Bank(BankMsg::Send {
+            to_address: "addr0001".to_string(),
+            amount: vec![Coin::new(700, "ukrw")],
+        }))]
+    );
+
+    let res = execute(
+        deps.as_mut(),
+        env,
+        owner_info,
+        ExecuteMsg::RecoverAsset {
+            asset_info: AssetInfoUnchecked::Cw20("other0000".to_string()),
+            recipient: "addr0001".to_string(),
+        },
+    )
+    .unwrap();
+    assert_eq!(
+        res.messages,
+        vec![SubMsg::new(CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: "other0000".to_string(),
+            msg: to_binary(&Cw20ExecuteMsg::Transfer {
+                recipient: "addr0001".to_string(),
+                amount: Uint128::from(300u128),
+            })
+            .unwrap(),
+            funds: vec![],
+        }))]
+    );
+    assert_eq!(
+        res.attributes,
+        vec![
+            attr("action", "recover_asset"),
+            attr("recovered_asset", "cw20:other0000:300"),
+        ]
+    );
+}
+
+#[test]
+fn proper_fixed_price_oversubscribed() {
+    let mut deps = mock_dependencies(&[]);
+    init(&mut deps);
+
+    // zero price is not allowed
+    let info = mock_info("owner0001", &[]);
+    let env = mock_env();
+    let launch_config = LaunchConfig {
+        amount: Uint128::from(1_000_000u64),
+        phase1_start: env.block.time.seconds(),
+        phase2_start: env.block.time.seconds() + 100,
+        phase2_end: env.block.time.seconds() + 100 + SECONDS_PER_HOUR,
+        phase2_slot_period: SECONDS_PER_HOUR,
+        claim_deadline: None,
+        sale_mode: SaleMode::FixedPrice {
+            price: Decimal::zero(),
+        },
+        bonus_tiers: vec![],
+        lock_options: vec![],
+        loyalty_bonus: None,
+        referral_portion: None,
+    };
+    let err = do_post_initialize(deps.as_mut(), env, info, launch_config).unwrap_err();
+    assert_eq!(err, ContractError::InvalidLaunchConfig {});
+
+    // 1M tokens at 0.01 uusd, the cap is 10,000 uusd
+    post_init_with_sale_mode(
+        &mut deps,
+        SaleMode::FixedPrice {
+            price: Decimal::percent(1),
+        },
+    );
+
+    let mut env = mock_env();
+
+    // deposits of 20,000 uusd, twice the cap
+    let info1 = mock_info("addr0001", &[Coin::new(5_000, "uusd")]);
+    do_deposit(deps.as_mut(), env.clone(), info1.clone()).unwrap();
+    let info2 = mock_info("addr0002", &[Coin::new(15_000, "uusd")]);
+    do_deposit(deps.as_mut(), env.clone(), info2).unwrap();
+
+    // fast forward past phase 2, all tokens are sold so nothing goes back to the operator
+    env.block.time = env.block.time.plus_seconds(100 + SECONDS_PER_HOUR);
+    let owner_info = mock_info("owner0001", &[]);
+    let res = release_tokens(deps.as_mut(), env.clone(), owner_info.clone()).unwrap();
+    assert_eq!(res.messages.len(), 0);
+
+    let deposit_info = do_query_deposit_info(deps.as_ref(), env.clone(), "addr0001".to_string());
+    assert_eq!(
+        deposit_info.unwrap(),
+        DepositResponse {
+            deposit: Uint128::from(5_000u128),
+            total_deposit: Uint128::from(20_000u128),
+            withdrawable_amount: Uint128::zero(),
+            tokens_to_claim: Uint128::from(250_000u128), // 1M * 5000 / 20000
+            refund_to_claim: Uint128::from(2_500u128),   // 5000 - 250000 * 0.01
+            bonus_to_claim: Uint128::zero(),
+            referral_rewards_to_claim: Uint128::zero(),
+            can_claim: true,
+            settled: false,
+            weight: Uint128::zero(),
+            bonus_tiers: vec![],
+        }
+    );
+
+    // tokens and refund are paid in one call
+    let res = do_withdraw_tokens(deps.as_mut(), env.clone(), info1).unwrap();
+    assert_eq!(
+        res.messages,
+        vec![
+            SubMsg::new(CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr: "prism0001".to_string(),
+                msg: to_binary(&Cw20ExecuteMsg::Transfer {
+                    recipient: "addr0001".to_string(),
+                    amount: Uint128::from(250_000u128),
+                })
+                .unwrap(),
+                funds: vec![],
+            })),
+            SubMsg::new(CosmosMsg::Bank(BankMsg::Send {
+                to_address: "addr0001".to_string(),
+                amount: vec![Coin::new(2_500, "uusd")],
+            })),
+        ]
+    );
+
+    // admin only withdraws the proceeds of the cap
+    let res = do_admin_withdraw(deps.as_mut(), env, owner_info).unwrap();
+    assert_eq!(
+        res.messages,
+        vec![SubMsg::new(CosmosMsg::Bank(BankMsg::Send {
+            to_address: "receiver0000".to_string(),
+            amount: vec![Coin::new(10_000, "uusd")],
+        }))]
+    );
+}
+
+#[test]
+fn proper_fixed_price_undersubscribed() {
+    let mut deps = mock_dependencies(&[]);
+    init(&mut deps);
+
+    // 1M tokens at 0.01 uusd, the cap is 10,000 uusd
+    post_init_with_sale_mode(
+        &mut deps,
+        SaleMode::FixedPrice {
+            price: Decimal::percent(1),
+        },
+    );
+
+    let mut env = mock_env();
+
+    // deposits of 4,000 uusd buy 400,000 tokens
+    let info1 = mock_info("addr0001", &[Coin::new(1_000, "uusd")]);
+    do_deposit(deps.as_mut(), env.clone(), info1.clone()).unwrap();
+    let info2 = mock_info("addr0002", &[Coin::new(3_000, "uusd")]);
+    do_deposit(deps.as_mut(), env.clone(), info2).unwrap();
+
+    // fast forward past phase 2, unsold tokens go back to the operator
+    env.block.time = env.block.time.plus_seconds(100 + SECONDS_PER_HOUR);
+    let owner_info = mock_info("owner0001", &[]);
+    let res = release_tokens(deps.as_mut(), env.clone(), owner_info.clone()).unwrap();
+    assert_eq!(
+        res.messages,
+        vec![SubMsg::new(CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: "prism0001".to_string(),
+            msg: to_binary(&Cw20ExecuteMsg::Transfer {
+                recipient: "owner0001".to_string(),
+                amount: Uint128::from(600_000u128),
+            })
+            .unwrap(),
+            funds: vec![],
+        }))]
+    );
+
+    // depositors get tokens at the fixed price, without refund
+    let res = do_withdraw_tokens(deps.as_mut(), env.clone(), info1).unwrap();
+    assert_eq!(
+        res.messages,
+        vec![SubMsg::new(CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: "prism0001".to_string(),
+            msg: to_binary(&Cw20ExecuteMsg::Transfer {
+                recipient: "addr0001".to_string(),
+                amount: Uint128::from(100_000u128),
+            })
+            .unwrap(),
+            funds: vec![],
+        }))]
+    );
+
+    let accounting: TokenAccountingResponse =
+        from_binary(&query(deps.as_ref(), env.clone(), QueryMsg::TokenAccounting {}).unwrap())
+            .unwrap();
+    assert_eq!(
+        accounting,
+        TokenAccountingResponse {
+            amount: Uint128::from(1_000_000u128),
+            allocated: Uint128::from(400_000u128),
+            claimed: Uint128::from(100_000u128),
+            unsold: Uint128::from(600_000u128),
+            dust: Uint128::zero(),
+            swept: Uint128::zero(),
+        }
+    );
+
+    // admin withdraws all deposits
+    let res = do_admin_withdraw(deps.as_mut(), env, owner_info).unwrap();
+    assert_eq!(
+        res.messages,
+        vec![SubMsg::new(CosmosMsg::Bank(BankMsg::Send {
+            to_address: "receiver0000".to_string(),
+            amount: vec![Coin::new(4_000, "uusd")],
+        }))]
+    );
+}
+
+#[test]
+fn proper_dutch_auction() {
+    let mut deps = mock_dependencies(&[]);
+    init(&mut deps);
+
+    // the price must fall during phase 1
+    let info = mock_info("owner0001", &[]);
+    let env = mock_env();
+    let launch_config = LaunchConfig {
+        amount: Uint128::from(1_000_000u64),
+        phase1_start: env.block.time.seconds(),
+        phase2_start: env.block.time.seconds() + 100,
+        phase2_end: env.block.time.seconds() + 100 + SECONDS_PER_HOUR,
+        phase2_slot_period: SECONDS_PER_HOUR,
+        claim_deadline: None,
+        sale_mode: SaleMode::DutchAuction {
+            start_price: Decimal::percent(1),
+            floor_price: Decimal::percent(1),
+        },
+        bonus_tiers: vec![],
+        lock_options: vec![],
+        loyalty_bonus: None,
+        referral_portion: None,
+    };
+    let err = do_post_initialize(deps.as_mut(), env, info, launch_config).unwrap_err();
+    assert_eq!(err, ContractError::InvalidLaunchConfig {});
+
+    // 1M tokens, price falls from 0.02 to 0.01 uusd over phase 1
+    post_init_with_sale_mode(
+        &mut deps,
+        SaleMode::DutchAuction {
+            start_price: Decimal::percent(2),
+            floor_price: Decimal::percent(1),
+        },
+    );
+
+    let mut env = mock_env();
+
+    // 10,000 uusd buy 500,000 tokens at 0.02
+    let info1 = mock_info("addr0001", &[Coin::new(10_000, "uusd")]);
+    let res = do_deposit(deps.as_mut(), env.clone(), info1.clone()).unwrap();
+    assert_eq!(res.messages.len(), 0);
+
+    // deposits are bids that can not be withdrawn
+    let err = do_withdraw(deps.as_mut(), env.clone(), info1.clone(), None).unwrap_err();
+    assert_eq!(
+        err,
+        ContractError::InvalidWithdraw {
+            reason: "deposits can not be withdrawn on a dutch auction".to_string()
+        }
+    );
+
+    // half way through phase 1 the price is 0.015, 6,000 uusd buy 400,000 tokens
+    env.block.time = env.block.time.plus_seconds(50);
+    let info2 = mock_info("addr0002", &[Coin::new(6_000, "uusd")]);
+    do_deposit(deps.as_mut(), env.clone(), info2.clone()).unwrap();
+
+    let auction: DutchAuctionResponse =
+        from_binary(&query(deps.as_ref(), env.clone(), QueryMsg::DutchAuction {}).unwrap())
+            .unwrap();
+    assert_eq!(
+        auction,
+        DutchAuctionResponse {
+            current_price: Decimal::permille(15),
+            clearing_price: Some(Decimal::permille(15)),
+            tokens_sold: Uint128::from(900_000u128),
+            remaining_supply: Uint128::from(100_000u128),
+        }
+    );
+
+    // at 0.012 only 100,000 tokens are left, the deposit pays 1,200 and gets the rest back
+    env.block.time = env.block.time.plus_seconds(30);
+    let info3 = mock_info("addr0003", &[Coin::new(3_000, "uusd")]);
+    let res = do_deposit(deps.as_mut(), env.clone(), info3).unwrap();
+    assert_eq!(
+        res.messages,
+        vec![SubMsg::new(CosmosMsg::Bank(BankMsg::Send {
+            to_address: "addr0003".to_string(),
+            amount: vec![Coin::new(1_800, "uusd")],
+        }))]
+    );
+
+    let info4 = mock_info("addr0004", &[Coin::new(1_000, "uusd")]);
+    let err = do_deposit(deps.as_mut(), env.clone(), info4).unwrap_err();
+    assert_eq!(
+        err,
+        ContractError::InvalidDeposit {
+            reason: "tokens are sold out".to_string()
+        }
+    );
+
+    // fast forward past phase 2, everything is sold
+    env.block.time = env.block.time.plus_seconds(20 + SECONDS_PER_HOUR);
+    let owner_info = mock_info("owner0001", &[]);
+    let res = release_tokens(deps.as_mut(), env.clone(), owner_info.clone()).unwrap();
+    assert_eq!(res.messages.len(), 0);
+
+    // all buyers settle at the clearing price of 0.012
+    let deposit_info = do_query_deposit_info(deps.as_ref(), env.clone(), "addr0001".to_string());
+    assert_eq!(
+        deposit_info.unwrap(),
+        DepositResponse {
+            deposit: Uint128::from(10_000u128),
+            total_deposit: Uint128::from(17_200u128),
+            withdrawable_amount: Uint128::zero(),
+            tokens_to_claim: Uint128::from(500_000u128),
+            refund_to_claim: Uint128::from(4_000u128), // 10000 - 500000 * 0.012
+            bonus_to_claim: Uint128::zero(),
+            referral_rewards_to_claim: Uint128::zero(),
+            can_claim: true,
+            settled: false,
+            weight: Uint128::zero(),
+            bonus_tiers: vec![],
+        }
+    );
+
+    let res = do_withdraw_tokens(deps.as_mut(), env.clone(), info2).unwrap();
+    assert_eq!(
+        res.messages,
+        vec![
+            SubMsg::new(CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr: "prism0001".to_string(),
+                msg: to_binary(&Cw20ExecuteMsg::Transfer {
+                    recipient: "addr0002".to_string(),
+                    amount: Uint128::from(400_000u128),
+                })
+                .unwrap(),
+                funds: vec![],
+            })),
+            SubMsg::new(CosmosMsg::Bank(BankMsg::Send {
+                to_address: "addr0002".to_string(),
+                amount: vec![Coin::new(1_200, "uusd")],
+            })),
+        ]
+    );
+
+    // admin withdraws the whole supply at the clearing price
+    let res = do_admin_withdraw(deps.as_mut(), env, owner_info).unwrap();
+    assert_eq!(
+        res.messages,
+        vec![SubMsg::new(CosmosMsg::Bank(BankMsg::Send {
+            to_address: "receiver0000".to_string(),
+            amount: vec![Coin::new(12_000, "uusd")],
+        }))]
+    );
+}
+
+pub fn do_bid(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    max_price: Decimal,
+) -> Result<Response, ContractError> {
+    execute(
+        deps,
+        env,
+        info,
+        ExecuteMsg::Deposit {
+            max_price: Some(max_price),
+            referrer: None,
+            lock_duration: None,
+            recipient: None,
+        },
+    )
+}
+
+pub fn do_settle(deps: DepsMut, env: Env, limit: u32) -> Result<Response, ContractError> {
+    let info = mock_info("anyone0000", &[]);
+    execute(deps, env, info, ExecuteMsg::Settle { limit: Some(limit) })
+}
+
+#[test]
+fn proper_batch_auction() {
+    let mut deps = mock_dependencies(&[]);
+    init(&mut deps);
+
+    // 1M tokens sold at a uniform clearing price
+    post_init_with_sale_mode(&mut deps, SaleMode::BatchAuction {});
+
+    let mut env = mock_env();
+
+    // bids need a max price
+    let info1 = mock_info("addr0001", &[Coin::new(6_000, "uusd")]);
+    let err = do_deposit(deps.as_mut(), env.clone(), info1.clone()).unwrap_err();
+    assert_eq!(
+        err,
+        ContractError::InvalidDeposit {
+            reason: "batch auction bids require a max price".to_string()
+        }
+    );
+
+    do_bid(
+        deps.as_mut(),
+        env.clone(),
+        info1.clone(),
+        Decimal::percent(3),
+    )
+    .unwrap();
+    let info2 = mock_info("addr0002", &[Coin::new(4_000, "uusd")]);
+    do_bid(deps.as_mut(), env.clone(), info2, Decimal::percent(2)).unwrap();
+    let info3 = mock_info("addr0003", &[Coin::new(4_000, "uusd")]);
+    do_bid(
+        deps.as_mut(),
+        env.clone(),
+        info3.clone(),
+        Decimal::permille(12),
+    )
+    .unwrap();
+    let info4 = mock_info("addr0004", &[Coin::new(2_000, "uusd")]);
+    do_bid(
+        deps.as_mut(),
+        env.clone(),
+        info4.clone(),
+        Decimal::permille(5),
+    )
+    .unwrap();
+
+    // the price of an open bid is fixed
+    let err = do_bid(
+        deps.as_mut(),
+        env.clone(),
+        info4.clone(),
+        Decimal::percent(1),
+    )
+    .unwrap_err();
+    assert_eq!(
+        err,
+        ContractError::InvalidDeposit {
+            reason: "max price of an open bid can not be changed".to_string()
+        }
+    );
+
+    // withdrawals shrink the bid
+    do_withdraw(
+        deps.as_mut(),
+        env.clone(),
+        info4.clone(),
+        Some(Uint128::from(1_000u128)),
+    )
+    .unwrap();
+
+    // settlement starts after phase 2
+    let err = do_settle(deps.as_mut(), env.clone(), 2).unwrap_err();
+    assert_eq!(
+        err,
+        ContractError::InvalidSettle {
+            reason: "cannot settle the auction yet".to_string()
+        }
+    );
+
+    env.block.time = env.block.time.plus_seconds(100 + SECONDS_PER_HOUR);
+
+    // tokens can not be released before the settlement
+    let owner_info = mock_info("owner0001", &[]);
+    let err = release_tokens(deps.as_mut(), env.clone(), owner_info.clone()).unwrap_err();
+    assert_eq!(
+        err,
+        ContractError::InvalidReleaseTokens {
+            reason: "auction is not settled yet".to_string()
+        }
+    );
+
+    // 10,000 uusd bid above 0.012 only buy 833,333 tokens, so the demand at 0.012 reaches the
+    // supply and the bids at 0.012 share the remaining 166,667 tokens
+    let res = do_settle(deps.as_mut(), env.clone(), 2).unwrap();
+    assert_eq!(
+        res.attributes,
+        vec![
+            attr("action", "settle"),
+            attr("clearing_price", ""),
+            attr("settled", "false"),
+        ]
+    );
+    let res = do_settle(deps.as_mut(), env.clone(), 2).unwrap();
+    assert_eq!(
+        res.attributes,
+        vec![
+            attr("action", "settle"),
+            attr("clearing_price", "0.012"),
+            attr("settled", "false"),
+        ]
+    );
+    do_settle(deps.as_mut(), env.clone(), 2).unwrap();
+    let res = do_settle(deps.as_mut(), env.clone(), 2).unwrap();
+    assert_eq!(
+        res.attributes,
+        vec![
+            attr("action", "settle"),
+            attr("clearing_price", "0.012"),
+            attr("settled", "true"),
+        ]
+    );
+    let err = do_settle(deps.as_mut(), env.clone(), 2).unwrap_err();
+    assert_eq!(
+        err,
+        ContractError::InvalidSettle {
+            reason: "auction is already settled".to_string()
+        }
+    );
+
+    // the whole supply is sold
+    let res = release_tokens(deps.as_mut(), env.clone(), owner_info.clone()).unwrap();
+    assert_eq!(res.messages.len(), 0);
+
+    // winning bids pay the clearing price
+    let deposit_info = do_query_deposit_info(deps.as_ref(), env.clone(), "addr0001".to_string());
+    assert_eq!(
+        deposit_info.unwrap(),
+        DepositResponse {
+            deposit: Uint128::from(6_000u128),
+            total_deposit: Uint128::from(15_000u128),
+            withdrawable_amount: Uint128::zero(),
+            tokens_to_claim: Uint128::from(500_000u128), // 6000 / 0.012
+            refund_to_claim: Uint128::zero(),
+            bonus_to_claim: Uint128::zero(),
+            referral_rewards_to_claim: Uint128::zero(),
+            can_claim: true,
+            settled: false,
+            weight: Uint128::zero(),
+            bonus_tiers: vec![],
+        }
+    );
+
+    // the marginal bid is partially filled
+    let res = do_withdraw_tokens(deps.as_mut(), env.clone(), info3).unwrap();
+    assert_eq!(
+        res.messages,
+        vec![
+            SubMsg::new(CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr: "prism0001".to_string(),
+                msg: to_binary(&Cw20ExecuteMsg::Transfer {
+                    recipient: "addr0003".to_string(),
+                    amount: Uint128::from(166_667u128),
+                })
+                .unwrap(),
+                funds: vec![],
+            })),
+            SubMsg::new(CosmosMsg::Bank(BankMsg::Send {
+                to_address: "addr0003".to_string(),
+                amount: vec![Coin::new(1_999, "uusd")], // 4000 - ceil(166667 * 0.012)
+            })),
+        ]
+    );
+
+    // the bid below the clearing price is fully refunded
+    let res = do_withdraw_tokens(deps.as_mut(), env.clone(), info4).unwrap();
+    assert_eq!(
+        res.messages,
+        vec![SubMsg::new(CosmosMsg::Bank(BankMsg::Send {
+            to_address: "addr0004".to_string(),
+            amount: vec![Coin::new(1_000, "uusd")],
+        }))]
+    );
+
+    // 6000 + 4000 (333,333 tokens) + 2001 (166,667 tokens)
+    let res = do_admin_withdraw(deps.as_mut(), env, owner_info).unwrap();
+    assert_eq!(
+        res.messages,
+        vec![SubMsg::new(CosmosMsg::Bank(BankMsg::Send {
+            to_address: "receiver0000".to_string(),
+            amount: vec![Coin::new(12_001, "uusd")],
+        }))]
+    );
+}
+
+#[test]
+fn test_batch_auction_uneven_supply() {
+    let mut deps = mock_dependencies(&[]);
+    init(&mut deps);
+
+    // the supply can not be divided evenly by the deposits above the lowest level
+    let info = mock_info("owner0001", &[]);
+    let mut env = mock_env();
+    let launch_config = LaunchConfig {
+        amount: Uint128::from(3_000_000_000_000u128),
+        phase1_start: env.block.time.seconds(),
+        phase2_start: env.block.time.seconds() + 100,
+        phase2_end: env.block.time.seconds() + 100 + SECONDS_PER_HOUR,
+        phase2_slot_period: SECONDS_PER_HOUR,
+        claim_deadline: None,
+        sale_mode: SaleMode::BatchAuction {},
+        bonus_tiers: vec![],
+        lock_options: vec![],
+        loyalty_bonus: None,
+        referral_portion: None,
+    };
+    do_post_initialize(deps.as_mut(), env.clone(), info, launch_config).unwrap();
+
+    let info1 = mock_info("addr0001", &[Coin::new(1_000_000, "uusd")]);
+    do_bid(deps.as_mut(), env.clone(), info1, Decimal::one()).unwrap();
+    let info2 = mock_info("addr0002", &[Coin::new(1_000, "uusd")]);
+    do_bid(
+        deps.as_mut(),
+        env.clone(),
+        info2,
+        Decimal::from_ratio(1u128, 10_000_000u128),
+    )
+    .unwrap();
+
+    env.block.time = env.block.time.plus_seconds(100 + SECONDS_PER_HOUR);
+    let res = do_settle(deps.as_mut(), env.clone(), 30).unwrap();
+    // the clearing price is rounded up so the winning bid never buys more than the supply
+    assert_eq!(
+        res.attributes[1],
+        attr("clearing_price", "0.000000333333333334")
+    );
+
+    let deposit_info =
+        do_query_deposit_info(deps.as_ref(), env.clone(), "addr0001".to_string()).unwrap();
+    assert_eq!(
+        deposit_info.tokens_to_claim,
+        Uint128::from(2_999_999_999_994u128)
+    );
+
+    let owner_info = mock_info("owner0001", &[]);
+    let res = release_tokens(deps.as_mut(), env, owner_info).unwrap();
+    assert_eq!(
+        res.messages,
+        vec![SubMsg::new(CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: "prism0001".to_string(),
+            msg: to_binary(&Cw20ExecuteMsg::Transfer {
+                recipient: "owner0001".to_string(),
+                amount: Uint128::from(6u128),
+            })
+            .unwrap(),
+            funds: vec![],
+        }))]
+    );
+}
+
+#[test]
+fn test_claim_refund_after_sweep() {
+    let mut deps = mock_dependencies(&[]);
+    init(&mut deps);
+
+    let info = mock_info("owner0001", &[]);
+    let mut env = mock_env();
+    let phase2_end = env.block.time.seconds() + 100 + SECONDS_PER_HOUR;
+    let launch_config = LaunchConfig {
+        amount: Uint128::from(1_000_000u64),
+        phase1_start: env.block.time.seconds(),
+        phase2_start: env.block.time.seconds() + 100,
+        phase2_end,
+        phase2_slot_period: SECONDS_PER_HOUR,
+        claim_deadline: Some(phase2_end + SECONDS_PER_HOUR),
+        sale_mode: SaleMode::BatchAuction {},
+        bonus_tiers: vec![],
+        lock_options: vec![],
+        loyalty_bonus: None,
+        referral_portion: None,
+    };
+    do_post_initialize(deps.as_mut(), env.clone(), info, launch_config).unwrap();
+
+    // the bid of addr0002 is below the clearing price and is fully refunded
+    let info1 = mock_info("addr0001", &[Coin::new(20_000, "uusd")]);
+    do_bid(deps.as_mut(), env.clone(), info1, Decimal::percent(2)).unwrap();
+    let info2 = mock_info("addr0002", &[Coin::new(10_000, "uusd")]);
+    do_bid(deps.as_mut(), env.clone(), info2, Decimal::percent(1)).unwrap();
+
+    env.block.time = env.block.time.plus_seconds(100 + SECONDS_PER_HOUR);
+    do_settle(deps.as_mut(), env.clone(), 30).unwrap();
+    let owner_info = mock_info("owner0001", &[]);
+    release_tokens(deps.as_mut(), env.clone(), owner_info.clone()).unwrap();
+
+    let info2 = mock_info("addr0002", &[]);
+    let err = execute(
+        deps.as_mut(),
+        env.clone(),
+        info2.clone(),
+        ExecuteMsg::ClaimRefund {},
+    )
+    .unwrap_err();
+    assert_eq!(
+        err,
+        ContractError::InvalidClaimRefund {
+            reason: "refunds are paid with the tokens until they are swept".to_string()
+        }
+    );
+
+    env.block.time = env.block.time.plus_seconds(SECONDS_PER_HOUR);
+    let res = execute(
+        deps.as_mut(),
+        env.clone(),
+        owner_info,
+        ExecuteMsg::SweepUnclaimedTokens {},
+    )
+    .unwrap();
+    assert_eq!(res.attributes[1], attr("swept_amount", "1000000"));
+
+    // the refund was not swept and can still be claimed, once
+    let res = execute(
+        deps.as_mut(),
+        env.clone(),
+        info2.clone(),
+        ExecuteMsg::ClaimRefund {},
+    )
+    .unwrap();
+    assert_eq!(
+        res.messages,
+        vec![SubMsg::new(CosmosMsg::Bank(BankMsg::Send {
+            to_address: "addr0002".to_string(),
+            amount: vec![Coin::new(10_000, "uusd")],
+        }))]
+    );
+    let err = execute(
+        deps.as_mut(),
+        env.clone(),
+        info2,
+        ExecuteMsg::ClaimRefund {},
+    )
+    .unwrap_err();
+    assert_eq!(
+        err,
+        ContractError::InvalidClaimRefund {
+            reason: "tokens were already claimed".to_string()
+        }
+    );
+
+    let info1 = mock_info("addr0001", &[]);
+    let err = execute(deps.as_mut(), env, info1, ExecuteMsg::ClaimRefund {}).unwrap_err();
+    assert_eq!(
+        err,
+        ContractError::InvalidClaimRefund {
+            reason: "no refund available for claim".to_string()
+        }
+    );
+}
+
+pub fn do_commit_bid(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    amount: u128,
+    salt: &str,
+) -> Result<Response, ContractError> {
+    let commitment = bid_commitment(&info.sender, Uint128::from(amount), salt);
+    execute(deps, env, info, ExecuteMsg::CommitBid { commitment })
+}
+
+pub fn do_reveal_bid(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    amount: u128,
+    salt: &str,
+) -> Result<Response, ContractError> {
+    let msg = ExecuteMsg::RevealBid {
+        amount: Uint128::from(amount),
+        salt: salt.to_string(),
+    };
+    execute(deps, env, info, msg)
+}
+
+#[test]
+fn proper_sealed_bid() {
+    let mut deps = mock_dependencies(&[]);
+    init(&mut deps);
+
+    post_init_with_sale_mode(&mut deps, SaleMode::SealedBid {});
+
+    let mut env = mock_env();
+
+    // public deposits are not allowed
+    let info1 = mock_info("addr0001", &[Coin::new(5_000, "uusd")]);
+    let err = do_deposit(deps.as_mut(), env.clone(), info1.clone()).unwrap_err();
+    assert_eq!(
+        err,
+        ContractError::InvalidDeposit {
+            reason: "sealed bid launches only take committed bids".to_string()
+        }
+    );
+
+    // bids of 3,000 and 2,000 uusd are committed with larger escrows
+    do_commit_bid(deps.as_mut(), env.clone(), info1.clone(), 3_000, "salt1").unwrap();
+    let info2 = mock_info("addr0002", &[Coin::new(2_000, "uusd")]);
+    do_commit_bid(deps.as_mut(), env.clone(), info2.clone(), 2_000, "salt2").unwrap();
+    let info3 = mock_info("addr0003", &[Coin::new(1_000, "uusd")]);
+    do_commit_bid(deps.as_mut(), env.clone(), info3.clone(), 1_000, "salt3").unwrap();
+
+    let err = do_commit_bid(deps.as_mut(), env.clone(), info2.clone(), 2_000, "salt2").unwrap_err();
+    assert_eq!(
+        err,
+        ContractError::InvalidCommitBid {
+            reason: "a bid was already committed".to_string()
+        }
+    );
+
+    // copying the commitment of someone else does not help, the hash includes the depositor
+    let info4 = mock_info("addr0004", &[Coin::new(5_000, "uusd")]);
+    let commitment = bid_commitment(&info1.sender, Uint128::from(3_000u128), "salt1");
+    execute(
+        deps.as_mut(),
+        env.clone(),
+        info4.clone(),
+        ExecuteMsg::CommitBid { commitment },
+    )
+    .unwrap();
+
+    let err = do_reveal_bid(deps.as_mut(), env.clone(), info1.clone(), 3_000, "salt1").unwrap_err();
+    assert_eq!(
+        err,
+        ContractError::InvalidRevealBid {
+            reason: "reveal period did not start yet".to_string()
+        }
+    );
+
+    // phase 2 is the reveal window
+    env.block.time = env.block.time.plus_seconds(100);
+    let err = do_reveal_bid(deps.as_mut(), env.clone(), info1.clone(), 3_000, "salt2").unwrap_err();
+    assert_eq!(
+        err,
+        ContractError::InvalidRevealBid {
+            reason: "bid does not match the commitment".to_string()
+        }
+    );
+
+    // the escrow above the bid is returned on reveal
+    let res = do_reveal_bid(deps.as_mut(), env.clone(), info1.clone(), 3_000, "salt1").unwrap();
+    assert_eq!(
+        res.messages,
+        vec![SubMsg::new(CosmosMsg::Bank(BankMsg::Send {
+            to_address: "addr0001".to_string(),
+            amount: vec![Coin::new(2_000, "uusd")],
+        }))]
+    );
+    let res = do_reveal_bid(deps.as_mut(), env.clone(), info2, 2_000, "salt2").unwrap();
+    assert_eq!(res.messages.len(), 0);
+
+    let err = do_reveal_bid(deps.as_mut(), env.clone(), info4.clone(), 3_000, "salt1").unwrap_err();
+    assert_eq!(
+        err,
+        ContractError::InvalidRevealBid {
+            reason: "bid does not match the commitment".to_string()
+        }
+    );
+
+    let err = execute(
+        deps.as_mut(),
+        env.clone(),
+        info4.clone(),
+        ExecuteMsg::ReclaimEscrow {},
+    )
+    .unwrap_err();
+    assert_eq!(
+        err,
+        ContractError::InvalidReclaimEscrow {
+            reason: "reveal period is not over yet".to_string()
+        }
+    );
+
+    // fast forward past phase 2, addr0003 and addr0004 did not reveal
+    env.block.time = env.block.time.plus_seconds(SECONDS_PER_HOUR);
+    let err = do_reveal_bid(deps.as_mut(), env.clone(), info3.clone(), 1_000, "salt3").unwrap_err();
+    assert_eq!(
+        err,
+        ContractError::InvalidRevealBid {
+            reason: "reveal period is over".to_string()
+        }
+    );
+
+    // the escrow can be reclaimed before the tokens are released
+    let res = execute(
+        deps.as_mut(),
+        env.clone(),
+        info4.clone(),
+        ExecuteMsg::ReclaimEscrow {},
+    )
+    .unwrap();
+    assert_eq!(
+        res.messages,
+        vec![SubMsg::new(CosmosMsg::Bank(BankMsg::Send {
+            to_address: "addr0004".to_string(),
+            amount: vec![Coin::new(5_000, "uusd")],
+        }))]
+    );
+    let err = execute(
+        deps.as_mut(),
+        env.clone(),
+        info4,
+        ExecuteMsg::ReclaimEscrow {},
+    )
+    .unwrap_err();
+    assert_eq!(
+        err,
+        ContractError::InvalidReclaimEscrow {
+            reason: "no unrevealed escrow found".to_string()
+        }
+    );
+
+    let owner_info = mock_info("owner0001", &[]);
+    release_tokens(deps.as_mut(), env.clone(), owner_info.clone()).unwrap();
+
+    // revealed bids share the tokens pro-rata
+    let deposit_info = do_query_deposit_info(deps.as_ref(), env.clone(), "addr0001".to_string());
+    assert_eq!(
+        deposit_info.unwrap(),
+        DepositResponse {
+            deposit: Uint128::from(3_000u128),
+            total_deposit: Uint128::from(5_000u128),
+            withdrawable_amount: Uint128::zero(),
+            tokens_to_claim: Uint128::from(600_000u128),
+            refund_to_claim: Uint128::zero(),
+            bonus_to_claim: Uint128::zero(),
+            referral_rewards_to_claim: Uint128::zero(),
+            can_claim: true,
+            settled: false,
+            weight: Uint128::zero(),
+            bonus_tiers: vec![],
+        }
+    );
+
+    // unrevealed bids lose the right to participate and get the escrow back
+    let res = do_withdraw_tokens(deps.as_mut(), env.clone(), info3.clone()).unwrap();
+    assert_eq!(
+        res.messages,
+        vec![SubMsg::new(CosmosMsg::Bank(BankMsg::Send {
+            to_address: "addr0003".to_string(),
+            amount: vec![Coin::new(1_000, "uusd")],
+        }))]
+    );
+
+    // the escrow refunded by the claim can not be reclaimed again
+    let err = execute(
+        deps.as_mut(),
+        env.clone(),
+        info3,
+        ExecuteMsg::ReclaimEscrow {},
+    )
+    .unwrap_err();
+    assert_eq!(
+        err,
+        ContractError::InvalidReclaimEscrow {
+            reason: "escrow was already refunded by the claim".to_string()
+        }
+    );
+
+    // escrows owed to depositors are not stray funds
+    deps.querier
+        .update_balance(MOCK_CONTRACT_ADDR, vec![Coin::new(5_000, "uusd")]);
+    let err = execute(
+        deps.as_mut(),
+        env.clone(),
+        owner_info.clone(),
+        ExecuteMsg::RecoverStrayFunds {
+            recipient: "owner0001".to_string(),
+        },
+    )
+    .unwrap_err();
+    assert_eq!(
+        err,
+        ContractError::InvalidRecoverStrayFunds {
+            reason: "no stray funds available to recover".to_string()
+        }
+    );
+
+    // only revealed bids are proceeds
+    let res = do_admin_withdraw(deps.as_mut(), env, owner_info).unwrap();
+    assert_eq!(
+        res.messages,
+        vec![SubMsg::new(CosmosMsg::Bank(BankMsg::Send {
+            to_address: "receiver0000".to_string(),
+            amount: vec![Coin::new(5_000, "uusd")],
+        }))]
+    );
+}
+
+#[test]
+fn proper_bonding_curve() {
+    let mut deps = mock_dependencies(&[]);
+    init(&mut deps);
+
+    // the spread can not take all the proceeds
+    let info = mock_info("owner0001", &[]);
+    let env = mock_env();
+    let curve = Curve::Linear {
+        base_price: Decimal::percent(1),
+        slope: Decimal::percent(1),
+    };
+    let launch_config = LaunchConfig {
+        amount: Uint128::from(1_000_000u64),
+        phase1_start: env.block.time.seconds(),
+        phase2_start: env.block.time.seconds() + 100,
+        phase2_end: env.block.time.seconds() + 100 + SECONDS_PER_HOUR,
+        phase2_slot_period: SECONDS_PER_HOUR,
+        claim_deadline: None,
+        sale_mode: SaleMode::BondingCurve {
+            curve: curve.clone(),
+            spread: Decimal::one(),
+        },
+        bonus_tiers: vec![],
+        lock_options: vec![],
+        loyalty_bonus: None,
+        referral_portion: None,
+    };
+    let err = do_post_initialize(deps.as_mut(), env, info, launch_config).unwrap_err();
+    assert_eq!(err, ContractError::InvalidLaunchConfig {});
+
+    // 1M tokens, price grows from 0.01 to 0.02 uusd
+    post_init_with_sale_mode(
+        &mut deps,
+        SaleMode::BondingCurve {
+            curve,
+            spread: Decimal::percent(1),
+        },
+    );
+
+    let mut env = mock_env();
+
+    // the first half of the supply costs 6,250 uusd
+    let info1 = mock_info("addr0001", &[Coin::new(6_250, "uusd")]);
+    let res = do_deposit(deps.as_mut(), env.clone(), info1.clone()).unwrap();
+    assert_eq!(res.messages.len(), 0);
+
+    // the second half costs 8,750 uusd, the rest of the deposit goes back
+    let info2 = mock_info("addr0002", &[Coin::new(10_000, "uusd")]);
+    let res = do_deposit(deps.as_mut(), env.clone(), info2.clone()).unwrap();
+    assert_eq!(
+        res.messages,
+        vec![SubMsg::new(CosmosMsg::Bank(BankMsg::Send {
+            to_address: "addr0002".to_string(),
+            amount: vec![Coin::new(1_250, "uusd")],
+        }))]
+    );
+
+    let info3 = mock_info("addr0003", &[Coin::new(1_000, "uusd")]);
+    let err = do_deposit(deps.as_mut(), env.clone(), info3).unwrap_err();
+    assert_eq!(
+        err,
+        ContractError::InvalidDeposit {
+            reason: "tokens are sold out".to_string()
+        }
+    );
+
+    // selling 100,000 tokens back from the top of the curve returns 1,950 uusd minus 1%
+    let res = do_withdraw(
+        deps.as_mut(),
+        env.clone(),
+        info2.clone(),
+        Some(Uint128::from(100_000u128)),
+    )
+    .unwrap();
+    assert_eq!(
+        res.attributes,
+        vec![
+            attr("action", "withdraw"),
+            attr("withdraw_amount", "1930"),
+            attr("sold_tokens", "100000"),
+            attr("recipient", "addr0002"),
+        ]
+    );
+
+    let deposit_info = do_query_deposit_info(deps.as_ref(), env.clone(), "addr0002".to_string());
+    assert_eq!(
+        deposit_info.unwrap(),
+        DepositResponse {
+            deposit: Uint128::from(7_000u128),
+            // deposits keep the cost of their tokens
+            total_deposit: Uint128::from(13_250u128),
+            withdrawable_amount: Uint128::from(6_732u128), // 6800 minus 1%
+            tokens_to_claim: Uint128::from(400_000u128),
+            refund_to_claim: Uint128::zero(),
+            bonus_to_claim: Uint128::zero(),
+            referral_rewards_to_claim: Uint128::zero(),
+            can_claim: false,
+            settled: false,
+            weight: Uint128::zero(),
+            bonus_tiers: vec![],
+        }
+    );
+
+    // tokens can only be sold back during phase 1
+    env.block.time = env.block.time.plus_seconds(100);
+    let err = do_withdraw(deps.as_mut(), env.clone(), info2, None).unwrap_err();
+    assert_eq!(
+        err,
+        ContractError::InvalidWithdraw {
+            reason: "tokens can only be sold back during phase 1".to_string()
+        }
+    );
+
+    // fast forward past phase 2, the tokens sold back return to the operator
+    env.block.time = env.block.time.plus_seconds(SECONDS_PER_HOUR);
+    let owner_info = mock_info("owner0001", &[]);
+    let res = release_tokens(deps.as_mut(), env.clone(), owner_info.clone()).unwrap();
+    assert_eq!(
+        res.messages,
+        vec![SubMsg::new(CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: "prism0001".to_string(),
+            msg: to_binary(&Cw20ExecuteMsg::Transfer {
+                recipient: "owner0001".to_string(),
+                amount: Uint128::from(100_000u128),
+            })
+            .unwrap(),
+            funds: vec![],
+        }))]
+    );
+
+    // claimable tokens were fixed when bought
+    let res = do_withdraw_tokens(deps.as_mut(), env.clone(), info1).unwrap();
+    assert_eq!(
+        res.messages,
+        vec![SubMsg::new(CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: "prism0001".to_string(),
+            msg: to_binary(&Cw20ExecuteMsg::Transfer {
+                recipient: "addr0001".to_string(),
+                amount: Uint128::from(500_000u128),
+            })
+            .unwrap(),
+            funds: vec![],
+        }))]
+    );
+
+    // the curve reserve, including the spread, is raised
+    let res = do_admin_withdraw(deps.as_mut(), env, owner_info).unwrap();
+    assert_eq!(
+        res.messages,
+        vec![SubMsg::new(CosmosMsg::Bank(BankMsg::Send {
+            to_address: "receiver0000".to_string(),
+            amount: vec![Coin::new(13_070, "uusd")],
+        }))]
+    );
+}
+
+#[test]
+fn proper_time_weighted() {
+    let mut deps = mock_dependencies(&[]);
+    init(&mut deps);
+
+    // 1M tokens, phase 1 lasts 100 seconds
+    post_init_with_sale_mode(&mut deps, SaleMode::TimeWeighted {});
+
+    let mut env = mock_env();
+
+    // 1,000 uusd with 100 seconds left weighs 100,000
+    let info1 = mock_info("addr0001", &[Coin::new(1_000, "uusd")]);
+    do_deposit(deps.as_mut(), env.clone(), info1.clone()).unwrap();
+
+    // 1,000 uusd with 50 seconds left and 1,000 uusd with 25 seconds left weigh 75,000
+    env.block.time = env.block.time.plus_seconds(50);
+    let info2 = mock_info("addr0002", &[Coin::new(1_000, "uusd")]);
+    do_deposit(deps.as_mut(), env.clone(), info2.clone()).unwrap();
+    env.block.time = env.block.time.plus_seconds(25);
+    do_deposit(deps.as_mut(), env.clone(), info2.clone()).unwrap();
+
+    // the withdrawal removes the last deposit first, then half of the first one
+    env.block.time = env.block.time.plus_seconds(5);
+    let info2 = mock_info("addr0002", &[]);
+    do_withdraw(
+        deps.as_mut(),
+        env.clone(),
+        info2.clone(),
+        Some(Uint128::from(1_500u128)),
+    )
+    .unwrap();
+    let deposit = DEPOSITS
+        .load(&deps.storage, &Addr::unchecked("addr0002"))
+        .unwrap();
+    assert_eq!(deposit.amount, Uint128::from(500u128));
+    assert_eq!(deposit.weight, Uint128::from(25_000u128));
+    assert_eq!(
+        TOTAL_WEIGHT.load(&deps.storage).unwrap(),
+        Uint128::from(125_000u128)
+    );
+
+    // tokens are split by weight rather than by deposit
+    let deposit_info =
+        do_query_deposit_info(deps.as_ref(), env.clone(), "addr0001".to_string()).unwrap();
+    assert_eq!(deposit_info.tokens_to_claim, Uint128::from(800_000u128));
+
+    // fast forward past phase 2, all the tokens are sold
+    env.block.time = env.block.time.plus_seconds(20 + SECONDS_PER_HOUR);
+    let owner_info = mock_info("owner0001", &[]);
+    let res = release_tokens(deps.as_mut(), env.clone(), owner_info.clone()).unwrap();
+    assert_eq!(res.messages, vec![]);
+
+    let res = do_withdraw_tokens(deps.as_mut(), env.clone(), info2).unwrap();
+    assert_eq!(
+        res.messages,
+        vec![SubMsg::new(CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: "prism0001".to_string(),
+            msg: to_binary(&Cw20ExecuteMsg::Transfer {
+                recipient: "addr0002".to_string(),
+                amount: Uint128::from(200_000u128),
+            })
+            .unwrap(),
+            funds: vec![],
+        }))]
+    );
+
+    let accounting: TokenAccountingResponse =
+        from_binary(&query(deps.as_ref(), env.clone(), QueryMsg::TokenAccounting {}).unwrap())
+            .unwrap();
+    assert_eq!(
+        accounting,
+        TokenAccountingResponse {
+            amount: Uint128::from(1_000_000u128),
+            allocated: Uint128::from(1_000_000u128),
+            claimed: Uint128::from(200_000u128),
+            unsold: Uint128::zero(),
+            dust: Uint128::zero(),
+            swept: Uint128::zero(),
+        }
+    );
+
+    // all the deposits are raised
+    let res = do_admin_withdraw(deps.as_mut(), env, owner_info).unwrap();
+    assert_eq!(
+        res.messages,
+        vec![SubMsg::new(CosmosMsg::Bank(BankMsg::Send {
+            to_address: "receiver0000".to_string(),
+            amount: vec![Coin::new(1_500, "uusd")],
+        }))]
+    );
+}
+
+#[test]
+fn proper_bonus_tiers() {
+    let mut deps = mock_dependencies(&[]);
+    init(&mut deps);
+
+    // 1.2x during the first 50 seconds and 1.1x until 80 seconds
+    let info = mock_info("owner0001", &[]);
+    let mut env = mock_env();
+    let bonus_tiers = vec![
+        BonusTier {
+            until_timestamp: env.block.time.seconds() + 50,
+            multiplier: Decimal::percent(120),
+        },
+        BonusTier {
+            until_timestamp: env.block.time.seconds() + 80,
+            multiplier: Decimal::percent(110),
+        },
+    ];
+    let mut launch_config = LaunchConfig {
+        amount: Uint128::from(1_000_000u64),
+        phase1_start: env.block.time.seconds(),
+        phase2_start: env.block.time.seconds() + 100,
+        phase2_end: env.block.time.seconds() + 100 + SECONDS_PER_HOUR,
+        phase2_slot_period: SECONDS_PER_HOUR,
+        claim_deadline: None,
+        sale_mode: SaleMode::ProRata {},
+        bonus_tiers: bonus_tiers.iter().rev().cloned().collect(),
+        lock_options: vec![],
+        loyalty_bonus: None,
+        referral_portion: None,
+    };
+
+    // tiers must be sorted
+    let err = do_post_initialize(
+        deps.as_mut(),
+        env.clone(),
+        info.clone(),
+        launch_config.clone(),
+    )
+    .unwrap_err();
+    assert_eq!(err, ContractError::InvalidLaunchConfig {});
+
+    // tiers are only allowed on pro-rata sales
+    launch_config.bonus_tiers = bonus_tiers.clone();
+    launch_config.sale_mode = SaleMode::TimeWeighted {};
+    let err = do_post_initialize(
+        deps.as_mut(),
+        env.clone(),
+        info.clone(),
+        launch_config.clone(),
+    )
+    .unwrap_err();
+    assert_eq!(err, ContractError::InvalidLaunchConfig {});
+
+    launch_config.sale_mode = SaleMode::ProRata {};
+    do_post_initialize(deps.as_mut(), env.clone(), info, launch_config).unwrap();
+
+    // 1,000 uusd in the first tier weighs 1,200
+    let info1 = mock_info("addr0001", &[Coin::new(1_000, "uusd")]);
+    do_deposit(deps.as_mut(), env.clone(), info1.clone()).unwrap();
+
+    // 1,000 uusd in the second tier weighs 1,100
+    env.block.time = env.block.time.plus_seconds(60);
+    let info2 = mock_info("addr0002", &[Coin::new(1_000, "uusd")]);
+    do_deposit(deps.as_mut(), env.clone(), info2).unwrap();
+
+    // 700 uusd after the tiers weighs 700
+    env.block.time = env.block.time.plus_seconds(30);
+    let info3 = mock_info("addr0003", &[Coin::new(700, "uusd")]);
+    do_deposit(deps.as_mut(), env.clone(), info3).unwrap();
+
+    // withdrawing half of the deposit burns half of the weight
+    env.block.time = env.block.time.plus_seconds(5);
+    let info2 = mock_info("addr0002", &[]);
+    do_withdraw(
+        deps.as_mut(),
+        env.clone(),
+        info2.clone(),
+        Some(Uint128::from(500u128)),
+    )
+    .unwrap();
+
+    // tokens are split by weight, out of a total weight of 2,450
+    let deposit_info =
+        do_query_deposit_info(deps.as_ref(), env.clone(), "addr0002".to_string()).unwrap();
+    assert_eq!(
+        deposit_info,
+        DepositResponse {
+            deposit: Uint128::from(500u128),
+            total_deposit: Uint128::from(2_200u128),
+            withdrawable_amount: Uint128::from(500u128),
+            tokens_to_claim: Uint128::from(224_489u128),
+            refund_to_claim: Uint128::zero(),
+            bonus_to_claim: Uint128::zero(),
+            referral_rewards_to_claim: Uint128::zero(),
+            can_claim: false,
+            settled: false,
+            weight: Uint128::from(550u128),
+            bonus_tiers,
+        }
+    );
+
+    // fast forward past phase 2, the rounding leaves some dust
+    env.block.time = env.block.time.plus_seconds(5 + SECONDS_PER_HOUR);
+    let owner_info = mock_info("owner0001", &[]);
+    release_tokens(deps.as_mut(), env.clone(), owner_info).unwrap();
+    let res = do_withdraw_tokens(deps.as_mut(), env.clone(), info1).unwrap();
+    assert_eq!(
+        res.messages,
+        vec![SubMsg::new(CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: "prism0001".to_string(),
+            msg: to_binary(&Cw20ExecuteMsg::Transfer {
+                recipient: "addr0001".to_string(),
+                amount: Uint128::from(489_795u128),
+            })
+            .unwrap(),
+            funds: vec![],
+        }))]
+    );
+
+    let accounting: TokenAccountingResponse =
+        from_binary(&query(deps.as_ref(), env, QueryMsg::TokenAccounting {}).unwrap()).unwrap();
+    assert_eq!(
+        accounting,
+        TokenAccountingResponse {
+            amount: Uint128::from(1_000_000u128),
+            allocated: Uint128::from(999_999u128),
+            claimed: Uint128::from(489_795u128),
+            unsold: Uint128::zero(),
+            dust: Uint128::from(1u128),
+            swept: Uint128::zero(),
+        }
+    );
+}
+
+#[test]
+fn proper_loyalty_bonus() {
+    let mut deps = mock_dependencies(&[]);
+    init(&mut deps);
+
+    // 1M tokens and a loyalty bonus of 100,000 tokens are funded together
+    let info = mock_info("owner0001", &[]);
+    let mut env = mock_env();
+    let launch_config = LaunchConfig {
+        amount: Uint128::from(1_000_000u64),
+        phase1_start: env.block.time.seconds(),
+        phase2_start: env.block.time.seconds() + 100,
+        phase2_end: env.block.time.seconds() + 100 + SECONDS_PER_HOUR,
+        phase2_slot_period: SECONDS_PER_HOUR,
+        claim_deadline: None,
+        sale_mode: SaleMode::ProRata {},
+        bonus_tiers: vec![],
+        lock_options: vec![],
+        loyalty_bonus: Some(Uint128::from(100_000u64)),
+        referral_portion: None,
+    };
+    let res = do_post_initialize(deps.as_mut(), env.clone(), info, launch_config).unwrap();
+    assert_eq!(
+        res.messages,
+        vec![SubMsg::new(CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: "prism0001".to_string(),
+            msg: to_binary(&Cw20ExecuteMsg::TransferFrom {
+                owner: "owner0001".to_string(),
+                recipient: MOCK_CONTRACT_ADDR.to_string(),
+                amount: Uint128::from(1_100_000u128),
+            })
+            .unwrap(),
+            funds: vec![],
+        }))]
+    );
+
+    let info1 = mock_info("addr0001", &[Coin::new(1_000, "uusd")]);
+    do_deposit(deps.as_mut(), env.clone(), info1.clone()).unwrap();
+    let info2 = mock_info("addr0002", &[Coin::new(3_000, "uusd")]);
+    do_deposit(deps.as_mut(), env.clone(), info2.clone()).unwrap();
+    let info3 = mock_info("addr0003", &[Coin::new(1_000, "uusd")]);
+    do_deposit(deps.as_mut(), env.clone(), info3).unwrap();
+
+    // withdrawing during phase 1 keeps the position loyal
+    let deposit_info =
+        do_query_deposit_info(deps.as_ref(), env.clone(), "addr0002".to_string()).unwrap();
+    assert_eq!(deposit_info.bonus_to_claim, Uint128::from(60_000u128));
+
+    // withdrawing during phase 2 gives up the bonus for the whole position
+    env.block.time = env.block.time.plus_seconds(101);
+    do_withdraw(
+        deps.as_mut(),
+        env.clone(),
+        info2,
+        Some(Uint128::from(1_000u128)),
+    )
+    .unwrap();
+    let deposit_info =
+        do_query_deposit_info(deps.as_ref(), env.clone(), "addr0002".to_string()).unwrap();
+    assert_eq!(deposit_info.tokens_to_claim, Uint128::from(500_000u128));
+    assert_eq!(deposit_info.bonus_to_claim, Uint128::zero());
+
+    // the bonus is shared by the loyal deposits
+    let deposit_info =
+        do_query_deposit_info(deps.as_ref(), env.clone(), "addr0001".to_string()).unwrap();
+    assert_eq!(deposit_info.tokens_to_claim, Uint128::from(250_000u128));
+    assert_eq!(deposit_info.bonus_to_claim, Uint128::from(50_000u128));
+
+    // fast forward past phase 2, claims pay the base tokens plus the bonus
+    env.block.time = env.block.time.plus_seconds(SECONDS_PER_HOUR);
+    let owner_info = mock_info("owner0001", &[]);
+    let res = release_tokens(deps.as_mut(), env.clone(), owner_info).unwrap();
+    assert_eq!(res.messages, vec![]);
+    let res = do_withdraw_tokens(deps.as_mut(), env.clone(), info1).unwrap();
+    assert_eq!(
+        res.messages,
+        vec![SubMsg::new(CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: "prism0001".to_string(),
+            msg: to_binary(&Cw20ExecuteMsg::Transfer {
+                recipient: "addr0001".to_string(),
+                amount: Uint128::from(300_000u128),
+            })
+            .unwrap(),
+            funds: vec![],
+        }))]
+    );
+
+    let accounting: TokenAccountingResponse =
+        from_binary(&query(deps.as_ref(), env, QueryMsg::TokenAccounting {}).unwrap()).unwrap();
+    assert_eq!(
+        accounting,
+        TokenAccountingResponse {
+            amount: Uint128::from(1_100_000u128),
+            allocated: Uint128::from(1_100_000u128),
+            claimed: Uint128::from(300_000u128),
+            unsold: Uint128::zero(),
+            dust: Uint128::zero(),
+            swept: Uint128::zero(),
+        }
+    );
+}
+
+#[test]
+fn test_loyalty_bonus_sale_modes() {
+    let mut deps = mock_dependencies(&[]);
+    init(&mut deps);
+
+    // bids, fixed prices and curve sell backs do not keep deposits proportional to the tokens
+    let info = mock_info("owner0001", &[]);
+    let env = mock_env();
+    let sale_modes = vec![
+        SaleMode::BondingCurve {
+            curve: Curve::Linear {
+                base_price: Decimal::percent(1),
+                slope: Decimal::percent(1),
+            },
+            spread: Decimal::percent(1),
+        },
+        SaleMode::FixedPrice {
+            price: Decimal::percent(1),
+        },
+        SaleMode::DutchAuction {
+            start_price: Decimal::percent(2),
+            floor_price: Decimal::percent(1),
+        },
+        SaleMode::BatchAuction {},
+        SaleMode::SealedBid {},
+    ];
+    let mut launch_config = LaunchConfig {
+        amount: Uint128::from(1_000_000u64),
+        phase1_start: env.block.time.seconds(),
+        phase2_start: env.block.time.seconds() + 100,
+        phase2_end: env.block.time.seconds() + 100 + SECONDS_PER_HOUR,
+        phase2_slot_period: SECONDS_PER_HOUR,
+        claim_deadline: None,
+        sale_mode: SaleMode::ProRata {},
+        bonus_tiers: vec![],
+        lock_options: vec![],
+        loyalty_bonus: Some(Uint128::from(100_000u64)),
+        referral_portion: None,
+    };
+    for sale_mode in sale_modes {
+        launch_config.sale_mode = sale_mode;
+        let err = do_post_initialize(
+            deps.as_mut(),
+            env.clone(),
+            info.clone(),
+            launch_config.clone(),
+        )
+        .unwrap_err();
+        assert_eq!(err, ContractError::InvalidLaunchConfig {});
+    }
+
+    // time weighted deposits still share the bonus by amount
+    launch_config.sale_mode = SaleMode::TimeWeighted {};
+    do_post_initialize(deps.as_mut(), env, info, launch_config).unwrap();
+}
+
+#[test]
+fn proper_loyalty_bonus_without_loyal_deposits() {
+    let mut deps = mock_dependencies(&[]);
+    init(&mut deps);
+
+    let info = mock_info("owner0001", &[]);
+    let mut env = mock_env();
+    let launch_config = LaunchConfig {
+        amount: Uint128::from(1_000_000u64),
+        phase1_start: env.block.time.seconds(),
+        phase2_start: env.block.time.seconds() + 100,
+        phase2_end: env.block.time.seconds() + 100 + SECONDS_PER_HOUR,
+        phase2_slot_period: SECONDS_PER_HOUR,
+        claim_deadline: None,
+        sale_mode: SaleMode::ProRata {},
+        bonus_tiers: vec![],
+        lock_options: vec![],
+        loyalty_bonus: Some(Uint128::from(100_000u64)),
+        referral_portion: None,
+    };
+    do_post_initialize(deps.as_mut(), env.clone(), info, launch_config).unwrap();
+
+    let info1 = mock_info("addr0001", &[Coin::new(1_000, "uusd")]);
+    do_deposit(deps.as_mut(), env.clone(), info1.clone()).unwrap();
+
+    env.block.time = env.block.time.plus_seconds(101);
+    do_withdraw(
+        deps.as_mut(),
+        env.clone(),
+        info1,
+        Some(Uint128::from(500u128)),
+    )
+    .unwrap();
+
+    // nobody is loyal, so the bonus goes back with the unsold tokens
+    env.block.time = env.block.time.plus_seconds(SECONDS_PER_HOUR);
+    let owner_info = mock_info("owner0001", &[]);
+    let res = release_tokens(deps.as_mut(), env, owner_info).unwrap();
+    assert_eq!(
+        res.messages,
+        vec![SubMsg::new(CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: "prism0001".to_string(),
+            msg: to_binary(&Cw20ExecuteMsg::Transfer {
+                recipient: "owner0001".to_string(),
+                amount: Uint128::from(100_000u128),
+            })
+            .unwrap(),
+            funds: vec![],
+        }))]
+    );
+}
+
+pub fn do_deposit_with_referrer(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    referrer: &str,
+) -> Result<Response, ContractError> {
+    execute(
+        deps,
+        env,
+        info,
+        ExecuteMsg::Deposit {
+            max_price: None,
+            referrer: Some(referrer.to_string()),
+            lock_duration: None,
+            recipient: None,
+        },
+    )
+}
+
+#[test]
+fn proper_referral_rewards() {
+    let mut deps = mock_dependencies(&[]);
+    init(&mut deps);
+
+    // referral rewards are only set aside on pro-rata sales
+    let info = mock_info("owner0001", &[]);
+    let mut env = mock_env();
+    let mut launch_config = LaunchConfig {
+        amount: Uint128::from(1_000_000u64),
+        phase1_start: env.block.time.seconds(),
+        phase2_start: env.block.time.seconds() + 100,
+        phase2_end: env.block.time.seconds() + 100 + SECONDS_PER_HOUR,
+        phase2_slot_period: SECONDS_PER_HOUR,
+        claim_deadline: None,
+        sale_mode: SaleMode::FixedPrice {
+            price: Decimal::percent(1),
+        },
+        bonus_tiers: vec![],
+        lock_options: vec![],
+        loyalty_bonus: None,
+        referral_portion: Some(Decimal::percent(10)),
+    };
+    let err = do_post_initialize(
+        deps.as_mut(),
+        env.clone(),
+        info.clone(),
+        launch_config.clone(),
+    )
+    .unwrap_err();
+    assert_eq!(err, ContractError::InvalidLaunchConfig {});
+
+    // 100,000 tokens go to the referrers and 900,000 tokens to the depositors
+    launch_config.sale_mode = SaleMode::ProRata {};
+    do_post_initialize(deps.as_mut(), env.clone(), info, launch_config).unwrap();
+
+    let info1 = mock_info("addr0001", &[Coin::new(1_000, "uusd")]);
+    let err = do_deposit_with_referrer(deps.as_mut(), env.clone(), info1.clone(), "addr0001")
+        .unwrap_err();
+    assert_eq!(
+        err,
+        ContractError::InvalidDeposit {
+            reason: "can not refer yourself".to_string()
+        }
+    );
+    do_deposit_with_referrer(deps.as_mut(), env.clone(), info1.clone(), "ref0001").unwrap();
+
+    // the referrer can not change after the first deposit
+    let err =
+        do_deposit_with_referrer(deps.as_mut(), env.clone(), info1.clone(), "ref0002").unwrap_err();
+    assert_eq!(
+        err,
+        ContractError::InvalidDeposit {
+            reason: "referrer can not be changed after the first deposit".to_string()
+        }
+    );
+    let info3 = mock_info("addr0003", &[Coin::new(1_000, "uusd")]);
+    do_deposit(deps.as_mut(), env.clone(), info3.clone()).unwrap();
+    let err = do_deposit_with_referrer(deps.as_mut(), env.clone(), info3, "ref0001").unwrap_err();
+    assert_eq!(
+        err,
+        ContractError::InvalidDeposit {
+            reason: "referrer can not be changed after the first deposit".to_string()
+        }
+    );
+
+    // depositors can refer other depositors
+    let info2 = mock_info("addr0002", &[Coin::new(3_000, "uusd")]);
+    do_deposit_with_referrer(deps.as_mut(), env.clone(), info2.clone(), "addr0001").unwrap();
+
+    // only the net deposits brought in count
+    let info2 = mock_info("addr0002", &[]);
+    do_withdraw(
+        deps.as_mut(),
+        env.clone(),
+        info2,
+        Some(Uint128::from(1_000u128)),
+    )
+    .unwrap();
+
+    let referral: ReferralResponse = from_binary(
+        &query(
+            deps.as_ref(),
+            env.clone(),
+            QueryMsg::Referral {
+                address: "addr0001".to_string(),
+            },
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(
+        referral,
+        ReferralResponse {
+            referrer: Some("ref0001".to_string()),
+            referred_deposit: Uint128::from(2_000u128),
+            total_referred_deposit: Uint128::from(3_000u128),
+            rewards_to_claim: Uint128::from(66_666u128),
+            rewards_claimed: false,
+        }
+    );
+    let deposit_info =
+        do_query_deposit_info(deps.as_ref(), env.clone(), "addr0001".to_string()).unwrap();
+    assert_eq!(deposit_info.tokens_to_claim, Uint128::from(225_000u128));
+
+    // rewards can be claimed once the tokens are released
+    let ref_info = mock_info("ref0001", &[]);
+    let err = execute(
+        deps.as_mut(),
+        env.clone(),
+        ref_info.clone(),
+        ExecuteMsg::ClaimReferralRewards {},
+    )
+    .unwrap_err();
+    assert_eq!(
+        err,
+        ContractError::InvalidClaimReferralRewards {
+            reason: "cannot claim referral rewards yet".to_string()
+        }
+    );
+
+    env.block.time = env.block.time.plus_seconds(100 + SECONDS_PER_HOUR);
+    let owner_info = mock_info("owner0001", &[]);
+    let res = release_tokens(deps.as_mut(), env.clone(), owner_info).unwrap();
+    assert_eq!(res.messages, vec![]);
+
+    let res = execute(
+        deps.as_mut(),
+        env.clone(),
+        ref_info.clone(),
+        ExecuteMsg::ClaimReferralRewards {},
+    )
+    .unwrap();
+    assert_eq!(
+        res.messages,
+        vec![SubMsg::new(CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: "prism0001".to_string(),
+            msg: to_binary(&Cw20ExecuteMsg::Transfer {
+                recipient: "ref0001".to_string(),
+                amount: Uint128::from(33_333u128),
+            })
+            .unwrap(),
+            funds: vec![],
+        }))]
+    );
+
+    let err = execute(
+        deps.as_mut(),
+        env.clone(),
+        ref_info,
+        ExecuteMsg::ClaimReferralRewards {},
+    )
+    .unwrap_err();
+    assert_eq!(
+        err,
+        ContractError::InvalidClaimReferralRewards {
+            reason: "referral rewards were already claimed".to_string()
+        }
+    );
+
+    let err = execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("addr0003", &[]),
+        ExecuteMsg::ClaimReferralRewards {},
+    )
+    .unwrap_err();
+    assert_eq!(
+        err,
+        ContractError::InvalidClaimReferralRewards {
+            reason: "referral information not found".to_string()
+        }
+    );
+
+    let accounting: TokenAccountingResponse =
+        from_binary(&query(deps.as_ref(), env, QueryMsg::TokenAccounting {}).unwrap()).unwrap();
+    assert_eq!(
+        accounting,
+        TokenAccountingResponse {
+            amount: Uint128::from(1_000_000u128),
+            allocated: Uint128::from(999_999u128),
+            claimed: Uint128::from(33_333u128),
+            unsold: Uint128::zero(),
+            dust: Uint128::from(1u128),
+            swept: Uint128::zero(),
+        }
+    );
+}
+
+pub fn do_deposit_with_lock(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    lock_duration: u64,
+) -> Result<Response, ContractError> {
+    execute(
+        deps,
+        env,
+        info,
+        ExecuteMsg::Deposit {
+            max_price: None,
+            referrer: None,
+            lock_duration: Some(lock_duration),
+            recipient: None,
+        },
+    )
+}
+
+#[test]
+fn proper_lock_positions() {
+    let mut deps = mock_dependencies(&[]);
+    init(&mut deps);
+
+    // 1.5x for 3 months and 2x for 6 months
+    let info = mock_info("owner0001", &[]);
+    let mut env = mock_env();
+    let three_months = 90 * 24 * SECONDS_PER_HOUR;
+    let six_months = 2 * three_months;
+    let phase2_end = env.block.time.seconds() + 100 + SECONDS_PER_HOUR;
+    let mut launch_config = LaunchConfig {
+        amount: Uint128::from(1_000_000u64),
+        phase1_start: env.block.time.seconds(),
+        phase2_start: env.block.time.seconds() + 100,
+        phase2_end,
+        phase2_slot_period: SECONDS_PER_HOUR,
+        claim_deadline: None,
+        sale_mode: SaleMode::ProRata {},
+        bonus_tiers: vec![],
+        lock_options: vec![
+            LockOption {
+                duration: three_months,
+                multiplier: Decimal::percent(150),
+            },
+            LockOption {
+                duration: three_months,
+                multiplier: Decimal::percent(200),
+            },
+        ],
+        loyalty_bonus: None,
+        referral_portion: None,
+    };
+
+    // each duration has a single multiplier
+    let err = do_post_initialize(
+        deps.as_mut(),
+        env.clone(),
+        info.clone(),
+        launch_config.clone(),
+    )
+    .unwrap_err();
+    assert_eq!(err, ContractError::InvalidLaunchConfig {});
+
+    // locks must last and end before the claim deadline
+    launch_config.lock_options[1].duration = 0;
+    let err = do_post_initialize(
+        deps.as_mut(),
+        env.clone(),
+        info.clone(),
+        launch_config.clone(),
+    )
+    .unwrap_err();
+    assert_eq!(err, ContractError::InvalidLaunchConfig {});
+
+    launch_config.lock_options[1].duration = six_months;
+    launch_config.claim_deadline = Some(phase2_end + three_months);
+    let err = do_post_initialize(
+        deps.as_mut(),
+        env.clone(),
+        info.clone(),
+        launch_config.clone(),
+    )
+    .unwrap_err();
+    assert_eq!(err, ContractError::InvalidLaunchConfig {});
+
+    launch_config.claim_deadline = None;
+    do_post_initialize(deps.as_mut(), env.clone(), info, launch_config).unwrap();
+
+    let info1 = mock_info("addr0001", &[Coin::new(1_000, "uusd")]);
+    let err = do_deposit_with_lock(deps.as_mut(), env.clone(), info1.clone(), 1).unwrap_err();
+    assert_eq!(
+        err,
+        ContractError::InvalidDeposit {
+            reason: "lock duration is not available".to_string()
+        }
+    );
+
+    // 1,000 uusd unlocked, 1,000 uusd locked for 6 months and 500 uusd locked for 3 months
+    // weigh 3,750
+    do_deposit(deps.as_mut(), env.clone(), info1.clone()).unwrap();
+    do_deposit_with_lock(deps.as_mut(), env.clone(), info1, six_months).unwrap();
+    let info1 = mock_info("addr0001", &[Coin::new(500, "uusd")]);
+    do_deposit_with_lock(deps.as_mut(), env.clone(), info1, three_months).unwrap();
+
+    let info2 = mock_info("addr0002", &[Coin::new(1_000, "uusd")]);
+    do_deposit_with_lock(deps.as_mut(), env.clone(), info2, three_months).unwrap();
+
+    // locks only give up phase 2 withdraws, withdrawing on phase 1 releases the lock
+    let info3 = mock_info("addr0003", &[Coin::new(1_000, "uusd")]);
+    do_deposit_with_lock(deps.as_mut(), env.clone(), info3, three_months).unwrap();
+    let info3 = mock_info("addr0003", &[]);
+    let res = do_withdraw(deps.as_mut(), env.clone(), info3, None).unwrap();
+    assert_eq!(
+        res.messages,
+        vec![SubMsg::new(CosmosMsg::Bank(BankMsg::Send {
+            to_address: "addr0003".to_string(),
+            amount: vec![Coin::new(1_000, "uusd")],
+        }))]
+    );
+    let locks: LocksResponse = from_binary(
+        &query(
+            deps.as_ref(),
+            env.clone(),
+            QueryMsg::Locks {
+                address: "addr0003".to_string(),
+            },
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(locks.locks, vec![]);
+
+    // the unlocked deposit is withdrawn first, with its weight
+    let info1 = mock_info("addr0001", &[]);
+    let res = do_withdraw(
+        deps.as_mut(),
+        env.clone(),
+        info1.clone(),
+        Some(Uint128::from(500u128)),
+    )
+    .unwrap();
+    assert_eq!(
+        res.messages,
+        vec![SubMsg::new(CosmosMsg::Bank(BankMsg::Send {
+            to_address: "addr0001".to_string(),
+            amount: vec![Coin::new(500, "uusd")],
+        }))]
+    );
+
+    let locks: LocksResponse = from_binary(
+        &query(
+            deps.as_ref(),
+            env.clone(),
+            QueryMsg::Locks {
+                address: "addr0001".to_string(),
+            },
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(
+        locks,
+        LocksResponse {
+            locks: vec![
+                LockPositionResponse {
+                    amount: Uint128::from(1_000u128),
+                    multiplier: Decimal::percent(200),
+                    unlock_time: phase2_end + six_months,
+                },
+                LockPositionResponse {
+                    amount: Uint128::from(500u128),
+                    multiplier: Decimal::percent(150),
+                    unlock_time: phase2_end + three_months,
+                },
+            ]
+        }
+    );
+
+    // tokens are split by weight, out of a total weight of 4,750
+    let deposit_info =
+        do_query_deposit_info(deps.as_ref(), env.clone(), "addr0001".to_string()).unwrap();
+    assert_eq!(deposit_info.withdrawable_amount, Uint128::from(2_000u128));
+    assert_eq!(deposit_info.weight, Uint128::from(3_250u128));
+    assert_eq!(deposit_info.tokens_to_claim, Uint128::from(684_210u128));
+
+    // locked deposits can not be withdrawn on phase 2
+    let mut phase2_env = env.clone();
+    phase2_env.block.time = phase2_env.block.time.plus_seconds(101);
+    let info2 = mock_info("addr0002", &[]);
+    let err = do_withdraw(deps.as_mut(), phase2_env, info2.clone(), None).unwrap_err();
+    assert_eq!(
+        err,
+        ContractError::InvalidWithdraw {
+            reason: "locked deposits can not be withdrawn on phase 2".to_string()
+        }
+    );
+
+    env.block.time = env.block.time.plus_seconds(100 + SECONDS_PER_HOUR);
+    let owner_info = mock_info("owner0001", &[]);
+    release_tokens(deps.as_mut(), env.clone(), owner_info.clone()).unwrap();
+
+    // the tokens of the locked part are held until the last lock ends
+    let err = do_withdraw_tokens(deps.as_mut(), env.clone(), info2.clone()).unwrap_err();
+    assert_eq!(
+        err,
+        ContractError::InvalidWithdrawTokens {
+            reason: "tokens are locked until the last lock ends".to_string()
+        }
+    );
+    let deposit_info =
+        do_query_deposit_info(deps.as_ref(), env.clone(), "addr0002".to_string()).unwrap();
+    assert!(!deposit_info.can_claim);
+
+    // the part outside the locks is claimed right away
+    let deposit_info =
+        do_query_deposit_info(deps.as_ref(), env.clone(), "addr0001".to_string()).unwrap();
+    assert!(deposit_info.can_claim);
+    let res = do_withdraw_tokens(deps.as_mut(), env.clone(), info1.clone()).unwrap();
+    assert_eq!(
+        res.messages,
+        vec![SubMsg::new(CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: "prism0001".to_string(),
+            msg: to_binary(&Cw20ExecuteMsg::Transfer {
+                recipient: "addr0001".to_string(),
+                amount: Uint128::from(105_263u128),
+            })
+            .unwrap(),
+            funds: vec![],
+        }))]
+    );
+    let deposit_info =
+        do_query_deposit_info(deps.as_ref(), env.clone(), "addr0001".to_string()).unwrap();
+    assert!(!deposit_info.can_claim);
+    assert_eq!(deposit_info.tokens_to_claim, Uint128::from(578_947u128));
+    let err = do_withdraw_tokens(deps.as_mut(), env.clone(), info1.clone()).unwrap_err();
+    assert_eq!(
+        err,
+        ContractError::InvalidWithdrawTokens {
+            reason: "tokens are locked until the last lock ends".to_string()
+        }
+    );
+
+    let res = do_distribute_tokens(deps.as_mut(), env.clone(), info2.clone(), None).unwrap();
+    assert_eq!(res.messages.len(), 0);
+
+    // the 3 months lock ends, addr0001 still has a lock running
+    env.block.time = env.block.time.plus_seconds(three_months);
+    let res = do_withdraw_tokens(deps.as_mut(), env.clone(), info2).unwrap();
+    assert_eq!(
+        res.messages,
+        vec![SubMsg::new(CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: "prism0001".to_string(),
+            msg: to_binary(&Cw20ExecuteMsg::Transfer {
+                recipient: "addr0002".to_string(),
+                amount: Uint128::from(315_789u128),
+            })
+            .unwrap(),
+            funds: vec![],
+        }))]
+    );
+    let err = do_withdraw_tokens(deps.as_mut(), env.clone(), info1.clone()).unwrap_err();
+    assert_eq!(
+        err,
+        ContractError::InvalidWithdrawTokens {
+            reason: "tokens are locked until the last lock ends".to_string()
+        }
+    );
+
+    env.block.time = env.block.time.plus_seconds(three_months);
+    let res = do_withdraw_tokens(deps.as_mut(), env.clone(), info1).unwrap();
+    assert_eq!(
+        res.messages,
+        vec![SubMsg::new(CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: "prism0001".to_string(),
+            msg: to_binary(&Cw20ExecuteMsg::Transfer {
+                recipient: "addr0001".to_string(),
+                amount: Uint128::from(578_947u128),
+            })
+            .unwrap(),
+            funds: vec![],
+        }))]
+    );
+
+    // locked deposits are kept as the proceeds of the sale
+    let res = do_admin_withdraw(deps.as_mut(), env, owner_info).unwrap();
+    assert_eq!(
+        res.messages,
+        vec![SubMsg::new(CosmosMsg::Bank(BankMsg::Send {
+            to_address: "receiver0000".to_string(),
+            amount: vec![Coin::new(3_000, "uusd")],
+        }))]
+    );
+}
+
+#[test]
+fn proper_distribute_tokens_after_lock_ends() {
+    let mut deps = mock_dependencies(&[]);
+    init(&mut deps);
+
+    let info = mock_info("owner0001", &[]);
+    let mut env = mock_env();
+    let three_months = 90 * 24 * SECONDS_PER_HOUR;
+    let launch_config = LaunchConfig {
+        amount: Uint128::from(1_000_000u64),
+        phase1_start: env.block.time.seconds(),
+        phase2_start: env.block.time.seconds() + 100,
+        phase2_end: env.block.time.seconds() + 100 + SECONDS_PER_HOUR,
+        phase2_slot_period: SECONDS_PER_HOUR,
+        claim_deadline: None,
+        sale_mode: SaleMode::ProRata {},
+        bonus_tiers: vec![],
+        lock_options: vec![LockOption {
+            duration: three_months,
+            multiplier: Decimal::percent(200),
+        }],
+        loyalty_bonus: None,
+        referral_portion: None,
+    };
+    do_post_initialize(deps.as_mut(), env.clone(), info, launch_config).unwrap();
+
+    // addr0001 weighs 2,000, all locked, addr0002 weighs 3,000, 2,000 of them locked
+    let info1 = mock_info("addr0001", &[Coin::new(1_000, "uusd")]);
+    do_deposit_with_lock(deps.as_mut(), env.clone(), info1, three_months).unwrap();
+    let info2 = mock_info("addr0002", &[Coin::new(1_000, "uusd")]);
+    do_deposit(deps.as_mut(), env.clone(), info2.clone()).unwrap();
+    do_deposit_with_lock(deps.as_mut(), env.clone(), info2, three_months).unwrap();
+
+    env.block.time = env.block.time.plus_seconds(100 + SECONDS_PER_HOUR);
+    let owner_info = mock_info("owner0001", &[]);
+    release_tokens(deps.as_mut(), env.clone(), owner_info).unwrap();
+
+    // the pass only pays the unlocked part of addr0002
+    let keeper_info = mock_info("keeper0000", &[]);
+    let res = do_distribute_tokens(deps.as_mut(), env.clone(), keeper_info.clone(), None).unwrap();
+    assert_eq!(
+        res.messages,
+        vec![SubMsg::new(CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: "prism0001".to_string(),
+            msg: to_binary(&Cw20ExecuteMsg::Transfer {
+                recipient: "addr0002".to_string(),
+                amount: Uint128::from(200_000u128),
+            })
+            .unwrap(),
+            funds: vec![],
+        }))]
+    );
+
+    let err =
+        do_distribute_tokens(deps.as_mut(), env.clone(), keeper_info.clone(), None).unwrap_err();
+    assert_eq!(
+        err,
+        ContractError::InvalidDistributeTokens {
+            reason: "the remaining positions are locked".to_string()
+        }
+    );
+
+    // once the locks end the skipped positions are revisited
+    env.block.time = env.block.time.plus_seconds(three_months);
+    for (addr, amount) in [("addr0001", 400_000u128), ("addr0002", 400_000)] {
+        let res =
+            do_distribute_tokens(deps.as_mut(), env.clone(), keeper_info.clone(), Some(1)).unwrap();
+        assert_eq!(
+            res.messages,
+            vec![SubMsg::new(CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr: "prism0001".to_string(),
+                msg: to_binary(&Cw20ExecuteMsg::Transfer {
+                    recipient: addr.to_string(),
+                    amount: Uint128::from(amount),
+                })
+                .unwrap(),
+                funds: vec![],
+            }))]
+        );
+    }
+
+    let err = do_distribute_tokens(deps.as_mut(), env, keeper_info, None).unwrap_err();
+    assert_eq!(
+        err,
+        ContractError::InvalidDistributeTokens {
+            reason: "all deposits were already processed".to_string()
+        }
+    );
+}
+
+#[test]
+fn proper_deposit_for_recipient() {
+    let mut deps = mock_dependencies(&[]);
+    init(&mut deps);
+    post_init(&mut deps);
+
+    let env = mock_env();
+
+    // the aggregator pays and the recipient is credited
+    let aggregator_info = mock_info("aggregator0000", &[Coin::new(1_000, "uusd")]);
+    let res = execute(
+        deps.as_mut(),
+        env.clone(),
+        aggregator_info,
+        ExecuteMsg::Deposit {
+            max_price: None,
+            referrer: None,
+            lock_duration: None,
+            recipient: Some("addr0001".to_string()),
+        },
+    )
+    .unwrap();
+    assert_eq!(
+        res.attributes,
+        vec![
+            attr("action", "deposit"),
+            attr("depositor", "addr0001"),
+            attr("sender", "aggregator0000"),
+        ]
+    );
+
+    let deposit_info =
+        do_query_deposit_info(deps.as_ref(), env.clone(), "addr0001".to_string()).unwrap();
+    assert_eq!(deposit_info.deposit, Uint128::from(1_000u128));
+
+    // withdraw rights belong to the recipient
+    let aggregator_info = mock_info("aggregator0000", &[]);
+    let err = do_withdraw(deps.as_mut(), env.clone(), aggregator_info, None).unwrap_err();
+    assert_eq!(
+        err,
+        ContractError::InvalidWithdraw {
+            reason: "no funds available to withdraw".to_string()
+        }
+    );
+
+    let info1 = mock_info("addr0001", &[]);
+    let res = do_withdraw(deps.as_mut(), env, info1, None).unwrap();
+    assert_eq!(
+        res.messages,
+        vec![SubMsg::new(CosmosMsg::Bank(BankMsg::Send {
+            to_address: "addr0001".to_string(),
+            amount: vec![Coin::new(1_000, "uusd")],
+        }))]
+    );
+}
+
+#[test]
+fn test_deposit_for_recipient_hijack() {
+    let mut deps = mock_dependencies(&[]);
+    init(&mut deps);
+
+    let info = mock_info("owner0001", &[]);
+    let env = mock_env();
+    let launch_config = LaunchConfig {
+        amount: Uint128::from(1_000_000u64),
+        phase1_start: env.block.time.seconds(),
+        phase2_start: env.block.time.seconds() + 100,
+        phase2_end: env.block.time.seconds() + 100 + SECONDS_PER_HOUR,
+        phase2_slot_period: SECONDS_PER_HOUR,
+        claim_deadline: None,
+        sale_mode: SaleMode::ProRata {},
+        bonus_tiers: vec![],
+        lock_options: vec![LockOption {
+            duration: SECONDS_PER_HOUR,
+            multiplier: Decimal::percent(150),
+        }],
+        loyalty_bonus: None,
+        referral_portion: Some(Decimal::percent(10)),
+    };
+    do_post_initialize(deps.as_mut(), env.clone(), info, launch_config).unwrap();
+
+    // a dust deposit can not lock the recipient's position or pick its referrer
+    let aggregator_info = mock_info("aggregator0000", &[Coin::new(1, "uusd")]);
+    let err = execute(
+        deps.as_mut(),
+        env.clone(),
+        aggregator_info.clone(),
+        ExecuteMsg::Deposit {
+            max_price: None,
+            referrer: None,
+            lock_duration: Some(SECONDS_PER_HOUR),
+            recipient: Some("addr0001".to_string()),
+        },
+    )
+    .unwrap_err();
+    assert_eq!(
+        err,
+        ContractError::InvalidDeposit {
+            reason: "can not lock a deposit for another address".to_string()
+        }
+    );
+
+    let err = execute(
+        deps.as_mut(),
+        env.clone(),
+        aggregator_info.clone(),
+        ExecuteMsg::Deposit {
+            max_price: None,
+            referrer: Some("aggregator0000".to_string()),
+            lock_duration: None,
+            recipient: Some("addr0001".to_string()),
+        },
+    )
+    .unwrap_err();
+    assert_eq!(
+        err,
+        ContractError::InvalidDeposit {
+            reason: "can not set the referrer of another address".to_string()
+        }
+    );
+
+    // the referrer chosen by the recipient can be passed along
+    let info1 = mock_info("addr0001", &[Coin::new(1_000, "uusd")]);
+    do_deposit_with_referrer(deps.as_mut(), env.clone(), info1, "referrer0000").unwrap();
+    execute(
+        deps.as_mut(),
+        env.clone(),
+        aggregator_info,
+        ExecuteMsg::Deposit {
+            max_price: None,
+            referrer: Some("referrer0000".to_string()),
+            lock_duration: None,
+            recipient: Some("addr0001".to_string()),
+        },
+    )
+    .unwrap();
+
+    // a bid for another address can only add to the open bid of the recipient
+    let mut deps = mock_dependencies(&[]);
+    init(&mut deps);
+    post_init_with_sale_mode(&mut deps, SaleMode::BatchAuction {});
+
+    let aggregator_info = mock_info("aggregator0000", &[Coin::new(1_000, "uusd")]);
+    let err = execute(
+        deps.as_mut(),
+        env.clone(),
+        aggregator_info.clone(),
+        ExecuteMsg::Deposit {
+            max_price: Some(Decimal::percent(1)),
+            referrer: None,
+            lock_duration: None,
+            recipient: Some("addr0001".to_string()),
+        },
+    )
+    .unwrap_err();
+    assert_eq!(
+        err,
+        ContractError::InvalidDeposit {
+            reason: "can not open a bid for another address".to_string()
+        }
+    );
+
+    let info1 = mock_info("addr0001", &[Coin::new(1_000, "uusd")]);
+    do_bid(deps.as_mut(), env.clone(), info1, Decimal::percent(2)).unwrap();
+    let err = execute(
+        deps.as_mut(),
+        env.clone(),
+        aggregator_info.clone(),
+        ExecuteMsg::Deposit {
+            max_price: Some(Decimal::percent(1)),
+            referrer: None,
+            lock_duration: None,
+            recipient: Some("addr0001".to_string()),
+        },
+    )
+    .unwrap_err();
+    assert_eq!(
+        err,
+        ContractError::InvalidDeposit {
+            reason: "max price of an open bid can not be changed".to_string()
+        }
+    );
+    execute(
+        deps.as_mut(),
+        env.clone(),
+        aggregator_info,
+        ExecuteMsg::Deposit {
+            max_price: Some(Decimal::percent(2)),
+            referrer: None,
+            lock_duration: None,
+            recipient: Some("addr0001".to_string()),
+        },
+    )
+    .unwrap();
+
+    let deposit_info = do_query_deposit_info(deps.as_ref(), env, "addr0001".to_string()).unwrap();
+    assert_eq!(deposit_info.deposit, Uint128::from(2_000u128));
+}
+
+#[test]
+fn proper_withdraw_to_recipient() {
+    let mut deps = mock_dependencies(&[]);
+    init(&mut deps);
+    post_init(&mut deps);
+
+    let mut env = mock_env();
+
+    let info1 = mock_info("addr0001", &[Coin::new(1_000, "uusd")]);
+    do_deposit(deps.as_mut(), env.clone(), info1).unwrap();
+
+    // the withdrawn uusd goes to the recipient
+    let info1 = mock_info("addr0001", &[]);
+    let res = execute(
+        deps.as_mut(),
+        env.clone(),
+        info1.clone(),
+        ExecuteMsg::Withdraw {
+            amount: Some(Uint128::from(400u128)),
+            recipient: Some("wallet0001".to_string()),
+            depositor: None,
+        },
+    )
+    .unwrap();
+    assert_eq!(
+        res.messages,
+        vec![SubMsg::new(CosmosMsg::Bank(BankMsg::Send {
+            to_address: "wallet0001".to_string(),
+            amount: vec![Coin::new(400, "uusd")],
+        }))]
+    );
+    assert_eq!(
+        res.attributes,
+        vec![
+            attr("action", "withdraw"),
+            attr("withdraw_amount", "400"),
+            attr("recipient", "wallet0001"),
+        ]
+    );
+
+    // the position is still held by the depositor
+    let deposit_info =
+        do_query_deposit_info(deps.as_ref(), env.clone(), "addr0001".to_string()).unwrap();
+    assert_eq!(deposit_info.deposit, Uint128::from(600u128));
+    let deposit_info =
+        do_query_deposit_info(deps.as_ref(), env.clone(), "wallet0001".to_string()).unwrap();
+    assert_eq!(deposit_info.deposit, Uint128::zero());
+
+    // fast forward past phase 2 and release the tokens
+    env.block.time = env.block.time.plus_seconds(100 + SECONDS_PER_HOUR);
+    let owner_info = mock_info("owner0001", &[]);
+    do_release_tokens(deps.as_mut(), env.clone(), owner_info).unwrap();
+
+    // the allocation goes to the recipient
+    let res = execute(
+        deps.as_mut(),
+        env.clone(),
+        info1.clone(),
+        ExecuteMsg::WithdrawTokens {
+            recipient: Some("wallet0001".to_string()),
+            depositor: None,
+            contract: None,
+            msg: None,
+            stake: false,
+        },
+    )
+    .unwrap();
+    assert_eq!(
+        res.messages,
+        vec![SubMsg::new(CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: "prism0001".to_string(),
+            msg: to_binary(&Cw20ExecuteMsg::Transfer {
+                recipient: "wallet0001".to_string(),
+                amount: Uint128::from(1_000_000u128),
+            })
+            .unwrap(),
+            funds: vec![],
+        }))]
+    );
+
+    // the claim is recorded on the depositor
+    let err = do_withdraw_tokens(deps.as_mut(), env, info1).unwrap_err();
+    assert_eq!(
+        err,
+        ContractError::InvalidWithdrawTokens {
+            reason: "tokens were already claimed".to_string()
+        }
+    );
+}
+
+pub fn do_transfer_position(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    recipient: &str,
+    amount: Option<Uint128>,
+) -> Result<Response, ContractError> {
+    execute(
+        deps,
+        env,
+        info,
+        ExecuteMsg::TransferPosition {
+            recipient: recipient.to_string(),
+            amount,
+            depositor: None,
+        },
+    )
+}
+
+#[test]
+fn proper_transfer_position() {
+    let mut deps = mock_dependencies(&[]);
+    init(&mut deps);
+
+    // 1M tokens, phase 1 lasts 100 seconds
+    post_init_with_sale_mode(&mut deps, SaleMode::TimeWeighted {});
+
+    let mut env = mock_env();
+
+    // 1,000 uusd with 100 seconds left and 1,000 uusd with 50 seconds left
+    let info1 = mock_info("addr0001", &[Coin::new(1_000, "uusd")]);
+    do_deposit(deps.as_mut(), env.clone(), info1.clone()).unwrap();
+    env.block.time = env.block.time.plus_seconds(50);
+    do_deposit(deps.as_mut(), env.clone(), info1).unwrap();
+
+    let info1 = mock_info("addr0001", &[]);
+    let err = do_transfer_position(deps.as_mut(), env.clone(), info1.clone(), "addr0001", None)
+        .unwrap_err();
+    assert_eq!(
+        err,
+        ContractError::InvalidTransferPosition {
+            reason: "can not transfer to yourself".to_string()
+        }
+    );
+
+    let err = do_transfer_position(
+        deps.as_mut(),
+        env.clone(),
+        info1.clone(),
+        "addr0002",
+        Some(Uint128::from(2_001u128)),
+    )
+    .unwrap_err();
+    assert_eq!(
+        err,
+        ContractError::InvalidTransferPosition {
+            reason: "can not transfer more than current transferable amount (2000)".to_string()
+        }
+    );
+
+    let info2 = mock_info("addr0002", &[]);
+    let err = do_transfer_position(deps.as_mut(), env.clone(), info2.clone(), "addr0001", None)
+        .unwrap_err();
+    assert_eq!(
+        err,
+        ContractError::InvalidTransferPosition {
+            reason: "no funds available to transfer".to_string()
+        }
+    );
+
+    // the last deposit moves first, then half of the first one
+    let res = do_transfer_position(
+        deps.as_mut(),
+        env.clone(),
+        info1.clone(),
+        "addr0002",
+        Some(Uint128::from(1_500u128)),
+    )
+    .unwrap();
+    assert_eq!(res.messages, vec![]);
+    assert_eq!(
+        res.attributes,
+        vec![
+            attr("action", "transfer_position"),
+            attr("recipient", "addr0002"),
+            attr("transfer_amount", "1500"),
+        ]
+    );
+
+    let deposit = DEPOSITS
+        .load(&deps.storage, &Addr::unchecked("addr0001"))
+        .unwrap();
+    assert_eq!(deposit.amount, Uint128::from(500u128));
+    assert_eq!(deposit.weight, Uint128::from(50_000u128));
+    let deposit = DEPOSITS
+        .load(&deps.storage, &Addr::unchecked("addr0002"))
+        .unwrap();
+    assert_eq!(deposit.amount, Uint128::from(1_500u128));
+    assert_eq!(deposit.weight, Uint128::from(100_000u128));
+    assert!(!deposit.withdrew_phase2);
+    assert!(!deposit.tokens_claimed);
+    assert_eq!(
+        TOTAL_WEIGHT.load(&deps.storage).unwrap(),
+        Uint128::from(150_000u128)
+    );
+
+    // the recipient withdraws the moved deposits in the same last in, first out order
+    do_withdraw(
+        deps.as_mut(),
+        env.clone(),
+        info2.clone(),
+        Some(Uint128::from(1_000u128)),
+    )
+    .unwrap();
+    let deposit = DEPOSITS
+        .load(&deps.storage, &Addr::unchecked("addr0002"))
+        .unwrap();
+    assert_eq!(deposit.amount, Uint128::from(500u128));
+    assert_eq!(deposit.weight, Uint128::from(50_000u128));
+    assert_eq!(
+        TOTAL_WEIGHT.load(&deps.storage).unwrap(),
+        Uint128::from(100_000u128)
+    );
+
+    // transfers are closed on phase 2
+    env.block.time = env.block.time.plus_seconds(50);
+    let err = do_transfer_position(deps.as_mut(), env, info1, "addr0002", None).unwrap_err();
+    assert_eq!(
+        err,
+        ContractError::InvalidTransferPosition {
+            reason: "positions can only be transferred during phase 1".to_string()
+        }
+    );
+}
+
+#[test]
+fn proper_receipt_tokens() {
+    let mut deps = mock_dependencies(&[]);
+
+    let msg = InstantiateMsg {
+        operator: "owner0001".to_string(),
+        receiver: "receiver0000".to_string(),
+        token: "prism0001".to_string(),
+        base_denom: "uusd".to_string(),
+        host_portion: Decimal::zero(),
+        host_portion_receiver: "host0000".to_string(),
+        unclaimed_tokens_receiver: "receiver0000".to_string(),
+        receipt_token: Some(ReceiptTokenInfo {
+            code_id: 7,
+            name: "Forge Receipt".to_string(),
+            symbol: "FRCPT".to_string(),
+        }),
+        position_nft: None,
+        staking_contract: None,
+    };
+    let owner_info = mock_info("owner0001", &[]);
+    let mut env = mock_env();
+    let res = instantiate(deps.as_mut(), env.clone(), owner_info.clone(), msg).unwrap();
+    assert_eq!(
+        res.messages,
+        vec![SubMsg::reply_on_success(
+            WasmMsg::Instantiate {
+                admin: None,
+                code_id: 7,
+                msg: to_binary(&ReceiptTokenInstantiateMsg {
+                    name: "Forge Receipt".to_string(),
+                    symbol: "FRCPT".to_string(),
+                    decimals: 6,
+                    initial_balances: vec![],
+                    mint: Some(MinterResponse {
+                        minter: MOCK_CONTRACT_ADDR.to_string(),
+                        cap: None,
+                    }),
+                })
+                .unwrap(),
+                funds: vec![],
+                label: "FRCPT receipt token".to_string(),
+            },
+            RECEIPT_TOKEN_REPLY_ID,
+        )]
+    );
+
+    let reply_msg = Reply {
+        id: RECEIPT_TOKEN_REPLY_ID,
+        result: ContractResult::Ok(SubMsgExecutionResponse {
+            events: vec![Event::new("instantiate_contract")
+                .add_attribute("code_id", "7")
+                .add_attribute("contract_address", "receipt0000")],
+            data: None,
+        }),
+    };
+    reply(deps.as_mut(), env.clone(), reply_msg).unwrap();
+
+    let config: ConfigResponse =
+        from_binary(&query(deps.as_ref(), env.clone(), QueryMsg::Config {}).unwrap()).unwrap();
+    assert_eq!(config.receipt_token, Some("receipt0000".to_string()));
+
+    // receipts only back plain pro-rata sales
+    let mut launch_config = LaunchConfig {
+        amount: Uint128::from(1_000_000u64),
+        phase1_start: env.block.time.seconds(),
+        phase2_start: env.block.time.seconds() + 100,
+        phase2_end: env.block.time.seconds() + 100 + SECONDS_PER_HOUR,
+        phase2_slot_period: SECONDS_PER_HOUR,
+        claim_deadline: None,
+        sale_mode: SaleMode::TimeWeighted {},
+        bonus_tiers: vec![],
+        lock_options: vec![],
+        loyalty_bonus: None,
+        referral_portion: None,
+    };
+    let err = do_post_initialize(
+        deps.as_mut(),
+        env.clone(),
+        owner_info.clone(),
+        launch_config.clone(),
+    )
+    .unwrap_err();
+    assert_eq!(err, ContractError::InvalidLaunchConfig {});
+
+    launch_config.sale_mode = SaleMode::ProRata {};
+    do_post_initialize(
+        deps.as_mut(),
+        env.clone(),
+        owner_info.clone(),
+        launch_config,
+    )
+    .unwrap();
+
+    // receipts are minted 1:1 to the depositor
+    let info1 = mock_info("addr0001", &[Coin::new(1_000, "uusd")]);
+    let res = do_deposit(deps.as_mut(), env.clone(), info1).unwrap();
+    assert_eq!(
+        res.messages,
+        vec![SubMsg::new(CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: "receipt0000".to_string(),
+            msg: to_binary(&Cw20ExecuteMsg::Mint {
+                recipient: "addr0001".to_string(),
+                amount: Uint128::from(1_000u128),
+            })
+            .unwrap(),
+            funds: vec![],
+        }))]
+    );
+    let info2 = mock_info("addr0002", &[Coin::new(3_000, "uusd")]);
+    do_deposit(deps.as_mut(), env.clone(), info2).unwrap();
+
+    // withdrawals go through the receipts, not the deposit of the sender
+    let info1 = mock_info("addr0001", &[]);
+    let err = do_withdraw(
+        deps.as_mut(),
+        env.clone(),
+        info1.clone(),
+        Some(Uint128::from(500u128)),
+    )
+    .unwrap_err();
+    assert_eq!(
+        err,
+        ContractError::InvalidWithdraw {
+            reason: "deposits are withdrawn by sending the receipt tokens".to_string()
+        }
+    );
+
+    // the holder of transferred receipts withdraws the deposit they were minted for
+    let withdraw_msg = ExecuteMsg::Receive(Cw20ReceiveMsg {
+        sender: "holder0000".to_string(),
+        amount: Uint128::from(500u128),
+        msg: to_binary(&Cw20HookMsg::Withdraw {}).unwrap(),
+    });
+    let receipt_info = mock_info("receipt0000", &[]);
+    let res = execute(
+        deps.as_mut(),
+        env.clone(),
+        receipt_info.clone(),
+        withdraw_msg.clone(),
+    )
+    .unwrap();
+    assert_eq!(
+        res.messages,
+        vec![
+            SubMsg::new(CosmosMsg::Bank(BankMsg::Send {
+                to_address: "holder0000".to_string(),
+                amount: vec![Coin::new(500, "uusd")],
+            })),
+            SubMsg::new(CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr: "receipt0000".to_string(),
+                msg: to_binary(&Cw20ExecuteMsg::Burn {
+                    amount: Uint128::from(500u128),
+                })
+                .unwrap(),
+                funds: vec![],
+            })),
+        ]
+    );
+
+    // the single phase 2 withdraw of a position can not follow the receipts around
+    let mut phase2_env = env.clone();
+    phase2_env.block.time = phase2_env.block.time.plus_seconds(101);
+    let err = execute(
+        deps.as_mut(),
+        phase2_env,
+        receipt_info.clone(),
+        withdraw_msg,
+    )
+    .unwrap_err();
+    assert_eq!(
+        err,
+        ContractError::InvalidWithdraw {
+            reason: "receipts can only be withdrawn on phase 1".to_string()
+        }
+    );
+
+    let redeem_msg = ExecuteMsg::Receive(Cw20ReceiveMsg {
+        sender: "holder0000".to_string(),
+        amount: Uint128::from(1_500u128),
+        msg: to_binary(&Cw20HookMsg::Redeem {}).unwrap(),
+    });
+
+    // only receipts can be redeemed
+    let err = execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("prism0001", &[]),
+        redeem_msg.clone(),
+    )
+    .unwrap_err();
+    assert_eq!(err, ContractError::Unauthorized {});
+
+    let err = execute(
+        deps.as_mut(),
+        env.clone(),
+        receipt_info.clone(),
+        redeem_msg.clone(),
+    )
+    .unwrap_err();
+    assert_eq!(
+        err,
+        ContractError::InvalidRedeem {
+            reason: "cannot redeem receipts yet".to_string()
+        }
+    );
+
+    // fast forward past phase 2 and release the tokens
+    env.block.time = env.block.time.plus_seconds(100 + SECONDS_PER_HOUR);
+    release_tokens(deps.as_mut(), env.clone(), owner_info).unwrap();
+
+    let err = do_withdraw_tokens(deps.as_mut(), env.clone(), info1).unwrap_err();
+    assert_eq!(
+        err,
+        ContractError::InvalidWithdrawTokens {
+            reason: "tokens are claimed by redeeming the receipt tokens".to_string()
+        }
+    );
+
+    // the holder of the receipts gets their share of the 3,500 uusd deposited
+    let res = execute(deps.as_mut(), env.clone(), receipt_info, redeem_msg).unwrap();
+    assert_eq!(
+        res.messages,
+        vec![
+            SubMsg::new(CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr: "receipt0000".to_string(),
+                msg: to_binary(&Cw20ExecuteMsg::Burn {
+                    amount: Uint128::from(1_500u128),
+                })
+                .unwrap(),
+                funds: vec![],
+            })),
+            SubMsg::new(CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr: "prism0001".to_string(),
+                msg: to_binary(&Cw20ExecuteMsg::Transfer {
+                    recipient: "holder0000".to_string(),
+                    amount: Uint128::from(428_571u128),
+                })
+                .unwrap(),
+                funds: vec![],
+            })),
+        ]
+    );
+
+    let accounting: TokenAccountingResponse =
+        from_binary(&query(deps.as_ref(), env, QueryMsg::TokenAccounting {}).unwrap()).unwrap();
+    assert_eq!(accounting.claimed, Uint128::from(428_571u128));
+}
+
+#[test]
+fn proper_position_nfts() {
+    let mut deps = mock_dependencies(&[]);
+
+    let msg = InstantiateMsg {
+        operator: "owner0001".to_string(),
+        receiver: "receiver0000".to_string(),
+        token: "prism0001".to_string(),
+        base_denom: "uusd".to_string(),
+        host_portion: Decimal::zero(),
+        host_portion_receiver: "host0000".to_string(),
+        unclaimed_tokens_receiver: "receiver0000".to_string(),
+        receipt_token: None,
+        position_nft: Some(PositionNftInfo {
+            code_id: 8,
+            name: "Forge Position".to_string(),
+            symbol: "FPOS".to_string(),
+        }),
+        staking_contract: None,
+    };
+    let owner_info = mock_info("owner0001", &[]);
+    let mut env = mock_env();
+    let res = instantiate(deps.as_mut(), env.clone(), owner_info.clone(), msg).unwrap();
+    assert_eq!(
+        res.messages,
+        vec![SubMsg::reply_on_success(
+            WasmMsg::Instantiate {
+                admin: None,
+                code_id: 8,
+                msg: to_binary(&PositionNftInstantiateMsg {
+                    name: "Forge Position".to_string(),
+                    symbol: "FPOS".to_string(),
+                    minter: MOCK_CONTRACT_ADDR.to_string(),
+                })
+                .unwrap(),
+                funds: vec![],
+                label: "FPOS position nft".to_string(),
+            },
+            POSITION_NFT_REPLY_ID,
+        )]
+    );
+
+    let reply_msg = Reply {
+        id: POSITION_NFT_REPLY_ID,
+        result: ContractResult::Ok(SubMsgExecutionResponse {
+            events: vec![Event::new("instantiate_contract")
+                .add_attribute("code_id", "8")
+                .add_attribute("contract_address", "nft0000")],
+            data: None,
+        }),
+    };
+    reply(deps.as_mut(), env.clone(), reply_msg).unwrap();
+    post_init(&mut deps);
+
+    // every deposit mints a position nft
+    let info1 = mock_info("addr0001", &[Coin::new(1_000, "uusd")]);
+    let res = do_deposit(deps.as_mut(), env.clone(), info1).unwrap();
+    assert_eq!(
+        res.messages,
+        vec![SubMsg::new(CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: "nft0000".to_string(),
+            msg: to_binary(&PositionNftExecuteMsg::Mint(MintMsg {
+                token_id: "1".to_string(),
+                owner: "addr0001".to_string(),
+                name: "Position #1".to_string(),
+                description: Some(format!(
+                    "1000uusd deposited at {}",
+                    env.block.time.seconds()
+                )),
+                image: None,
+            }))
+            .unwrap(),
+            funds: vec![],
+        }))]
+    );
+    let info2 = mock_info("addr0002", &[Coin::new(3_000, "uusd")]);
+    let res = do_deposit(deps.as_mut(), env.clone(), info2).unwrap();
+    assert_eq!(res.attributes[3], attr("token_id", "2"));
+
+    // the first position is sold, its new owner controls it
+    deps.querier
+        .with_nft_owners(&[("nft0000", &[("1", "buyer0000"), ("2", "addr0002")])]);
+
+    let withdraw_msg = ExecuteMsg::WithdrawPosition {
+        token_id: "1".to_string(),
+        amount: Some(Uint128::from(500u128)),
+        recipient: None,
+    };
+    let err = execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("addr0001", &[]),
+        withdraw_msg.clone(),
+    )
+    .unwrap_err();
+    assert_eq!(err, ContractError::Unauthorized {});
+
+    let buyer_info = mock_info("buyer0000", &[]);
+    let res = execute(deps.as_mut(), env.clone(), buyer_info.clone(), withdraw_msg).unwrap();
+    assert_eq!(
+        res.messages,
+        vec![SubMsg::new(CosmosMsg::Bank(BankMsg::Send {
+            to_address: "buyer0000".to_string(),
+            amount: vec![Coin::new(500, "uusd")],
+        }))]
+    );
+
+    let position: PositionResponse = from_binary(
+        &query(
+            deps.as_ref(),
+            env.clone(),
+            QueryMsg::Position {
+                token_id: "1".to_string(),
+            },
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(
+        position,
+        PositionResponse {
+            owner: "buyer0000".to_string(),
+            deposit: Uint128::from(500u128),
+            deposit_time: env.block.time.seconds(),
+            withdrew_phase2: false,
+            withdrawable_amount: Uint128::from(500u128),
+            tokens_to_claim: Uint128::from(142_857u128),
+            refund_to_claim: Uint128::zero(),
+            can_claim: false,
+        }
+    );
+
+    // fast forward past phase 2 and release the tokens
+    env.block.time = env.block.time.plus_seconds(100 + SECONDS_PER_HOUR);
+    release_tokens(deps.as_mut(), env.clone(), owner_info).unwrap();
+
+    // positions are not distributed, the owner of the NFT claims them
+    let err =
+        do_distribute_tokens(deps.as_mut(), env.clone(), buyer_info.clone(), None).unwrap_err();
+    assert_eq!(
+        err,
+        ContractError::InvalidDistributeTokens {
+            reason: "tokens are claimed with the position NFTs".to_string()
+        }
+    );
+
+    let claim_msg = ExecuteMsg::ClaimPosition {
+        token_id: "1".to_string(),
+        recipient: None,
+    };
+    let res = execute(
+        deps.as_mut(),
+        env.clone(),
+        buyer_info.clone(),
+        claim_msg.clone(),
+    )
+    .unwrap();
+    assert_eq!(
+        res.messages,
+        vec![SubMsg::new(CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: "prism0001".to_string(),
+            msg: to_binary(&Cw20ExecuteMsg::Transfer {
+                recipient: "buyer0000".to_string(),
+                amount: Uint128::from(142_857u128),
+            })
+            .unwrap(),
+            funds: vec![],
+        }))]
+    );
+
+    let err = execute(deps.as_mut(), env, buyer_info, claim_msg).unwrap_err();
+    assert_eq!(
+        err,
+        ContractError::InvalidWithdrawTokens {
+            reason: "tokens were already claimed".to_string()
+        }
+    );
+}
+
+#[test]
+fn proper_delegates() {
+    let mut deps = mock_dependencies(&[]);
+    init(&mut deps);
+    post_init(&mut deps);
+
+    let mut env = mock_env();
+
+    let info1 = mock_info("addr0001", &[Coin::new(1_000, "uusd")]);
+    do_deposit(deps.as_mut(), env.clone(), info1).unwrap();
+
+    let info1 = mock_info("addr0001", &[]);
+    let authorize_msg =
+        |delegate: &str, permissions: Vec<Permission>, expires| ExecuteMsg::Authorize {
+            delegate: delegate.to_string(),
+            permissions,
+            expires,
+            transfer_recipients: vec![],
+        };
+
+    let err = execute(
+        deps.as_mut(),
+        env.clone(),
+        info1.clone(),
+        authorize_msg("addr0001", vec![Permission::Withdraw], None),
+    )
+    .unwrap_err();
+    assert_eq!(
+        err,
+        ContractError::InvalidAuthorize {
+            reason: "can not delegate to yourself".to_string()
+        }
+    );
+
+    let err = execute(
+        deps.as_mut(),
+        env.clone(),
+        info1.clone(),
+        authorize_msg("custodian0000", vec![], None),
+    )
+    .unwrap_err();
+    assert_eq!(
+        err,
+        ContractError::InvalidAuthorize {
+            reason: "at least one permission is required".to_string()
+        }
+    );
+
+    let err = execute(
+        deps.as_mut(),
+        env.clone(),
+        info1.clone(),
+        authorize_msg(
+            "custodian0000",
+            vec![Permission::Withdraw],
+            Some(Expiration::AtHeight(env.block.height)),
+        ),
+    )
+    .unwrap_err();
+    assert_eq!(
+        err,
+        ContractError::InvalidAuthorize {
+            reason: "delegation is already expired".to_string()
+        }
+    );
+
+    execute(
+        deps.as_mut(),
+        env.clone(),
+        info1.clone(),
+        authorize_msg(
+            "custodian0000",
+            vec![Permission::Withdraw, Permission::Claim],
+            None,
+        ),
+    )
+    .unwrap();
+
+    let delegates: DelegatesResponse = from_binary(
+        &query(
+            deps.as_ref(),
+            env.clone(),
+            QueryMsg::Delegates {
+                address: "addr0001".to_string(),
+                start_after: None,
+                limit: None,
+            },
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(
+        delegates,
+        DelegatesResponse {
+            delegates: vec![DelegateResponse {
+                delegate: "custodian0000".to_string(),
+                permissions: vec![Permission::Withdraw, Permission::Claim],
+                expires: Expiration::Never {},
+                transfer_recipients: vec![],
+            }]
+        }
+    );
+
+    // the delegate withdraws from the position of the depositor and the depositor is paid
+    let custodian_info = mock_info("custodian0000", &[]);
+    let res = execute(
+        deps.as_mut(),
+        env.clone(),
+        custodian_info.clone(),
+        ExecuteMsg::Withdraw {
+            amount: Some(Uint128::from(400u128)),
+            recipient: None,
+            depositor: Some("addr0001".to_string()),
+        },
+    )
+    .unwrap();
+    assert_eq!(
+        res.messages,
+        vec![SubMsg::new(CosmosMsg::Bank(BankMsg::Send {
+            to_address: "addr0001".to_string(),
+            amount: vec![Coin::new(400, "uusd")],
+        }))]
+    );
+
+    // delegates can not choose the recipient or act beyond their permissions
+    let err = execute(
+        deps.as_mut(),
+        env.clone(),
+        custodian_info.clone(),
+        ExecuteMsg::Withdraw {
+            amount: None,
+            recipient: Some("custodian0000".to_string()),
+            depositor: Some("addr0001".to_string()),
+        },
+    )
+    .unwrap_err();
+    assert_eq!(err, ContractError::Unauthorized {});
+
+    let err = execute(
+        deps.as_mut(),
+        env.clone(),
+        custodian_info.clone(),
+        ExecuteMsg::TransferPosition {
+            recipient: "custodian0000".to_string(),
+            amount: None,
+            depositor: Some("addr0001".to_string()),
+        },
+    )
+    .unwrap_err();
+    assert_eq!(err, ContractError::Unauthorized {});
+
+    let err = execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("addr0002", &[]),
+        ExecuteMsg::Withdraw {
+            amount: None,
+            recipient: None,
+            depositor: Some("addr0001".to_string()),
+        },
+    )
+    .unwrap_err();
+    assert_eq!(err, ContractError::Unauthorized {});
+
+    // fast forward past phase 2 and release the tokens
+    env.block.time = env.block.time.plus_seconds(100 + SECONDS_PER_HOUR);
+    let owner_info = mock_info("owner0001", &[]);
+    release_tokens(deps.as_mut(), env.clone(), owner_info).unwrap();
+
+    let res = execute(
+        deps.as_mut(),
+        env.clone(),
+        custodian_info,
+        ExecuteMsg::WithdrawTokens {
+            recipient: None,
+            depositor: Some("addr0001".to_string()),
+            contract: None,
+            msg: None,
+            stake: false,
+        },
+    )
+    .unwrap();
+    assert_eq!(
+        res.messages,
+        vec![SubMsg::new(CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: "prism0001".to_string(),
+            msg: to_binary(&Cw20ExecuteMsg::Transfer {
+                recipient: "addr0001".to_string(),
+                amount: Uint128::from(1_000_000u128),
+            })
+            .unwrap(),
+            funds: vec![],
+        }))]
+    );
+
+    let revoke_msg = ExecuteMsg::Revoke {
+        delegate: "custodian0000".to_string(),
+    };
+    execute(
+        deps.as_mut(),
+        env.clone(),
+        info1.clone(),
+        revoke_msg.clone(),
+    )
+    .unwrap();
+    let err = execute(deps.as_mut(), env.clone(), info1, revoke_msg).unwrap_err();
+    assert_eq!(
+        err,
+        ContractError::InvalidRevoke {
+            reason: "delegate not found".to_string()
+        }
+    );
+
+    let delegates: DelegatesResponse = from_binary(
+        &query(
+            deps.as_ref(),
+            env,
+            QueryMsg::Delegates {
+                address: "addr0001".to_string(),
+                start_after: None,
+                limit: None,
+            },
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(delegates.delegates, vec![]);
+}
+
+#[test]
+fn test_delegated_transfer_recipients() {
+    let mut deps = mock_dependencies(&[]);
+    init(&mut deps);
+    post_init(&mut deps);
+
+    let env = mock_env();
+
+    let info1 = mock_info("addr0001", &[Coin::new(1_000, "uusd")]);
+    do_deposit(deps.as_mut(), env.clone(), info1).unwrap();
+
+    let info1 = mock_info("addr0001", &[]);
+    let authorize_msg =
+        |permissions: Vec<Permission>, transfer_recipients: Vec<&str>| ExecuteMsg::Authorize {
+            delegate: "custodian0000".to_string(),
+            permissions,
+            expires: None,
+            transfer_recipients: transfer_recipients
+                .into_iter()
+                .map(|recipient| recipient.to_string())
+                .collect(),
+        };
+
+    // the transfer permission always comes with the recipients it can transfer to
+    for msg in [
+        authorize_msg(vec![Permission::Transfer], vec![]),
+        authorize_msg(vec![Permission::Withdraw], vec!["addr0002"]),
+    ] {
+        let err = execute(deps.as_mut(), env.clone(), info1.clone(), msg).unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::InvalidAuthorize {
+                reason: "transfer recipients must be set with the transfer permission only"
+                    .to_string()
+            }
+        );
+    }
+
+    let err = execute(
+        deps.as_mut(),
+        env.clone(),
+        info1.clone(),
+        authorize_msg(vec![Permission::Transfer], vec!["custodian0000"]),
+    )
+    .unwrap_err();
+    assert_eq!(
+        err,
+        ContractError::InvalidAuthorize {
+            reason: "transfer recipients can not be the delegate or the depositor".to_string()
+        }
+    );
+
+    execute(
+        deps.as_mut(),
+        env.clone(),
+        info1,
+        authorize_msg(vec![Permission::Transfer], vec!["addr0002"]),
+    )
+    .unwrap();
+
+    let delegates: DelegatesResponse = from_binary(
+        &query(
+            deps.as_ref(),
+            env.clone(),
+            QueryMsg::Delegates {
+                address: "addr0001".to_string(),
+                start_after: None,
+                limit: None,
+            },
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(
+        delegates.delegates[0].transfer_recipients,
+        vec!["addr0002".to_string()]
+    );
+
+    // the delegate can not take the position for itself or anyone else
+    let custodian_info = mock_info("custodian0000", &[]);
+    for recipient in ["custodian0000", "addr0003"] {
+        let err = execute(
+            deps.as_mut(),
+            env.clone(),
+            custodian_info.clone(),
+            ExecuteMsg::TransferPosition {
+                recipient: recipient.to_string(),
+                amount: None,
+                depositor: Some("addr0001".to_string()),
+            },
+        )
+        .unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::InvalidTransferPosition {
+                reason: "recipient is not allowed by the delegation".to_string()
+            }
+        );
+    }
+
+    execute(
+        deps.as_mut(),
+        env.clone(),
+        custodian_info,
+        ExecuteMsg::TransferPosition {
+            recipient: "addr0002".to_string(),
+            amount: None,
+            depositor: Some("addr0001".to_string()),
+        },
+    )
+    .unwrap();
+
+    let deposit_info = do_query_deposit_info(deps.as_ref(), env, "addr0002".to_string()).unwrap();
+    assert_eq!(deposit_info.deposit, Uint128::from(1_000u128));
+}
+
+#[test]
+fn proper_claim_for() {
+    let mut deps = mock_dependencies(&[]);
+    init(&mut deps);
+    post_init(&mut deps);
+
+    let mut env = mock_env();
+
+    let info1 = mock_info("addr0001", &[Coin::new(1_000, "uusd")]);
+    do_deposit(deps.as_mut(), env.clone(), info1).unwrap();
+    let info2 = mock_info("addr0002", &[Coin::new(1_000, "uusd")]);
+    do_deposit(deps.as_mut(), env.clone(), info2).unwrap();
+
+    let info1 = mock_info("addr0001", &[]);
+    let err = execute(
+        deps.as_mut(),
+        env.clone(),
+        info1.clone(),
+        ExecuteMsg::SetClaimTip {
+            tip: Decimal::percent(6),
+        },
+    )
+    .unwrap_err();
+    assert_eq!(
+        err,
+        ContractError::InvalidClaimTip {
+            reason: "tip can not be bigger than 5%".to_string()
+        }
+    );
+
+    let err = execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("addr0003", &[]),
+        ExecuteMsg::SetClaimTip {
+            tip: Decimal::percent(1),
+        },
+    )
+    .unwrap_err();
+    assert_eq!(
+        err,
+        ContractError::InvalidClaimTip {
+            reason: "deposit information not found".to_string()
+        }
+    );
+
+    execute(
+        deps.as_mut(),
+        env.clone(),
+        info1,
+        ExecuteMsg::SetClaimTip {
+            tip: Decimal::percent(1),
+        },
+    )
+    .unwrap();
+
+    let keeper_info = mock_info("keeper0000", &[]);
+    let claim_for_msg = |address: &str| ExecuteMsg::ClaimFor {
+        address: address.to_string(),
+    };
+
+    let err = execute(
+        deps.as_mut(),
+        env.clone(),
+        keeper_info.clone(),
+        claim_for_msg("addr0001"),
+    )
+    .unwrap_err();
+    assert_eq!(
+        err,
+        ContractError::InvalidWithdrawTokens {
+            reason: "cannot withdraw tokens yet".to_string()
+        }
+    );
+
+    // fast forward past phase 2 and release the tokens
+    env.block.time = env.block.time.plus_seconds(100 + SECONDS_PER_HOUR);
+    let owner_info = mock_info("owner0001", &[]);
+    release_tokens(deps.as_mut(), env.clone(), owner_info).unwrap();
+
+    // the keeper gets the tip and the depositor the rest of the tokens
+    let res = execute(
+        deps.as_mut(),
+        env.clone(),
+        keeper_info.clone(),
+        claim_for_msg("addr0001"),
+    )
+    .unwrap();
+    assert_eq!(
+        res.messages,
+        vec![
+            SubMsg::new(CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr: "prism0001".to_string(),
+                msg: to_binary(&Cw20ExecuteMsg::Transfer {
+                    recipient: "addr0001".to_string(),
+                    amount: Uint128::from(495_000u128),
+                })
+                .unwrap(),
+                funds: vec![],
+            })),
+            SubMsg::new(CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr: "prism0001".to_string(),
+                msg: to_binary(&Cw20ExecuteMsg::Transfer {
+                    recipient: "keeper0000".to_string(),
+                    amount: Uint128::from(5_000u128),
+                })
+                .unwrap(),
+                funds: vec![],
+            })),
+        ]
+    );
+    assert_eq!(
+        res.attributes,
+        vec![
+            attr("action", "claim_for"),
+            attr("depositor", "addr0001"),
+            attr("withdraw_amount", "495000"),
+            attr("refund_amount", "0"),
+            attr("tip_amount", "5000"),
+        ]
+    );
+
+    let err = execute(
+        deps.as_mut(),
+        env.clone(),
+        keeper_info.clone(),
+        claim_for_msg("addr0001"),
+    )
+    .unwrap_err();
+    assert_eq!(
+        err,
+        ContractError::InvalidWithdrawTokens {
+            reason: "tokens were already claimed".to_string()
+        }
+    );
+
+    // no tip is paid when the depositor did not set one
+    let res = execute(
+        deps.as_mut(),
+        env.clone(),
+        keeper_info,
+        claim_for_msg("addr0002"),
+    )
+    .unwrap();
+    assert_eq!(
+        res.messages,
+        vec![SubMsg::new(CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: "prism0001".to_string(),
+            msg: to_binary(&Cw20ExecuteMsg::Transfer {
+                recipient: "addr0002".to_string(),
+                amount: Uint128::from(500_000u128),
+            })
+            .unwrap(),
+            funds: vec![],
+        }))]
+    );
+
+    let deposit_info = do_query_deposit_info(deps.as_ref(), env, "addr0002".to_string()).unwrap();
+    assert!(!deposit_info.can_claim);
+}
+
+#[test]
+fn proper_withdraw_and_claim() {
+    let mut deps = mock_dependencies(&[]);
+    init(&mut deps);
+
+    // 100,000 tokens go to the referrers and 900,000 tokens to the depositors
+    let info = mock_info("owner0001", &[]);
+    let mut env = mock_env();
+    let launch_config = LaunchConfig {
+        amount: Uint128::from(1_000_000u64),
+        phase1_start: env.block.time.seconds(),
+        phase2_start: env.block.time.seconds() + 100,
+        phase2_end: env.block.time.seconds() + 100 + SECONDS_PER_HOUR,
+        phase2_slot_period: SECONDS_PER_HOUR,
+        claim_deadline: None,
+        sale_mode: SaleMode::ProRata {},
+        bonus_tiers: vec![],
+        lock_options: vec![],
+        loyalty_bonus: None,
+        referral_portion: Some(Decimal::percent(10)),
+    };
+    do_post_initialize(deps.as_mut(), env.clone(), info, launch_config).unwrap();
+
+    let info1 = mock_info("addr0001", &[Coin::new(1_000, "uusd")]);
+    do_deposit_with_referrer(deps.as_mut(), env.clone(), info1, "ref0001").unwrap();
+    let info2 = mock_info("addr0002", &[Coin::new(3_000, "uusd")]);
+    do_deposit_with_referrer(deps.as_mut(), env.clone(), info2, "addr0001").unwrap();
+
+    let info1 = mock_info("addr0001", &[]);
+    let err = execute(
+        deps.as_mut(),
+        env.clone(),
+        info1.clone(),
+        ExecuteMsg::WithdrawAndClaim {},
+    )
+    .unwrap_err();
+    assert_eq!(
+        err,
+        ContractError::InvalidWithdrawTokens {
+            reason: "cannot withdraw tokens yet".to_string()
+        }
+    );
+
+    env.block.time = env.block.time.plus_seconds(100 + SECONDS_PER_HOUR);
+    let owner_info = mock_info("owner0001", &[]);
+    release_tokens(deps.as_mut(), env.clone(), owner_info).unwrap();
+
+    let deposit_info =
+        do_query_deposit_info(deps.as_ref(), env.clone(), "addr0001".to_string()).unwrap();
+    assert_eq!(deposit_info.tokens_to_claim, Uint128::from(225_000u128));
+    assert_eq!(
+        deposit_info.referral_rewards_to_claim,
+        Uint128::from(75_000u128)
+    );
+    assert!(!deposit_info.settled);
+
+    // the tokens of the deposit and the referral rewards are paid together
+    let res = execute(
+        deps.as_mut(),
+        env.clone(),
+        info1.clone(),
+        ExecuteMsg::WithdrawAndClaim {},
+    )
+    .unwrap();
+    assert_eq!(
+        res.messages,
+        vec![
+            SubMsg::new(CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr: "prism0001".to_string(),
+                msg: to_binary(&Cw20ExecuteMsg::Transfer {
+                    recipient: "addr0001".to_string(),
+                    amount: Uint128::from(225_000u128),
+                })
+                .unwrap(),
+                funds: vec![],
+            })),
+            SubMsg::new(CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr: "prism0001".to_string(),
+                msg: to_binary(&Cw20ExecuteMsg::Transfer {
+                    recipient: "addr0001".to_string(),
+                    amount: Uint128::from(75_000u128),
+                })
+                .unwrap(),
+                funds: vec![],
+            })),
+        ]
+    );
+    assert_eq!(
+        res.attributes,
+        vec![
+            attr("action", "withdraw_and_claim"),
+            attr("withdraw_amount", "225000"),
+            attr("rewards_amount", "75000"),
+            attr("refund_amount", "0"),
+        ]
+    );
+
+    let deposit_info =
+        do_query_deposit_info(deps.as_ref(), env.clone(), "addr0001".to_string()).unwrap();
+    assert_eq!(deposit_info.referral_rewards_to_claim, Uint128::zero());
+    assert!(deposit_info.settled);
+
+    let err = execute(
+        deps.as_mut(),
+        env.clone(),
+        info1,
+        ExecuteMsg::WithdrawAndClaim {},
+    )
+    .unwrap_err();
+    assert_eq!(
+        err,
+        ContractError::InvalidWithdrawAndClaim {
+            reason: "nothing left to settle".to_string()
+        }
+    );
+
+    // referrers without a deposit only get their rewards
+    let res = execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("ref0001", &[]),
+        ExecuteMsg::WithdrawAndClaim {},
+    )
+    .unwrap();
+    assert_eq!(
+        res.messages,
+        vec![SubMsg::new(CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: "prism0001".to_string(),
+            msg: to_binary(&Cw20ExecuteMsg::Transfer {
+                recipient: "ref0001".to_string(),
+                amount: Uint128::from(25_000u128),
+            })
+            .unwrap(),
+            funds: vec![],
+        }))]
+    );
+
+    let accounting: TokenAccountingResponse =
+        from_binary(&query(deps.as_ref(), env, QueryMsg::TokenAccounting {}).unwrap()).unwrap();
+    assert_eq!(accounting.claimed, Uint128::from(325_000u128));
+}
+
+#[test]
+fn proper_claim_targets() {
+    let mut deps = mock_dependencies(&[]);
+    init(&mut deps);
+    post_init(&mut deps);
+
+    let mut env = mock_env();
+
+    let info1 = mock_info("addr0001", &[Coin::new(1_000, "uusd")]);
+    do_deposit(deps.as_mut(), env.clone(), info1).unwrap();
+
+    let update_msg = ExecuteMsg::UpdateClaimTargets {
+        add: vec!["staking0001".to_string(), "pair0001".to_string()],
+        remove: vec![],
+    };
+    let err = execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("addr0001", &[]),
+        update_msg.clone(),
+    )
+    .unwrap_err();
+    assert_eq!(err, ContractError::Unauthorized {});
+
+    let owner_info = mock_info("owner0001", &[]);
+    execute(deps.as_mut(), env.clone(), owner_info.clone(), update_msg).unwrap();
+    execute(
+        deps.as_mut(),
+        env.clone(),
+        owner_info.clone(),
+        ExecuteMsg::UpdateClaimTargets {
+            add: vec![],
+            remove: vec!["pair0001".to_string()],
+        },
+    )
+    .unwrap();
+
+    let targets: ClaimTargetsResponse = from_binary(
+        &query(
+            deps.as_ref(),
+            env.clone(),
+            QueryMsg::ClaimTargets {
+                start_after: None,
+                limit: None,
+            },
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(targets.targets, vec!["staking0001".to_string()]);
+
+    // fast forward past phase 2 and release the tokens
+    env.block.time = env.block.time.plus_seconds(100 + SECONDS_PER_HOUR);
+    release_tokens(deps.as_mut(), env.clone(), owner_info).unwrap();
+
+    let hook_msg = to_binary("bond").unwrap();
+    let withdraw_tokens_msg =
+        |contract: Option<&str>, msg: Option<Binary>| ExecuteMsg::WithdrawTokens {
+            recipient: None,
+            depositor: None,
+            contract: contract.map(|contract| contract.to_string()),
+            msg,
+            stake: false,
+        };
+    let info1 = mock_info("addr0001", &[]);
+
+    let err = execute(
+        deps.as_mut(),
+        env.clone(),
+        info1.clone(),
+        withdraw_tokens_msg(Some("staking0001"), None),
+    )
+    .unwrap_err();
+    assert_eq!(
+        err,
+        ContractError::InvalidWithdrawTokens {
+            reason: "contract and msg must be set together".to_string()
+        }
+    );
+
+    let err = execute(
+        deps.as_mut(),
+        env.clone(),
+        info1.clone(),
+        withdraw_tokens_msg(Some("pair0001"), Some(hook_msg.clone())),
+    )
+    .unwrap_err();
+    assert_eq!(
+        err,
+        ContractError::InvalidWithdrawTokens {
+            reason: "contract is not an allowed claim target".to_string()
+        }
+    );
+
+    // the tokens are sent to the target with the hook instead of transferred
+    let res = execute(
+        deps.as_mut(),
+        env,
+        info1,
+        withdraw_tokens_msg(Some("staking0001"), Some(hook_msg.clone())),
+    )
+    .unwrap();
+    assert_eq!(
+        res.messages,
+        vec![SubMsg::new(CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: "prism0001".to_string(),
+            msg: to_binary(&Cw20ExecuteMsg::Send {
+                contract: "staking0001".to_string(),
+                amount: Uint128::from(1_000_000u128),
+                msg: hook_msg,
+            })
+            .unwrap(),
+            funds: vec![],
+        }))]
+    );
+}
+
+#[test]
+fn proper_stake_tokens() {
+    let mut deps = mock_dependencies(&[]);
+    let msg = InstantiateMsg {
+        operator: "owner0001".to_string(),
+        receiver: "receiver0000".to_string(),
+        token: "prism0001".to_string(),
+        base_denom: "uusd".to_string(),
+        host_portion: Decimal::zero(),
+        host_portion_receiver: "host0000".to_string(),
+        unclaimed_tokens_receiver: "receiver0000".to_string(),
+        receipt_token: None,
+        position_nft: None,
+        staking_contract: Some("staking0000".to_string()),
+    };
+    let owner_info = mock_info("owner0001", &[]);
+    let mut env = mock_env();
+    instantiate(deps.as_mut(), env.clone(), owner_info.clone(), msg).unwrap();
+    post_init(&mut deps);
+
+    let config: ConfigResponse =
+        from_binary(&query(deps.as_ref(), env.clone(), QueryMsg::Config {}).unwrap()).unwrap();
+    assert_eq!(config.staking_contract, Some("staking0000".to_string()));
+
+    let info1 = mock_info("addr0001", &[Coin::new(1_000, "uusd")]);
+    do_deposit(deps.as_mut(), env.clone(), info1).unwrap();
+    let info2 = mock_info("addr0002", &[Coin::new(3_000, "uusd")]);
+    do_deposit(deps.as_mut(), env.clone(), info2).unwrap();
+
+    let info2 = mock_info("addr0002", &[]);
+    execute(
+        deps.as_mut(),
+        env.clone(),
+        info2,
+        ExecuteMsg::Authorize {
+            delegate: "custodian0000".to_string(),
+            permissions: vec![Permission::Claim],
+            expires: None,
+            transfer_recipients: vec![],
+        },
+    )
+    .unwrap();
+
+    // fast forward past phase 2 and release the tokens
+    env.block.time = env.block.time.plus_seconds(100 + SECONDS_PER_HOUR);
+    release_tokens(deps.as_mut(), env.clone(), owner_info).unwrap();
+
+    let stake_msg = |depositor: Option<&str>| ExecuteMsg::WithdrawTokens {
+        recipient: None,
+        depositor: depositor.map(|depositor| depositor.to_string()),
+        contract: None,
+        msg: None,
+        stake: true,
+    };
+    let mut staking = MockStaking::new("staking0000", "prism0001");
+
+    let err = execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("addr0001", &[]),
+        ExecuteMsg::WithdrawTokens {
+            recipient: None,
+            depositor: None,
+            contract: Some("staking0000".to_string()),
+            msg: Some(to_binary("bond").unwrap()),
+            stake: true,
+        },
+    )
+    .unwrap_err();
+    assert_eq!(
+        err,
+        ContractError::InvalidWithdrawTokens {
+            reason: "can not stake and send the tokens to a contract at once".to_string()
+        }
+    );
+
+    // the tokens are bonded to the recipient chosen by the depositor
+    let res = execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("addr0001", &[]),
+        ExecuteMsg::WithdrawTokens {
+            recipient: Some("vault0000".to_string()),
+            depositor: None,
+            contract: None,
+            msg: None,
+            stake: true,
+        },
+    )
+    .unwrap();
+    assert_eq!(res.messages.len(), 1);
+    staking
+        .execute(MOCK_CONTRACT_ADDR, &res.messages[0].msg)
+        .unwrap();
+    assert_eq!(staking.bonded("vault0000"), Uint128::from(250_000u128));
+    assert_eq!(staking.bonded("addr0001"), Uint128::zero());
+
+    // delegates can stake the tokens, they are still bonded to the depositor
+    let res = execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("custodian0000", &[]),
+        stake_msg(Some("addr0002")),
+    )
+    .unwrap();
+    staking
+        .execute(MOCK_CONTRACT_ADDR, &res.messages[0].msg)
+        .unwrap();
+    assert_eq!(staking.bonded("addr0002"), Uint128::from(750_000u128));
+    assert_eq!(staking.bonded("custodian0000"), Uint128::zero());
+}
+
+#[test]
+fn test_stake_without_staking_contract() {
+    let mut deps = mock_dependencies(&[]);
+    init(&mut deps);
+    post_init(&mut deps);
+
+    let mut env = mock_env();
+    let info1 = mock_info("addr0001", &[Coin::new(1_000, "uusd")]);
+    do_deposit(deps.as_mut(), env.clone(), info1).unwrap();
+
+    env.block.time = env.block.time.plus_seconds(100 + SECONDS_PER_HOUR);
+    let owner_info = mock_info("owner0001", &[]);
+    release_tokens(deps.as_mut(), env.clone(), owner_info).unwrap();
+
+    let err = execute(
+        deps.as_mut(),
+        env,
+        mock_info("addr0001", &[]),
+        ExecuteMsg::WithdrawTokens {
+            recipient: None,
+            depositor: None,
+            contract: None,
+            msg: None,
+            stake: true,
+        },
+    )
+    .unwrap_err();
+    assert_eq!(
+        err,
+        ContractError::InvalidWithdrawTokens {
+            reason: "launch has no staking contract".to_string()
+        }
+    );
+}